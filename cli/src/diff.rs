@@ -0,0 +1,512 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use redscript::bundle::{ConstantPool, PoolIndex};
+use redscript::definition::{AnyDefinition, Class, Definition, Field, Function};
+use redscript_decompiler::error::Error;
+use redscript_decompiler::print::{format_param, format_type};
+use redscript_decompiler::Decompiler;
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DefinitionKind {
+    Class,
+    Enum,
+    BitField,
+    Field,
+    Function,
+}
+
+impl fmt::Display for DefinitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefinitionKind::Class => f.write_str("class"),
+            DefinitionKind::Enum => f.write_str("enum"),
+            DefinitionKind::BitField => f.write_str("bitfield"),
+            DefinitionKind::Field => f.write_str("field"),
+            DefinitionKind::Function => f.write_str("function"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Removed,
+    Changed(Vec<String>),
+}
+
+#[derive(Debug)]
+pub struct Change {
+    pub kind: DefinitionKind,
+    pub name: String,
+    pub status: ChangeStatus,
+}
+
+impl Change {
+    pub fn to_json(&self) -> serde_json::Value {
+        let (change, details) = match &self.status {
+            ChangeStatus::Added => ("added", &[][..]),
+            ChangeStatus::Removed => ("removed", &[][..]),
+            ChangeStatus::Changed(details) => ("changed", details.as_slice()),
+        };
+        json!({
+            "kind": self.kind.to_string(),
+            "name": self.name,
+            "change": change,
+            "details": details,
+        })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            ChangeStatus::Added => write!(f, "+ {} {}", self.kind, self.name),
+            ChangeStatus::Removed => write!(f, "- {} {}", self.kind, self.name),
+            ChangeStatus::Changed(details) => {
+                write!(f, "~ {} {}", self.kind, self.name)?;
+                for detail in details {
+                    write!(f, "\n    {}", detail)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compares two pools by definition names and returns the differences ordered by kind and name.
+/// Functions are matched by their parent and unmangled name, so that a change of signature is
+/// reported as a change of the function rather than a removal and an addition.
+pub fn diff_pools(old: &ConstantPool, new: &ConstantPool, bytecode: bool) -> Result<Vec<Change>, Error> {
+    let old_defs = index_definitions(old)?;
+    let new_defs = index_definitions(new)?;
+    let mut changes = vec![];
+
+    for ((kind, name), old_group) in &old_defs {
+        let new_group = new_defs.get(&(*kind, name.clone())).map_or(&[][..], Vec::as_slice);
+        let (pairs, removed, added) = match_overloads(old_group, new_group);
+
+        for ((old_name, old_idx), (new_name, new_idx)) in pairs {
+            let details = DefinitionDiff { old, new, bytecode }.compare(*old_idx, *new_idx)?;
+            if details.is_empty() {
+                continue;
+            }
+            // overloads keep their full name unless the signature is what has changed
+            let name = if old_name == new_name { old_name } else { name };
+            changes.push(Change {
+                kind: *kind,
+                name: name.clone(),
+                status: ChangeStatus::Changed(details),
+            });
+        }
+        for (name, _) in removed {
+            changes.push(Change {
+                kind: *kind,
+                name: name.clone(),
+                status: ChangeStatus::Removed,
+            });
+        }
+        for (name, _) in added {
+            changes.push(Change {
+                kind: *kind,
+                name: name.clone(),
+                status: ChangeStatus::Added,
+            });
+        }
+    }
+    for ((kind, _), new_group) in new_defs.iter().filter(|(key, _)| !old_defs.contains_key(key)) {
+        for (name, _) in new_group {
+            changes.push(Change {
+                kind: *kind,
+                name: name.clone(),
+                status: ChangeStatus::Added,
+            });
+        }
+    }
+    changes.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+    Ok(changes)
+}
+
+type DefinitionKey = (DefinitionKind, String);
+
+// a definition together with its full name, which includes the signature for functions
+type NamedDefinition = (String, PoolIndex<Definition>);
+
+fn index_definitions(pool: &ConstantPool) -> Result<BTreeMap<DefinitionKey, Vec<NamedDefinition>>, Error> {
+    let mut index: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for entry in pool.definitions() {
+        let (idx, def) = entry?;
+        let (kind, name) = match &def.value {
+            AnyDefinition::Class(_) => (DefinitionKind::Class, pool.names.get(def.name)?.to_string()),
+            AnyDefinition::Enum(_) => (DefinitionKind::Enum, pool.names.get(def.name)?.to_string()),
+            AnyDefinition::BitField(_) => (DefinitionKind::BitField, pool.names.get(def.name)?.to_string()),
+            AnyDefinition::Field(_) => {
+                let name = format!("{}.{}", pool.def_name(def.parent)?, pool.names.get(def.name)?);
+                (DefinitionKind::Field, name)
+            }
+            AnyDefinition::Function(_) if def.parent.is_undefined() => {
+                (DefinitionKind::Function, pool.names.get(def.name)?.to_string())
+            }
+            AnyDefinition::Function(_) => {
+                let name = format!("{}::{}", pool.def_name(def.parent)?, pool.names.get(def.name)?);
+                (DefinitionKind::Function, name)
+            }
+            _ => continue,
+        };
+        let unmangled = name.split(';').next().unwrap_or(&name).to_owned();
+        index.entry((kind, unmangled)).or_default().push((name, idx));
+    }
+    Ok(index)
+}
+
+// pairs up the overloads with identical signatures first and then the remaining ones in order,
+// returns the pairs along with the overloads that were removed and added
+fn match_overloads<'a>(
+    old: &'a [NamedDefinition],
+    new: &'a [NamedDefinition],
+) -> (
+    Vec<(&'a NamedDefinition, &'a NamedDefinition)>,
+    Vec<&'a NamedDefinition>,
+    Vec<&'a NamedDefinition>,
+) {
+    let mut pairs = vec![];
+    let mut removed = vec![];
+    let mut added: Vec<_> = new.iter().collect();
+
+    for old_def in old {
+        match added.iter().position(|(name, _)| *name == old_def.0) {
+            Some(i) => pairs.push((old_def, added.remove(i))),
+            None => removed.push(old_def),
+        }
+    }
+    let paired = removed.len().min(added.len());
+    pairs.extend(removed.drain(..paired).zip(added.drain(..paired)));
+    (pairs, removed, added)
+}
+
+struct DefinitionDiff<'a> {
+    old: &'a ConstantPool,
+    new: &'a ConstantPool,
+    bytecode: bool,
+}
+
+impl<'a> DefinitionDiff<'a> {
+    fn compare(&self, old_idx: PoolIndex<Definition>, new_idx: PoolIndex<Definition>) -> Result<Vec<String>, Error> {
+        let old_def = self.old.definition(old_idx)?;
+        let new_def = self.new.definition(new_idx)?;
+        let mut details = vec![];
+
+        match (&old_def.value, &new_def.value) {
+            (AnyDefinition::Class(old), AnyDefinition::Class(new)) => self.compare_classes(old, new, &mut details)?,
+            (AnyDefinition::Enum(old), AnyDefinition::Enum(new)) => {
+                self.compare_members(&old.members, &new.members, &mut details)?;
+            }
+            (AnyDefinition::BitField(old), AnyDefinition::BitField(new)) => {
+                self.compare_members(&old.members, &new.members, &mut details)?;
+            }
+            (AnyDefinition::Field(old), AnyDefinition::Field(new)) => self.compare_fields(old, new, &mut details)?,
+            (AnyDefinition::Function(old), AnyDefinition::Function(new)) => {
+                self.compare_functions(old, new, &mut details)?;
+                if self.bytecode {
+                    self.compare_bytecode(old, new, &mut details);
+                }
+            }
+            _ => {}
+        }
+        Ok(details)
+    }
+
+    fn compare_classes(&self, old: &Class, new: &Class, details: &mut Vec<String>) -> Result<(), Error> {
+        changed("visibility", old.visibility, new.visibility, details);
+        changed("qualifiers", class_qualifiers(old), class_qualifiers(new), details);
+
+        let old_base = base_name(old.base, self.old)?;
+        let new_base = base_name(new.base, self.new)?;
+        changed("base class", old_base, new_base, details);
+        Ok(())
+    }
+
+    fn compare_members(
+        &self,
+        old: &[PoolIndex<i64>],
+        new: &[PoolIndex<i64>],
+        details: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        let old_members = enum_members(old, self.old)?;
+        let new_members = enum_members(new, self.new)?;
+
+        for (name, old_value) in &old_members {
+            match new_members.get(name) {
+                None => details.push(format!("member {} removed", name)),
+                Some(new_value) => changed(&format!("value of {}", name), old_value, new_value, details),
+            }
+        }
+        for name in new_members.keys().filter(|name| !old_members.contains_key(*name)) {
+            details.push(format!("member {} added", name));
+        }
+        Ok(())
+    }
+
+    fn compare_fields(&self, old: &Field, new: &Field, details: &mut Vec<String>) -> Result<(), Error> {
+        changed("visibility", old.visibility, new.visibility, details);
+        changed("qualifiers", field_qualifiers(old), field_qualifiers(new), details);
+
+        let old_type = format_type(self.old.definition(old.type_)?, self.old)?;
+        let new_type = format_type(self.new.definition(new.type_)?, self.new)?;
+        changed("type", old_type, new_type, details);
+        Ok(())
+    }
+
+    fn compare_functions(&self, old: &Function, new: &Function, details: &mut Vec<String>) -> Result<(), Error> {
+        changed("visibility", old.visibility, new.visibility, details);
+        changed(
+            "qualifiers",
+            function_qualifiers(old),
+            function_qualifiers(new),
+            details,
+        );
+
+        let old_params = parameters(old, self.old)?;
+        let new_params = parameters(new, self.new)?;
+        changed("parameters", old_params, new_params, details);
+
+        let old_return = return_type(old, self.old)?;
+        let new_return = return_type(new, self.new)?;
+        changed("return type", old_return, new_return, details);
+        Ok(())
+    }
+
+    fn compare_bytecode(&self, old: &Function, new: &Function, details: &mut Vec<String>) {
+        // pool indexes are not stable across bundles, so bodies are compared in their decompiled form
+        match (decompiled_body(old, self.old), decompiled_body(new, self.new)) {
+            (Ok(old_code), Ok(new_code)) if old_code == new_code => {}
+            (Ok(_), Ok(_)) => details.push(format!(
+                "bytecode changed ({} to {} instructions)",
+                old.code.0.len(),
+                new.code.0.len()
+            )),
+            (Err(err), _) | (_, Err(err)) => details.push(format!("bytecode could not be compared: {}", err)),
+        }
+    }
+}
+
+fn changed<A: PartialEq + fmt::Display>(what: &str, old: A, new: A, details: &mut Vec<String>) {
+    if old != new {
+        details.push(format!("{} changed from {} to {}", what, old, new));
+    }
+}
+
+fn base_name(base: PoolIndex<Class>, pool: &ConstantPool) -> Result<String, Error> {
+    if base.is_undefined() {
+        Ok("none".to_owned())
+    } else {
+        Ok(pool.def_name(base)?.to_string())
+    }
+}
+
+fn enum_members(indexes: &[PoolIndex<i64>], pool: &ConstantPool) -> Result<BTreeMap<String, i64>, Error> {
+    let mut members = BTreeMap::new();
+    for member in indexes {
+        members.insert(pool.def_name(*member)?.to_string(), pool.enum_value(*member)?);
+    }
+    Ok(members)
+}
+
+fn parameters(fun: &Function, pool: &ConstantPool) -> Result<String, Error> {
+    let params = fun
+        .parameters
+        .iter()
+        .map(|param| format_param(pool.definition(*param)?, pool))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!("({})", params.join(", ")))
+}
+
+fn return_type(fun: &Function, pool: &ConstantPool) -> Result<String, Error> {
+    match fun.return_type {
+        Some(type_) => format_type(pool.definition(type_)?, pool),
+        None => Ok("Void".to_owned()),
+    }
+}
+
+// renders the syntax tree of a body, which leaves out the signature
+fn decompiled_body(fun: &Function, pool: &ConstantPool) -> Result<String, Error> {
+    Ok(format!("{:?}", Decompiler::decompiled(fun, pool)?.exprs))
+}
+
+fn class_qualifiers(class: &Class) -> Qualifiers {
+    let flags = &class.flags;
+    Qualifiers::of(&[
+        (flags.is_native(), "native"),
+        (flags.is_abstract(), "abstract"),
+        (flags.is_final(), "final"),
+        (flags.is_struct(), "struct"),
+        (flags.is_import_only(), "importonly"),
+        (flags.is_test_only(), "testonly"),
+    ])
+}
+
+fn field_qualifiers(field: &Field) -> Qualifiers {
+    let flags = &field.flags;
+    Qualifiers::of(&[
+        (flags.is_native(), "native"),
+        (flags.is_editable(), "edit"),
+        (flags.is_inline(), "inline"),
+        (flags.is_const(), "const"),
+        (flags.is_replicated(), "rep"),
+        (flags.is_instance_editable(), "instanceedit"),
+        (flags.is_persistent(), "persistent"),
+        (flags.is_test_only(), "testonly"),
+        (flags.is_browsable(), "browsable"),
+    ])
+}
+
+fn function_qualifiers(fun: &Function) -> Qualifiers {
+    let flags = &fun.flags;
+    Qualifiers::of(&[
+        (flags.is_static(), "static"),
+        (flags.is_exec(), "exec"),
+        (flags.is_timer(), "timer"),
+        (flags.is_final(), "final"),
+        (flags.is_native(), "native"),
+        (flags.is_callback(), "cb"),
+        (flags.is_operator(), "operator"),
+        (flags.is_cast(), "cast"),
+        (flags.is_implicit_cast(), "implicit"),
+        (flags.is_const(), "const"),
+        (flags.is_thread_safe(), "threadsafe"),
+        (flags.is_quest(), "quest"),
+        (flags.is_test_only(), "testonly"),
+    ])
+}
+
+#[derive(PartialEq, Eq)]
+struct Qualifiers(Vec<&'static str>);
+
+impl Qualifiers {
+    fn of(flags: &[(bool, &'static str)]) -> Self {
+        Qualifiers(flags.iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect())
+    }
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.0.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redscript::bundle::ScriptBundle;
+    use redscript::bytecode::{Code, Instr, Offset};
+    use redscript_compiler::source_map::Files;
+    use redscript_compiler::unit::CompilationUnit;
+
+    use super::*;
+
+    const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+    fn compiled(source: &str) -> ConstantPool {
+        let mut pool = ScriptBundle::load(&mut std::io::Cursor::new(PREDEF)).unwrap().pool;
+        let mut files = Files::new();
+        files.add("test.reds".into(), source.to_owned());
+        CompilationUnit::new(&mut pool)
+            .unwrap()
+            .compile_and_report(&files)
+            .unwrap();
+        pool
+    }
+
+    #[test]
+    fn diff_changed_definitions() {
+        let old = compiled(
+            "
+            class Base {}
+            class A { let x: Int32; func Get() -> Int32 { return this.x; } }
+            func Removed() {}
+            ",
+        );
+        let new = compiled(
+            "
+            class Base {}
+            class A extends Base { persistent let x: Float; final func Get() -> Float { let y = this.x; return y; } }
+            func Added(a: Int32) {}
+            ",
+        );
+        let changes: Vec<_> = diff_pools(&old, &new, true)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(changes, vec![
+            "~ class A\n    base class changed from none to Base",
+            "~ field A.x\n    qualifiers changed from [browsable] to [persistent, browsable]\n    type changed from Int32 to Float",
+            "~ function A::Get;\n    qualifiers changed from [] to [final]\n    return type changed from Int32 to Float\n    bytecode changed (5 to 8 instructions)",
+            "+ function Added;Int32",
+            "- function Removed;",
+        ]);
+    }
+
+    #[test]
+    fn diff_function_bodies() {
+        let old = compiled(
+            "
+            func Same(a: Int32) -> Int32 { return a; }
+            func Signature(a: Int32) -> Int32 { return 1; }
+            func Broken() -> Int32 { return 1; }
+            ",
+        );
+        let mut new = compiled(
+            "
+            func Same(a: Int32) -> Int32 { return 1; }
+            func Signature(a: Float) -> Int32 { return 1; }
+            func Broken() -> Int32 { return 1; }
+            ",
+        );
+        let (broken, _) = new
+            .definitions()
+            .map(Result::unwrap)
+            .find(|(idx, _)| new.def_name(*idx).unwrap().starts_with("Broken;"))
+            .unwrap();
+        new.function_mut(broken.cast()).unwrap().code = Code(vec![Instr::Jump(Offset::new(100))]);
+
+        let changes: Vec<_> = diff_pools(&old, &new, true)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(changes, vec![
+            "~ function Broken;\n    bytecode could not be compared: multiple errors",
+            "~ function Same;Int32\n    bytecode changed (3 to 3 instructions)",
+            "~ function Signature\n    parameters changed from (a: Int32) to (a: Float)",
+        ]);
+    }
+
+    #[test]
+    fn diff_changed_parameter_type() {
+        let old = compiled(
+            "
+            class A { func Set(a: Int32) {} func Set(a: String) {} }
+            func Take(a: Int32, b: Bool) {}
+            ",
+        );
+        let new = compiled(
+            "
+            class A { func Set(a: Float) {} func Set(a: String) {} }
+            func Take(a: Int32, b: Float) {}
+            ",
+        );
+        let changes: Vec<_> = diff_pools(&old, &new, false)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(changes, vec![
+            "~ function A::Set\n    parameters changed from (a: Int32) to (a: Float)",
+            "~ function Take\n    parameters changed from (a: Int32, b: Bool) to (a: Int32, b: Float)",
+        ]);
+    }
+}
@@ -1,17 +1,25 @@
 use std::fs::File;
-use std::io;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use fern::colors::ColoredLevelConfig;
 use gumdrop::Options;
-use redscript::bundle::ScriptBundle;
-use redscript::definition::AnyDefinition;
+use redscript::bundle::{ConstantPool, DefinitionType, PoolError, PoolIndex, ScriptBundle};
+use redscript::definition::{AnyDefinition, Class, Definition, Function};
+use redscript_compiler::error::Error;
+use redscript_compiler::lint::{Lint, LintConfig};
 use redscript_compiler::source_map::{Files, SourceFilter};
-use redscript_compiler::unit::CompilationUnit;
+use redscript_compiler::symbol::ModulePath;
+use redscript_compiler::unit::{CompilationUnit, Diagnostic};
+use redscript_compiler::{asm, formatter};
 use redscript_decompiler::files::FileIndex;
 use redscript_decompiler::print::{write_definition, OutputMode};
+use serde_json::json;
 use vmap::Map;
 
+mod diff;
+
 #[derive(Debug, Options)]
 enum Command {
     #[options(help = "[opts]")]
@@ -20,15 +28,41 @@ enum Command {
     Compile(CompileOpts),
     #[options(help = "[opts]")]
     Lint(LintOpts),
+    #[options(help = "[opts]")]
+    Fmt(FmtOpts),
+    #[options(help = "[opts]")]
+    Diff(DiffOpts),
+    #[options(help = "[opts]")]
+    Verify(VerifyOpts),
+    #[options(help = "[opts]")]
+    Info(InfoOpts),
+    #[options(help = "[opts]")]
+    Dump(DumpOpts),
+    #[options(help = "[opts]")]
+    Pack(PackOpts),
+    #[options(help = "[opts]")]
+    Asm(AsmOpts),
+    #[options(help = "[opts]")]
+    Disasm(DisasmOpts),
 }
 
 #[derive(Debug, Options)]
 struct DecompileOpts {
     #[options(required, short = "i", help = "input redscripts bundle file")]
     input: PathBuf,
-    #[options(required, short = "o", help = "output file or directory")]
-    output: PathBuf,
-    #[options(short = "m", help = "dump mode (one of: 'ast', 'bytecode' or 'code')")]
+    #[options(short = "o", help = "output file or directory")]
+    output: Option<PathBuf>,
+    #[options(no_short, help = "class, enum or bitfield to print to stdout, e.g. 'PlayerPuppet'")]
+    class: Option<String>,
+    #[options(
+        no_short,
+        help = "function to print to stdout, e.g. 'Class::Method' or 'Class::Method;Int32' for a single overload"
+    )]
+    function: Option<String>,
+    #[options(
+        short = "m",
+        help = "dump mode (one of: 'ast', 'bytecode', 'code' or 'recompilable')"
+    )]
     mode: String,
     #[options(short = "f", help = "split output into individual files")]
     dump_files: bool,
@@ -44,6 +78,16 @@ struct CompileOpts {
     bundle: PathBuf,
     #[options(required, short = "o", help = "redscript bundle file to write")]
     output: PathBuf,
+    #[options(default = "text", help = "diagnostics format (one of: 'text' or 'json')")]
+    format: DiagnosticFormat,
+    #[options(
+        no_short,
+        meta = "LINT",
+        help = "disable a lint, e.g. 'unused-local' (can be repeated)"
+    )]
+    allow: Vec<Lint>,
+    #[options(no_short, help = "verify the bytecode of the compiled functions")]
+    verify: bool,
 }
 
 #[derive(Debug, Options)]
@@ -52,25 +96,139 @@ struct LintOpts {
     src: PathBuf,
     #[options(short = "b", help = "redscript bundle file to use, optional")]
     bundle: Option<PathBuf>,
+    #[options(default = "text", help = "diagnostics format (one of: 'text' or 'json')")]
+    format: DiagnosticFormat,
+    #[options(
+        no_short,
+        meta = "LINT",
+        help = "disable a lint, e.g. 'unused-local' (can be repeated)"
+    )]
+    allow: Vec<Lint>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    setup_logger();
+#[derive(Debug, Options)]
+struct FmtOpts {
+    #[options(required, short = "s", help = "source file or directory")]
+    src: PathBuf,
+    #[options(help = "fail when files are not formatted instead of rewriting them")]
+    check: bool,
+}
+
+#[derive(Debug, Options)]
+struct DiffOpts {
+    #[options(required, short = "a", help = "old redscripts bundle file")]
+    old: PathBuf,
+    #[options(required, short = "b", help = "new redscripts bundle file")]
+    new: PathBuf,
+    #[options(help = "compare function bodies as well")]
+    bytecode: bool,
+    #[options(default = "text", help = "output format (one of: 'text' or 'json')")]
+    format: DiagnosticFormat,
+}
 
+#[derive(Debug, Options)]
+struct VerifyOpts {
+    #[options(required, short = "i", help = "input redscripts bundle file")]
+    input: PathBuf,
+    #[options(help = "verify the bytecode of every function as well")]
+    bytecode: bool,
+}
+
+#[derive(Debug, Options)]
+struct InfoOpts {
+    #[options(required, short = "i", help = "input redscripts bundle file")]
+    input: PathBuf,
+}
+
+#[derive(Debug, Options)]
+struct DumpOpts {
+    #[options(required, short = "i", help = "input redscripts bundle file")]
+    input: PathBuf,
+    #[options(required, short = "o", help = "output file")]
+    output: PathBuf,
+    #[options(default = "json", help = "output format (one of: 'json' or 'yaml')")]
+    format: DumpFormat,
+}
+
+#[derive(Debug, Options)]
+struct PackOpts {
+    #[options(required, short = "i", help = "input file created by the dump command")]
+    input: PathBuf,
+    #[options(required, short = "o", help = "redscripts bundle file to write")]
+    output: PathBuf,
+    #[options(default = "json", help = "input format (one of: 'json' or 'yaml')")]
+    format: DumpFormat,
+}
+
+#[derive(Debug, Options)]
+struct AsmOpts {
+    #[options(required, short = "s", help = "assembly listing file")]
+    src: PathBuf,
+    #[options(required, short = "b", help = "redscript bundle file to use")]
+    bundle: PathBuf,
+    #[options(required, short = "o", help = "redscript bundle file to write")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Options)]
+struct DisasmOpts {
+    #[options(required, short = "i", help = "input redscripts bundle file")]
+    input: PathBuf,
+    #[options(required, short = "f", help = "function to disassemble, e.g. 'Class::Method;Int32'")]
+    function: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiagnosticFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for DiagnosticFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "text" => Ok(DiagnosticFormat::Text),
+            "json" => Ok(DiagnosticFormat::Json),
+            other => Err(format!("invalid diagnostics format: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DumpFormat {
+    Json,
+    Yaml,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "json" => Ok(DumpFormat::Json),
+            "yaml" => Ok(DumpFormat::Yaml),
+            other => Err(format!("invalid dump format: {}", other)),
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     run().map_err(|err| {
         log::error!("{}", err);
         err
     })
 }
 
-fn setup_logger() {
+fn setup_logger(output: impl Into<fern::Output>) {
     let colors = ColoredLevelConfig::new();
     fern::Dispatch::new()
         .format(move |out, message, rec| {
             out.finish(format_args!("[{}] {}", colors.color(rec.level()), message));
         })
         .level(log::LevelFilter::Info)
-        .chain(io::stdout())
+        .chain(output)
         .apply()
         .expect("Failed to initialize the logger");
 }
@@ -80,6 +238,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     let command: Command = match Command::parse_args_default(&args) {
         Ok(res) => res,
         Err(err) => {
+            setup_logger(io::stdout());
             log::info!(
                 "{} \n\
                  Usage: \n\
@@ -89,31 +248,82 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                  Decompiler options: \n\
                  {} \n\
                  Lint options \n\
+                 {} \n\
+                 Formatter options \n\
+                 {} \n\
+                 Diff options \n\
+                 {} \n\
+                 Verify options \n\
+                 {} \n\
+                 Info options \n\
+                 {} \n\
+                 Dump options \n\
+                 {} \n\
+                 Pack options \n\
+                 {} \n\
+                 Assembler options \n\
+                 {} \n\
+                 Disassembler options \n\
                  {}",
                 err,
                 Command::usage(),
                 CompileOpts::usage(),
                 DecompileOpts::usage(),
-                LintOpts::usage()
+                LintOpts::usage(),
+                FmtOpts::usage(),
+                DiffOpts::usage(),
+                VerifyOpts::usage(),
+                InfoOpts::usage(),
+                DumpOpts::usage(),
+                PackOpts::usage(),
+                AsmOpts::usage(),
+                DisasmOpts::usage()
             );
             return Ok(());
         }
     };
 
+    match &command {
+        // stdout is reserved for the diagnostics or the decompiled definitions
+        Command::Decompile(DecompileOpts { class: Some(_), .. })
+        | Command::Decompile(DecompileOpts { function: Some(_), .. })
+        | Command::Compile(CompileOpts {
+            format: DiagnosticFormat::Json,
+            ..
+        })
+        | Command::Lint(LintOpts {
+            format: DiagnosticFormat::Json,
+            ..
+        })
+        | Command::Diff(DiffOpts {
+            format: DiagnosticFormat::Json,
+            ..
+        }) => setup_logger(io::stderr()),
+        _ => setup_logger(io::stdout()),
+    }
+
     match command {
         Command::Decompile(opts) => decompile(opts)?,
         Command::Compile(opts) => compile(opts)?,
         Command::Lint(opts) => lint(opts)?,
+        Command::Fmt(opts) => fmt(opts)?,
+        Command::Diff(opts) => diff(opts)?,
+        Command::Verify(opts) => verify(opts)?,
+        Command::Info(opts) => info(opts)?,
+        Command::Dump(opts) => dump(opts)?,
+        Command::Pack(opts) => pack(opts)?,
+        Command::Asm(opts) => asm(opts)?,
+        Command::Disasm(opts) => disasm(opts)?,
     }
     Ok(())
 }
 
-fn compile(opts: CompileOpts) -> Result<(), redscript_compiler::error::Error> {
+fn compile(opts: CompileOpts) -> Result<(), Error> {
     let mut bundle = load_bundle(&opts.bundle)?;
 
     let files = Files::from_dir(&opts.src, SourceFilter::None)?;
 
-    match CompilationUnit::new(&mut bundle.pool)?.compile_and_report(&files) {
+    match compile_files(&mut bundle.pool, &files, opts.format, &opts.allow, opts.verify) {
         Ok(()) => {
             bundle.save(&mut io::BufWriter::new(File::create(&opts.output)?))?;
             log::info!("Output successfully saved to {}", opts.output.display());
@@ -125,19 +335,45 @@ fn compile(opts: CompileOpts) -> Result<(), redscript_compiler::error::Error> {
     Ok(())
 }
 
-fn decompile(opts: DecompileOpts) -> Result<(), redscript_decompiler::error::Error> {
-    let bundle = load_bundle(&opts.input)?;
+fn decompile(opts: DecompileOpts) -> Result<(), Box<dyn std::error::Error>> {
+    let bundle = load_bundle_lazy(&opts.input)?;
     let pool = &bundle.pool;
 
     let mode = match opts.mode.as_str() {
         "ast" => OutputMode::SyntaxTree,
         "bytecode" => OutputMode::Bytecode,
+        "recompilable" => OutputMode::Recompilable,
         _ => OutputMode::Code { verbose: opts.verbose },
     };
 
+    if opts.class.is_some() || opts.function.is_some() {
+        let mut definitions: Vec<PoolIndex<Definition>> = vec![];
+        if let Some(name) = &opts.class {
+            let type_ = find_type(pool, name)?.ok_or_else(|| format!("Type {} not found", name))?;
+            definitions.push(type_);
+        }
+        if let Some(name) = &opts.function {
+            let functions = find_functions(pool, name)?;
+            if functions.is_empty() {
+                return Err(format!("Function {} not found", name).into());
+            }
+            definitions.extend(functions.into_iter().map(|index| index.cast()));
+        }
+
+        let mut output = io::stdout().lock();
+        for index in definitions {
+            write_definition(&mut output, pool.definition(index)?, pool, 0, mode)?;
+            writeln!(output)?;
+        }
+        return Ok(());
+    }
+    let output_path = opts
+        .output
+        .ok_or("An output path is required to decompile the whole bundle")?;
+
     if opts.dump_files {
-        for entry in FileIndex::from_pool(pool).iter() {
-            let path = opts.output.as_path().join(&entry.path);
+        for entry in FileIndex::from_pool(pool)?.iter() {
+            let path = output_path.as_path().join(entry.path);
 
             std::fs::create_dir_all(path.parent().unwrap())?;
             let mut output = io::BufWriter::new(File::create(path)?);
@@ -148,33 +384,104 @@ fn decompile(opts: DecompileOpts) -> Result<(), redscript_decompiler::error::Err
             }
         }
     } else {
-        let mut output = io::BufWriter::new(File::create(&opts.output)?);
+        let mut output = io::BufWriter::new(File::create(&output_path)?);
 
-        for (_, def) in pool.roots().filter(|(_, def)| {
-            matches!(&def.value, AnyDefinition::Class(_))
-                || matches!(&def.value, AnyDefinition::Enum(_))
-                || matches!(&def.value, AnyDefinition::Function(_))
-        }) {
+        for entry in pool.roots() {
+            let (_, def) = entry?;
+            if !matches!(
+                &def.value,
+                AnyDefinition::Class(_)
+                    | AnyDefinition::Enum(_)
+                    | AnyDefinition::BitField(_)
+                    | AnyDefinition::Function(_)
+            ) {
+                continue;
+            }
             if let Err(err) = write_definition(&mut output, def, pool, 0, mode) {
                 log::error!("Failed to process definition at {:?}: {}", def, err);
             }
         }
     }
-    log::info!("Output successfully saved to {}", opts.output.display());
+    log::info!("Output successfully saved to {}", output_path.display());
     Ok(())
 }
 
-fn lint(opts: LintOpts) -> Result<(), redscript_compiler::error::Error> {
+/// Looks up a class, an enum or a bitfield by its name, which can include a module path, e.g. `Module.Class`.
+fn find_type(pool: &ConstantPool, name: &str) -> Result<Option<PoolIndex<Definition>>, PoolError> {
+    find_root(pool, name, |type_| {
+        matches!(
+            type_,
+            DefinitionType::Class | DefinitionType::Enum | DefinitionType::BitField
+        )
+    })
+}
+
+/// Looks up a class by its name, which can include a module path, e.g. `Module.Class`.
+fn find_class(pool: &ConstantPool, name: &str) -> Result<Option<PoolIndex<Class>>, PoolError> {
+    let index = find_root(pool, name, |type_| type_ == DefinitionType::Class)?;
+    Ok(index.map(|index| index.cast()))
+}
+
+fn find_root(
+    pool: &ConstantPool,
+    name: &str,
+    is_match: impl Fn(DefinitionType) -> bool,
+) -> Result<Option<PoolIndex<Definition>>, PoolError> {
+    let path = ModulePath::parse(name);
+    // compare the names first to avoid decoding the definitions of lazily loaded pools
+    for (index, type_) in pool.definition_types() {
+        if is_match(type_)
+            && ModulePath::parse(&pool.def_name(index)?) == path
+            && pool.definition(index)?.parent.is_undefined()
+        {
+            return Ok(Some(index));
+        }
+    }
+    Ok(None)
+}
+
+/// Looks up the overloads of a global function or a method, e.g. `Function` or `Class::Method`.
+/// A signature like `Class::Method;Int32` selects a single overload.
+fn find_functions(pool: &ConstantPool, name: &str) -> Result<Vec<PoolIndex<Function>>, PoolError> {
+    let matches = |query: &str, name: &str| {
+        if query.contains(';') {
+            // natives are mangled with their return type as well
+            name == query || name.strip_prefix(query).is_some_and(|rest| rest.starts_with(';'))
+        } else {
+            ModulePath::parse(query) == ModulePath::parse(name)
+        }
+    };
+
+    let mut functions = vec![];
+    if let Some((class_name, method)) = name.rsplit_once("::") {
+        if let Some(class) = find_class(pool, class_name)? {
+            for index in &pool.class(class)?.functions {
+                if matches(method, &pool.def_name(*index)?) {
+                    functions.push(*index);
+                }
+            }
+        }
+    } else {
+        for (index, type_) in pool.definition_types() {
+            if type_ == DefinitionType::Function
+                && matches(name, &pool.def_name(index)?)
+                && pool.definition(index)?.parent.is_undefined()
+            {
+                functions.push(index.cast());
+            }
+        }
+    }
+    Ok(functions)
+}
+
+fn lint(opts: LintOpts) -> Result<(), Error> {
     match opts.bundle {
         Some(bundle_path) => {
-            let mut bundle = load_bundle(&bundle_path)?;
+            let mut bundle = load_bundle_lazy(&bundle_path)?;
 
             let files = Files::from_dir(&opts.src, SourceFilter::None)?;
 
-            if CompilationUnit::new(&mut bundle.pool)?
-                .compile_and_report(&files)
-                .is_ok()
-            {
+            if compile_files(&mut bundle.pool, &files, opts.format, &opts.allow, false).is_ok() {
                 log::info!("Lint successful");
             }
             Ok(())
@@ -183,10 +490,344 @@ fn lint(opts: LintOpts) -> Result<(), redscript_compiler::error::Error> {
     }
 }
 
+fn fmt(opts: FmtOpts) -> Result<(), Error> {
+    let files = Files::from_dir(&opts.src, SourceFilter::None)?;
+    let mut failures = vec![];
+
+    for file in files.files() {
+        match formatter::format_file(file) {
+            Ok(formatted) if formatted == file.source() => {}
+            Ok(_) if opts.check => {
+                log::error!("{} is not formatted", file.path().display());
+                failures.push(file.span());
+            }
+            Ok(formatted) => {
+                std::fs::write(file.path(), formatted)?;
+                log::info!("Formatted {}", file.path().display());
+            }
+            Err(Error::SyntaxError(msg, span)) => {
+                let loc = files.lookup(span).expect("Unknown file");
+                log::error!("At {}:\n {}", loc, msg);
+                failures.push(span);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::MultipleErrors(failures))
+    }
+}
+
+fn diff(opts: DiffOpts) -> Result<(), redscript_decompiler::error::Error> {
+    let old = load_bundle(&opts.old)?;
+    let new = load_bundle(&opts.new)?;
+    let changes = diff::diff_pools(&old.pool, &new.pool, opts.bytecode)?;
+
+    match opts.format {
+        DiagnosticFormat::Text => {
+            for change in &changes {
+                println!("{}", change);
+            }
+            log::info!("Found {} changed definitions", changes.len());
+        }
+        DiagnosticFormat::Json => {
+            let json = changes.iter().map(diff::Change::to_json).collect();
+            println!("{}", serde_json::Value::Array(json));
+        }
+    }
+    Ok(())
+}
+
+fn verify(opts: VerifyOpts) -> Result<(), Box<dyn std::error::Error>> {
+    let map = map_file(&opts.input)?;
+    let report = redscript::verify::verify(map.as_ref());
+
+    for issue in &report.issues {
+        log::error!("{}", issue);
+    }
+    let mut issue_count = report.issues.len();
+    if opts.bytecode && report.is_valid() {
+        let bundle = ScriptBundle::load(&mut io::Cursor::new(map.as_ref()))?;
+        issue_count += verify_bytecode(&bundle.pool)?;
+    }
+
+    if issue_count == 0 {
+        log::info!("{} is valid ({} definitions)", opts.input.display(), report.definitions);
+        Ok(())
+    } else {
+        let msg = format!("Found {} issues in {}", issue_count, opts.input.display());
+        Err(msg.into())
+    }
+}
+
+fn verify_bytecode(pool: &ConstantPool) -> Result<usize, PoolError> {
+    let mut issue_count = 0;
+    for entry in pool.definitions() {
+        let (index, def) = entry?;
+        if let AnyDefinition::Function(_) = def.value {
+            let index = index.cast();
+            for issue in redscript::verify::verify_code(pool, index)? {
+                log::error!("{}: {}", pool.def_name(index)?, issue);
+                issue_count += 1;
+            }
+        }
+    }
+    Ok(issue_count)
+}
+
+fn info(opts: InfoOpts) -> Result<(), io::Error> {
+    let bundle = load_bundle_lazy(&opts.input)?;
+    let pool = &bundle.pool;
+    let count = |expected| pool.definition_types().filter(|(_, type_)| *type_ == expected).count();
+
+    log::info!("{}", opts.input.display());
+    log::info!("Version: {}", bundle.version());
+    log::info!("Classes: {}", count(DefinitionType::Class));
+    log::info!("Functions: {}", count(DefinitionType::Function));
+    log::info!("Enums: {}", count(DefinitionType::Enum));
+    log::info!("Strings: {}", pool.strings.strings.len());
+    Ok(())
+}
+
+fn dump(opts: DumpOpts) -> Result<(), Box<dyn std::error::Error>> {
+    let bundle = load_bundle(&opts.input)?;
+    let output = io::BufWriter::new(File::create(&opts.output)?);
+    match opts.format {
+        DumpFormat::Json => serde_json::to_writer_pretty(output, &bundle)?,
+        DumpFormat::Yaml => serde_yaml::to_writer(output, &bundle)?,
+    }
+    log::info!("Output successfully saved to {}", opts.output.display());
+    Ok(())
+}
+
+fn pack(opts: PackOpts) -> Result<(), Box<dyn std::error::Error>> {
+    let input = io::BufReader::new(File::open(&opts.input)?);
+    let bundle: ScriptBundle = match opts.format {
+        DumpFormat::Json => serde_json::from_reader(input)?,
+        DumpFormat::Yaml => serde_yaml::from_reader(input)?,
+    };
+    bundle.save(&mut io::BufWriter::new(File::create(&opts.output)?))?;
+    log::info!("Output successfully saved to {}", opts.output.display());
+    Ok(())
+}
+
+fn asm(opts: AsmOpts) -> Result<(), Box<dyn std::error::Error>> {
+    let mut bundle = load_bundle(&opts.bundle)?;
+    let source = std::fs::read_to_string(&opts.src)?;
+    let functions = asm::assemble(&source, &mut bundle.pool)?;
+
+    bundle.save(&mut io::BufWriter::new(File::create(&opts.output)?))?;
+    log::info!("Assembled {} functions", functions.len());
+    log::info!("Output successfully saved to {}", opts.output.display());
+    Ok(())
+}
+
+fn disasm(opts: DisasmOpts) -> Result<(), Box<dyn std::error::Error>> {
+    let bundle = load_bundle_lazy(&opts.input)?;
+    let function = asm::find_function(&bundle.pool, &opts.function)?
+        .ok_or_else(|| format!("Function {} not found", opts.function))?;
+    print!("{}", asm::disassemble(&bundle.pool, function)?);
+    Ok(())
+}
+
+fn compile_files(
+    pool: &mut ConstantPool,
+    files: &Files,
+    format: DiagnosticFormat,
+    allow: &[Lint],
+    verify: bool,
+) -> Result<(), Error> {
+    let lints = allow
+        .iter()
+        .fold(LintConfig::default(), |lints, lint| lints.allow(*lint));
+    let unit = CompilationUnit::new(pool)?
+        .with_lints(lints)
+        .with_bytecode_verification(verify);
+
+    match format {
+        DiagnosticFormat::Text => unit.compile_and_report(files),
+        DiagnosticFormat::Json => {
+            let diagnostics = unit.compile_and_collect(files)?;
+            let json: Vec<_> = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic_json(diagnostic, files))
+                .collect();
+            println!("{}", serde_json::Value::Array(json));
+
+            if diagnostics.iter().any(Diagnostic::is_fatal) {
+                Err(Error::MultipleErrors(
+                    diagnostics.iter().map(Diagnostic::span).collect(),
+                ))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+// diagnostics that can't be traced back to a source file are reported without a location
+fn diagnostic_json(diagnostic: &Diagnostic, files: &Files) -> serde_json::Value {
+    match diagnostic.locate(files) {
+        Some(located) => json!({
+            "code": located.code,
+            "severity": located.severity.to_string(),
+            "message": located.message,
+            "file": located.path,
+            "start": { "line": located.start.line + 1, "column": located.start.col + 1 },
+            "end": { "line": located.end.line + 1, "column": located.end.col + 1 },
+        }),
+        None => json!({
+            "code": diagnostic.code(),
+            "severity": diagnostic.severity().to_string(),
+            "message": diagnostic.message(),
+        }),
+    }
+}
+
 fn load_bundle(path: &Path) -> Result<ScriptBundle, io::Error> {
+    let map = map_file(path)?;
+    let mut reader = io::Cursor::new(map.as_ref());
+    ScriptBundle::load(&mut reader)
+}
+
+/// Loads a bundle that decodes its definitions from the mapped file when they're first used.
+fn load_bundle_lazy(path: &Path) -> Result<ScriptBundle, io::Error> {
+    ScriptBundle::load_lazy(map_file(path)?)
+}
+
+fn map_file(path: &Path) -> Result<Map, io::Error> {
     let (map, _) = Map::with_options()
         .open(path)
         .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-    let mut reader = io::Cursor::new(map.as_ref());
-    ScriptBundle::load(&mut reader)
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use redscript::ast::Span;
+    use redscript_compiler::source_map::Files;
+
+    use super::*;
+
+    const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+    #[test]
+    fn dump_and_pack_bundles() {
+        let mut bundle = ScriptBundle::load(&mut io::Cursor::new(PREDEF)).unwrap();
+        let mut files = Files::new();
+        let source = "
+            enum Direction { Up = 0, Down = 1 }
+            class Counter {
+                let count: Int32;
+                func Add(by: Int32, const dir: Direction) -> Float {
+                    let msg = \"added\";
+                    switch dir {
+                        case Direction.Up:
+                            this.count = by;
+                            break;
+                        default:
+                            this.count = 0;
+                    }
+                    return 1.5;
+                }
+            }
+            ";
+        files.add("test.reds".into(), source.to_owned());
+        CompilationUnit::new(&mut bundle.pool)
+            .unwrap()
+            .compile_and_report(&files)
+            .unwrap();
+
+        let mut expected = io::Cursor::new(Vec::new());
+        bundle.save(&mut expected).unwrap();
+        let expected = ScriptBundle::load(&mut io::Cursor::new(expected.into_inner())).unwrap();
+        let mut original = io::Cursor::new(Vec::new());
+        expected.save(&mut original).unwrap();
+
+        let json = serde_json::to_string(&expected).unwrap();
+        let yaml = serde_yaml::to_string(&expected).unwrap();
+        for bundle in [
+            serde_json::from_str::<ScriptBundle>(&json).unwrap(),
+            serde_yaml::from_str::<ScriptBundle>(&yaml).unwrap(),
+        ] {
+            let mut packed = io::Cursor::new(Vec::new());
+            bundle.save(&mut packed).unwrap();
+            assert_eq!(packed.get_ref(), original.get_ref());
+        }
+    }
+
+    #[test]
+    fn find_classes_and_functions() {
+        let mut pool = ScriptBundle::load(&mut io::Cursor::new(PREDEF)).unwrap().pool;
+        let mut files = Files::new();
+        let module = "
+            module Test.Things
+            public class Counter {
+                func Add(by: Int32) {}
+                func Add(by: Float) {}
+            }
+            enum Direction {
+                Left = 0,
+                Right = 1
+            }
+            func Describe(counter: ref<Counter>) {}
+            ";
+        files.add("module.reds".into(), module.to_owned());
+        files.add(
+            "natives.reds".into(),
+            "native func OperatorGreater(l: Int32, r: Int32) -> Bool".to_owned(),
+        );
+        CompilationUnit::new(&mut pool)
+            .unwrap()
+            .compile_and_report(&files)
+            .unwrap();
+
+        let names = |name: &str| -> Vec<String> {
+            find_functions(&pool, name)
+                .unwrap()
+                .into_iter()
+                .map(|index| pool.def_name(index).unwrap().to_string())
+                .collect()
+        };
+
+        let class = find_class(&pool, "Test.Things.Counter").unwrap().unwrap();
+        assert_eq!(pool.def_name(class).unwrap().as_str(), "Test.Things.Counter");
+        assert!(find_class(&pool, "Counter").unwrap().is_none());
+        assert_eq!(find_type(&pool, "Test.Things.Counter").unwrap(), Some(class.cast()));
+
+        let enum_ = find_type(&pool, "Test.Things.Direction").unwrap().unwrap();
+        assert!(pool.enum_(enum_.cast()).is_ok());
+        assert!(find_class(&pool, "Test.Things.Direction").unwrap().is_none());
+
+        assert_eq!(names("Test.Things.Counter::Add"), vec!["Add;Int32", "Add;Float"]);
+        assert_eq!(names("Test.Things.Counter::Add;Int32"), vec!["Add;Int32"]);
+        assert!(pool
+            .class(class)
+            .unwrap()
+            .functions
+            .contains(&find_functions(&pool, "Test.Things.Counter::Add;Int32").unwrap()[0]));
+        assert_eq!(names("Test.Things.Describe"), vec!["Test.Things.Describe;Counter"]);
+        assert_eq!(names("OperatorGreater;Int32Int32"), vec![
+            "OperatorGreater;Int32Int32;Bool"
+        ]);
+        assert_eq!(names("OperatorGreater"), vec!["OperatorGreater;Int32Int32;Bool"]);
+
+        assert!(names("Test.Things.Counter::Missing").is_empty());
+        assert!(names("Test.Things.Counter::Add;String").is_empty());
+        assert!(names("Missing::Add").is_empty());
+        assert!(names("Missing").is_empty());
+    }
+
+    #[test]
+    fn report_unlocated_diagnostics() {
+        let diagnostic = Diagnostic::ResolutionError("Missing".to_owned(), Span::ZERO);
+        let json = diagnostic_json(&diagnostic, &Files::new());
+        assert_eq!(
+            json,
+            json!({ "code": "E0004", "severity": "error", "message": "Missing" })
+        );
+    }
 }
@@ -0,0 +1,824 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use redscript::bundle::{ConstantPool, DefinitionType, PoolError, PoolIndex};
+use redscript::bytecode::{Code, Instr, Label, Location, Offset};
+use redscript::definition::{
+    AnyDefinition, Class, Definition, Enum, Field, Function, Local, LocalFlags, Parameter, Type
+};
+use redscript::Ref;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AsmError {
+    #[error("line {0}: {1}")]
+    SyntaxError(usize, String),
+    #[error("constant pool error: {0}")]
+    PoolError(#[from] PoolError),
+}
+
+/// Assembles the functions of a listing and replaces their bytecode in the pool.
+///
+/// A listing consists of blocks like `func Class::Method;Int32 { ... }`. Each line of a block is a local
+/// declaration (`let name: Type`), a label (`name:`) or a lowercase instruction followed by its operands.
+/// Pool entries are referred to by name (`Class`, `Class.field`, `Class::Method;Int32`, `Enum.Member`
+/// or types like `array:Int32`), names and strings are quoted and jump targets are labels.
+pub fn assemble(source: &str, pool: &mut ConstantPool) -> Result<Vec<PoolIndex<Function>>, AsmError> {
+    let symbols = Symbols::new(pool)?;
+    let mut functions = vec![];
+    let mut current: Option<FunctionAsm> = None;
+
+    for (line, text) in source.lines().enumerate() {
+        let line = line + 1;
+        let syntax_error = |message: String| AsmError::SyntaxError(line, message);
+        let tokens = tokenize(text).map_err(syntax_error)?;
+
+        match (&mut current, tokens.as_slice()) {
+            (_, []) => {}
+            (None, [Token::Word(keyword), Token::Word(name), Token::Word(brace)])
+                if keyword == "func" && brace == "{" =>
+            {
+                let index = symbols.function(name).map_err(syntax_error)?;
+                current = Some(FunctionAsm::new(index, pool)?);
+            }
+            (None, _) => return Err(syntax_error("expected a function block".to_owned())),
+            (Some(_), [Token::Word(brace)]) if brace == "}" => {
+                let function = current.take().unwrap();
+                functions.push(function.finish(pool).map_err(syntax_error)?);
+            }
+            (Some(function), [Token::Word(label)]) if label.ends_with(':') => {
+                function
+                    .define_label(label.trim_end_matches(':'))
+                    .map_err(syntax_error)?;
+            }
+            (Some(function), [Token::Word(keyword), Token::Word(name), Token::Word(type_)])
+                if keyword == "let" || keyword == "const" =>
+            {
+                let name = name
+                    .strip_suffix(':')
+                    .ok_or_else(|| syntax_error("expected a ':' after the local name".to_owned()))?;
+                let type_ = symbols.type_(type_).map_err(syntax_error)?;
+                function
+                    .declare_local(name, type_, keyword == "const", pool)
+                    .map_err(syntax_error)?;
+            }
+            (Some(function), [Token::Word(op), rest @ ..]) => {
+                let mut operands = Operands {
+                    tokens: rest.iter(),
+                    symbols: &symbols,
+                    function,
+                };
+                let instr = parse_instr(op, &mut operands, pool).map_err(syntax_error)?;
+                operands.finish().map_err(syntax_error)?;
+                function.instrs.push(instr);
+            }
+            (Some(_), _) => return Err(syntax_error("expected an instruction".to_owned())),
+        }
+    }
+    if current.is_some() {
+        let line = source.lines().count();
+        return Err(AsmError::SyntaxError(line, "unterminated function block".to_owned()));
+    }
+    Ok(functions)
+}
+
+/// Prints the bytecode of a function in the syntax accepted by `assemble`.
+pub fn disassemble(pool: &ConstantPool, index: PoolIndex<Function>) -> Result<String, PoolError> {
+    let fun = pool.function(index)?;
+    let mut out = String::new();
+    writeln!(out, "func {} {{", qualified_name(pool, index.cast())?).unwrap();
+
+    for idx in &fun.locals {
+        let local = pool.local(*idx)?;
+        let keyword = if local.flags.is_const() { "const" } else { "let" };
+        let type_name = pool.def_name(local.type_)?;
+        writeln!(out, "    {} {}: {}", keyword, pool.def_name(*idx)?, type_name).unwrap();
+    }
+
+    let mut targets: BTreeMap<Location, String> = BTreeMap::new();
+    let mut end = Location::new(0);
+    for (loc, instr) in fun.code.cursor() {
+        for offset in jump_offsets(&instr) {
+            targets.insert(offset.absolute(loc), String::new());
+        }
+        end = Location::new(loc.value + instr.size());
+    }
+    for (i, name) in targets.values_mut().enumerate() {
+        *name = format!("L{}", i);
+    }
+
+    let mut written = 0;
+    for (loc, instr) in fun.code.cursor() {
+        if let Some(label) = targets.get(&loc) {
+            writeln!(out, "{}:", label).unwrap();
+            written += 1;
+        }
+        let mut line = format!("    {}", <&str>::from(&instr));
+        for operand in format_operands(&instr, loc, &targets, pool)? {
+            line.push(' ');
+            line.push_str(&operand);
+        }
+        writeln!(out, "{}", line).unwrap();
+    }
+    if let Some(label) = targets.get(&end) {
+        writeln!(out, "{}:", label).unwrap();
+        written += 1;
+    }
+    if written != targets.len() {
+        return Err(PoolError(format!("Function {} has invalid jump targets", index)));
+    }
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+/// Looks up a function by its qualified name, e.g. `Class::Method;Int32`.
+pub fn find_function(pool: &ConstantPool, name: &str) -> Result<Option<PoolIndex<Function>>, PoolError> {
+    // compare the unqualified names first to avoid decoding the definitions of lazily loaded pools
+    let short_name = name.rsplit("::").next().unwrap_or(name);
+    for (index, type_) in pool.definition_types() {
+        if type_ == DefinitionType::Function
+            && pool.def_name(index)?.as_str() == short_name
+            && qualified_name(pool, index)? == name
+        {
+            return Ok(Some(index.cast()));
+        }
+    }
+    Ok(None)
+}
+
+fn jump_offsets(instr: &Instr<Offset>) -> Vec<Offset> {
+    match instr {
+        Instr::Target(offset)
+        | Instr::Switch(_, offset)
+        | Instr::Jump(offset)
+        | Instr::JumpIfFalse(offset)
+        | Instr::Skip(offset)
+        | Instr::Context(offset)
+        | Instr::InvokeStatic(offset, _, _, _)
+        | Instr::InvokeVirtual(offset, _, _, _) => vec![*offset],
+        Instr::SwitchLabel(first, second) | Instr::Conditional(first, second) => vec![*first, *second],
+        _ => vec![],
+    }
+}
+
+fn format_operands(
+    instr: &Instr<Offset>,
+    loc: Location,
+    targets: &BTreeMap<Location, String>,
+    pool: &ConstantPool,
+) -> Result<Vec<String>, PoolError> {
+    let label = |offset: &Offset| targets[&offset.absolute(loc)].clone();
+    let name = |idx: PoolIndex<Definition>| pool.def_name(idx).map(|name| name.to_string());
+    let qualified = |idx: PoolIndex<Definition>| qualified_name(pool, idx);
+
+    let operands = match instr {
+        Instr::I8Const(val) => vec![val.to_string()],
+        Instr::I16Const(val) => vec![val.to_string()],
+        Instr::I32Const(val) => vec![val.to_string()],
+        Instr::I64Const(val) => vec![val.to_string()],
+        Instr::U8Const(val) => vec![val.to_string()],
+        Instr::U16Const(val) => vec![val.to_string()],
+        Instr::U32Const(val) => vec![val.to_string()],
+        Instr::U64Const(val) => vec![val.to_string()],
+        Instr::F32Const(val) => vec![format!("{:?}", val)],
+        Instr::F64Const(val) => vec![format!("{:?}", val)],
+        Instr::NameConst(idx) => vec![quoted(&pool.names.get(*idx)?)],
+        Instr::EnumConst(_, member) => vec![qualified(member.cast())?],
+        Instr::StringConst(idx) => vec![quoted(&pool.strings.get(*idx)?)],
+        Instr::TweakDbIdConst(idx) => vec![quoted(&pool.tweakdb_ids.get(*idx)?)],
+        Instr::ResourceConst(idx) => vec![quoted(&pool.resources.get(*idx)?)],
+        Instr::Breakpoint(a, b, c, d, e, f) => vec![
+            a.to_string(),
+            b.to_string(),
+            c.to_string(),
+            d.to_string(),
+            e.to_string(),
+            f.to_string(),
+        ],
+        Instr::Target(_) => return Err(PoolError("Unexpected label target in bytecode".to_owned())),
+        Instr::Local(idx) => vec![name(idx.cast())?],
+        Instr::Param(idx) => vec![name(idx.cast())?],
+        Instr::ObjectField(idx) | Instr::StructField(idx) => vec![qualified(idx.cast())?],
+        Instr::Switch(type_, exit) => vec![name(type_.cast())?, label(exit)],
+        Instr::SwitchLabel(first, second) | Instr::Conditional(first, second) => vec![label(first), label(second)],
+        Instr::Jump(target) | Instr::JumpIfFalse(target) | Instr::Skip(target) | Instr::Context(target) => {
+            vec![label(target)]
+        }
+        Instr::Construct(args, class) => vec![args.to_string(), name(class.cast())?],
+        Instr::InvokeStatic(exit, line, fun, flags) => {
+            vec![label(exit), line.to_string(), qualified(fun.cast())?, flags.to_string()]
+        }
+        Instr::InvokeVirtual(exit, line, fun, flags) => vec![
+            label(exit),
+            line.to_string(),
+            quoted(&pool.names.get(*fun)?),
+            flags.to_string(),
+        ],
+        Instr::New(class) => vec![name(class.cast())?],
+        Instr::DynamicCast(class, flags) => vec![name(class.cast())?, flags.to_string()],
+        Instr::StartProfiling(bytes, flags) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            vec![format!("0x{}", hex), flags.to_string()]
+        }
+        Instr::EnumToI32(type_, size) | Instr::I32ToEnum(type_, size) => vec![name(type_.cast())?, size.to_string()],
+        Instr::Equals(type_)
+        | Instr::NotEquals(type_)
+        | Instr::ArrayClear(type_)
+        | Instr::ArraySize(type_)
+        | Instr::ArrayResize(type_)
+        | Instr::ArrayFindFirst(type_)
+        | Instr::ArrayFindFirstFast(type_)
+        | Instr::ArrayFindLast(type_)
+        | Instr::ArrayFindLastFast(type_)
+        | Instr::ArrayContains(type_)
+        | Instr::ArrayContainsFast(type_)
+        | Instr::ArrayCount(type_)
+        | Instr::ArrayCountFast(type_)
+        | Instr::ArrayPush(type_)
+        | Instr::ArrayPop(type_)
+        | Instr::ArrayInsert(type_)
+        | Instr::ArrayRemove(type_)
+        | Instr::ArrayRemoveFast(type_)
+        | Instr::ArrayGrow(type_)
+        | Instr::ArrayErase(type_)
+        | Instr::ArrayEraseFast(type_)
+        | Instr::ArrayLast(type_)
+        | Instr::ArrayElement(type_)
+        | Instr::StaticArraySize(type_)
+        | Instr::StaticArrayFindFirst(type_)
+        | Instr::StaticArrayFindFirstFast(type_)
+        | Instr::StaticArrayFindLast(type_)
+        | Instr::StaticArrayFindLastFast(type_)
+        | Instr::StaticArrayContains(type_)
+        | Instr::StaticArrayContainsFast(type_)
+        | Instr::StaticArrayCount(type_)
+        | Instr::StaticArrayCountFast(type_)
+        | Instr::StaticArrayLast(type_)
+        | Instr::StaticArrayElement(type_)
+        | Instr::ToString(type_)
+        | Instr::ToVariant(type_)
+        | Instr::FromVariant(type_)
+        | Instr::AsRef(type_)
+        | Instr::Deref(type_) => vec![name(type_.cast())?],
+        _ => vec![],
+    };
+    Ok(operands)
+}
+
+type TypeInstr = fn(PoolIndex<Type>) -> Instr<Label>;
+
+const TYPE_INSTRS: &[(&str, TypeInstr)] = &[
+    ("equals", Instr::Equals),
+    ("notequals", Instr::NotEquals),
+    ("arrayclear", Instr::ArrayClear),
+    ("arraysize", Instr::ArraySize),
+    ("arrayresize", Instr::ArrayResize),
+    ("arrayfindfirst", Instr::ArrayFindFirst),
+    ("arrayfindfirstfast", Instr::ArrayFindFirstFast),
+    ("arrayfindlast", Instr::ArrayFindLast),
+    ("arrayfindlastfast", Instr::ArrayFindLastFast),
+    ("arraycontains", Instr::ArrayContains),
+    ("arraycontainsfast", Instr::ArrayContainsFast),
+    ("arraycount", Instr::ArrayCount),
+    ("arraycountfast", Instr::ArrayCountFast),
+    ("arraypush", Instr::ArrayPush),
+    ("arraypop", Instr::ArrayPop),
+    ("arrayinsert", Instr::ArrayInsert),
+    ("arrayremove", Instr::ArrayRemove),
+    ("arrayremovefast", Instr::ArrayRemoveFast),
+    ("arraygrow", Instr::ArrayGrow),
+    ("arrayerase", Instr::ArrayErase),
+    ("arrayerasefast", Instr::ArrayEraseFast),
+    ("arraylast", Instr::ArrayLast),
+    ("arrayelement", Instr::ArrayElement),
+    ("staticarraysize", Instr::StaticArraySize),
+    ("staticarrayfindfirst", Instr::StaticArrayFindFirst),
+    ("staticarrayfindfirstfast", Instr::StaticArrayFindFirstFast),
+    ("staticarrayfindlast", Instr::StaticArrayFindLast),
+    ("staticarrayfindlastfast", Instr::StaticArrayFindLastFast),
+    ("staticarraycontains", Instr::StaticArrayContains),
+    ("staticarraycontainsfast", Instr::StaticArrayContainsFast),
+    ("staticarraycount", Instr::StaticArrayCount),
+    ("staticarraycountfast", Instr::StaticArrayCountFast),
+    ("staticarraylast", Instr::StaticArrayLast),
+    ("staticarrayelement", Instr::StaticArrayElement),
+    ("tostring", Instr::ToString),
+    ("tovariant", Instr::ToVariant),
+    ("fromvariant", Instr::FromVariant),
+    ("asref", Instr::AsRef),
+    ("deref", Instr::Deref),
+];
+
+fn parse_instr(op: &str, ops: &mut Operands, pool: &mut ConstantPool) -> Result<Instr<Label>, String> {
+    if let Some((_, instr)) = TYPE_INSTRS.iter().find(|(name, _)| *name == op) {
+        return Ok(instr(ops.type_()?));
+    }
+    let instr = match op {
+        "nop" => Instr::Nop,
+        "null" => Instr::Null,
+        "i32one" => Instr::I32One,
+        "i32zero" => Instr::I32Zero,
+        "i8const" => Instr::I8Const(ops.number()?),
+        "i16const" => Instr::I16Const(ops.number()?),
+        "i32const" => Instr::I32Const(ops.number()?),
+        "i64const" => Instr::I64Const(ops.number()?),
+        "u8const" => Instr::U8Const(ops.number()?),
+        "u16const" => Instr::U16Const(ops.number()?),
+        "u32const" => Instr::U32Const(ops.number()?),
+        "u64const" => Instr::U64Const(ops.number()?),
+        "f32const" => Instr::F32Const(ops.number()?),
+        "f64const" => Instr::F64Const(ops.number()?),
+        "nameconst" => Instr::NameConst(pool.names.add(Ref::new(ops.string()?))),
+        "enumconst" => {
+            let (enum_, member) = ops.enum_member()?;
+            Instr::EnumConst(enum_, member)
+        }
+        "stringconst" => Instr::StringConst(pool.strings.add(Ref::new(ops.string()?))),
+        "tweakdbidconst" => Instr::TweakDbIdConst(pool.tweakdb_ids.add(Ref::new(ops.string()?))),
+        "resourceconst" => Instr::ResourceConst(pool.resources.add(Ref::new(ops.string()?))),
+        "trueconst" => Instr::TrueConst,
+        "falseconst" => Instr::FalseConst,
+        "breakpoint" => Instr::Breakpoint(
+            ops.number()?,
+            ops.number()?,
+            ops.number()?,
+            ops.number()?,
+            ops.number()?,
+            ops.number()?,
+        ),
+        "assign" => Instr::Assign,
+        "local" => Instr::Local(ops.local()?),
+        "param" => Instr::Param(ops.param()?),
+        "objectfield" => Instr::ObjectField(ops.field()?),
+        "externalvar" => Instr::ExternalVar,
+        "switch" => Instr::Switch(ops.type_()?, ops.label()?),
+        "switchlabel" => Instr::SwitchLabel(ops.label()?, ops.label()?),
+        "switchdefault" => Instr::SwitchDefault,
+        "jump" => Instr::Jump(ops.label()?),
+        "jumpiffalse" => Instr::JumpIfFalse(ops.label()?),
+        "skip" => Instr::Skip(ops.label()?),
+        "conditional" => Instr::Conditional(ops.label()?, ops.label()?),
+        "construct" => Instr::Construct(ops.number()?, ops.class()?),
+        "invokestatic" => Instr::InvokeStatic(ops.label()?, ops.number()?, ops.function()?, ops.number()?),
+        "invokevirtual" => {
+            let exit = ops.label()?;
+            let line = ops.number()?;
+            let name = pool.names.add(Ref::new(ops.string()?));
+            Instr::InvokeVirtual(exit, line, name, ops.number()?)
+        }
+        "paramend" => Instr::ParamEnd,
+        "return" => Instr::Return,
+        "structfield" => Instr::StructField(ops.field()?),
+        "context" => Instr::Context(ops.label()?),
+        "new" => Instr::New(ops.class()?),
+        "delete" => Instr::Delete,
+        "this" => Instr::This,
+        "startprofiling" => Instr::StartProfiling(ops.bytes()?, ops.number()?),
+        "reftobool" => Instr::RefToBool,
+        "weakreftobool" => Instr::WeakRefToBool,
+        "enumtoi32" => Instr::EnumToI32(ops.type_()?, ops.number()?),
+        "i32toenum" => Instr::I32ToEnum(ops.type_()?, ops.number()?),
+        "dynamiccast" => Instr::DynamicCast(ops.class()?, ops.number()?),
+        "variantisdefined" => Instr::VariantIsDefined,
+        "variantisref" => Instr::VariantIsRef,
+        "variantisarray" => Instr::VariantIsArray,
+        "varianttypename" => Instr::VariantTypeName,
+        "varianttostring" => Instr::VariantToString,
+        "weakreftoref" => Instr::WeakRefToRef,
+        "reftoweakref" => Instr::RefToWeakRef,
+        "weakrefnull" => Instr::WeakRefNull,
+        other => return Err(format!("unknown instruction: {}", other)),
+    };
+    Ok(instr)
+}
+
+struct FunctionAsm {
+    index: PoolIndex<Function>,
+    instrs: Vec<Instr<Label>>,
+    labels: HashMap<String, (Label, bool)>,
+    locals: HashMap<String, PoolIndex<Local>>,
+    params: HashMap<String, PoolIndex<Parameter>>,
+    new_locals: Vec<PoolIndex<Local>>,
+}
+
+impl FunctionAsm {
+    fn new(index: PoolIndex<Function>, pool: &ConstantPool) -> Result<Self, PoolError> {
+        let fun = pool.function(index)?;
+        let mut locals = HashMap::new();
+        for local in &fun.locals {
+            locals.insert(pool.def_name(*local)?.to_string(), *local);
+        }
+        let mut params = HashMap::new();
+        for param in &fun.parameters {
+            params.insert(pool.def_name(*param)?.to_string(), *param);
+        }
+        let result = FunctionAsm {
+            index,
+            instrs: vec![],
+            labels: HashMap::new(),
+            locals,
+            params,
+            new_locals: vec![],
+        };
+        Ok(result)
+    }
+
+    fn label(&mut self, name: &str) -> Label {
+        let index = self.labels.len();
+        self.labels.entry(name.to_owned()).or_insert((Label { index }, false)).0
+    }
+
+    fn define_label(&mut self, name: &str) -> Result<(), String> {
+        let label = self.label(name);
+        let defined = &mut self.labels.get_mut(name).unwrap().1;
+        if *defined {
+            return Err(format!("label {} is defined more than once", name));
+        }
+        *defined = true;
+        self.instrs.push(Instr::Target(label));
+        Ok(())
+    }
+
+    fn declare_local(
+        &mut self,
+        name: &str,
+        type_: PoolIndex<Type>,
+        is_const: bool,
+        pool: &mut ConstantPool,
+    ) -> Result<(), String> {
+        if let Some(local) = self.locals.get(name) {
+            let existing = pool.local(*local).map_err(|err| err.to_string())?;
+            if existing.type_ != type_ {
+                return Err(format!("local {} is already declared with a different type", name));
+            }
+            return Ok(());
+        }
+        let name_idx = pool.names.add(Ref::new(name.to_owned()));
+        let local = Local::new(type_, LocalFlags::new().with_is_const(is_const));
+        let local_idx = pool.add_definition(Definition::local(name_idx, self.index, local));
+        self.locals.insert(name.to_owned(), local_idx);
+        self.new_locals.push(local_idx);
+        Ok(())
+    }
+
+    fn finish(self, pool: &mut ConstantPool) -> Result<PoolIndex<Function>, String> {
+        if let Some(name) = self
+            .labels
+            .iter()
+            .filter(|(_, (_, defined))| !defined)
+            .map(|(name, _)| name)
+            .min()
+        {
+            return Err(format!("label {} is never defined", name));
+        }
+        let code = Code(self.instrs).resolve_labels(self.labels.len());
+        let fun = pool.function_mut(self.index).map_err(|err| err.to_string())?;
+        fun.code = code;
+        fun.locals.extend(self.new_locals);
+        Ok(self.index)
+    }
+}
+
+struct Operands<'a> {
+    tokens: std::slice::Iter<'a, Token>,
+    symbols: &'a Symbols,
+    function: &'a mut FunctionAsm,
+}
+
+impl<'a> Operands<'a> {
+    fn word(&mut self) -> Result<&'a str, String> {
+        match self.tokens.next() {
+            Some(Token::Word(word)) => Ok(word),
+            Some(Token::Str(str)) => Err(format!("unexpected string \"{}\"", str)),
+            None => Err("missing operand".to_owned()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.tokens.next() {
+            Some(Token::Str(str)) => Ok(str.clone()),
+            Some(Token::Word(word)) => Err(format!("expected a quoted string, found {}", word)),
+            None => Err("missing operand".to_owned()),
+        }
+    }
+
+    fn number<N: FromStr>(&mut self) -> Result<N, String> {
+        let word = self.word()?;
+        word.parse().map_err(|_| format!("invalid number: {}", word))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let word = self.word()?;
+        let invalid = || format!("invalid byte string: {}", word);
+        let hex = word
+            .strip_prefix("0x")
+            .filter(|hex| hex.len() % 2 == 0)
+            .ok_or_else(invalid)?;
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect()
+    }
+
+    fn label(&mut self) -> Result<Label, String> {
+        let name = self.word()?;
+        Ok(self.function.label(name))
+    }
+
+    fn local(&mut self) -> Result<PoolIndex<Local>, String> {
+        let name = self.word()?;
+        let local = self.function.locals.get(name);
+        local.copied().ok_or_else(|| format!("unknown local: {}", name))
+    }
+
+    fn param(&mut self) -> Result<PoolIndex<Parameter>, String> {
+        let name = self.word()?;
+        let param = self.function.params.get(name);
+        param.copied().ok_or_else(|| format!("unknown parameter: {}", name))
+    }
+
+    fn type_(&mut self) -> Result<PoolIndex<Type>, String> {
+        self.symbols.type_(self.word()?)
+    }
+
+    fn class(&mut self) -> Result<PoolIndex<Class>, String> {
+        let name = self.word()?;
+        let class = self.symbols.classes.get(name);
+        class.copied().ok_or_else(|| format!("unknown class: {}", name))
+    }
+
+    fn field(&mut self) -> Result<PoolIndex<Field>, String> {
+        let name = self.word()?;
+        let field = self.symbols.fields.get(name);
+        field.copied().ok_or_else(|| format!("unknown field: {}", name))
+    }
+
+    fn function(&mut self) -> Result<PoolIndex<Function>, String> {
+        self.symbols.function(self.word()?)
+    }
+
+    fn enum_member(&mut self) -> Result<(PoolIndex<Enum>, PoolIndex<i64>), String> {
+        let name = self.word()?;
+        let member = self.symbols.enum_members.get(name);
+        member.copied().ok_or_else(|| format!("unknown enum member: {}", name))
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        match self.tokens.next() {
+            None => Ok(()),
+            Some(_) => Err("too many operands".to_owned()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Symbols {
+    types: HashMap<String, PoolIndex<Type>>,
+    classes: HashMap<String, PoolIndex<Class>>,
+    fields: HashMap<String, PoolIndex<Field>>,
+    functions: HashMap<String, PoolIndex<Function>>,
+    enum_members: HashMap<String, (PoolIndex<Enum>, PoolIndex<i64>)>,
+}
+
+impl Symbols {
+    fn new(pool: &ConstantPool) -> Result<Self, PoolError> {
+        let mut symbols = Symbols::default();
+        for entry in pool.definitions() {
+            let (idx, def) = entry?;
+            match def.value {
+                AnyDefinition::Type(_) => {
+                    symbols.types.entry(qualified_name(pool, idx)?).or_insert(idx.cast());
+                }
+                AnyDefinition::Class(_) => {
+                    symbols.classes.entry(qualified_name(pool, idx)?).or_insert(idx.cast());
+                }
+                AnyDefinition::Field(_) => {
+                    symbols.fields.entry(qualified_name(pool, idx)?).or_insert(idx.cast());
+                }
+                AnyDefinition::Function(_) => {
+                    symbols
+                        .functions
+                        .entry(qualified_name(pool, idx)?)
+                        .or_insert(idx.cast());
+                }
+                AnyDefinition::EnumValue(_) => {
+                    let member = (def.parent.cast(), idx.cast());
+                    symbols.enum_members.entry(qualified_name(pool, idx)?).or_insert(member);
+                }
+                _ => {}
+            }
+        }
+        Ok(symbols)
+    }
+
+    fn type_(&self, name: &str) -> Result<PoolIndex<Type>, String> {
+        let type_ = self.types.get(name);
+        type_.copied().ok_or_else(|| format!("unknown type: {}", name))
+    }
+
+    fn function(&self, name: &str) -> Result<PoolIndex<Function>, String> {
+        let function = self.functions.get(name);
+        function.copied().ok_or_else(|| format!("unknown function: {}", name))
+    }
+}
+
+fn qualified_name(pool: &ConstantPool, index: PoolIndex<Definition>) -> Result<String, PoolError> {
+    let def = pool.definition(index)?;
+    let name = pool.names.get(def.name)?;
+    if def.parent.is_undefined() {
+        return Ok(name.to_string());
+    }
+    let separator = match def.value {
+        AnyDefinition::Function(_) => "::",
+        _ => ".",
+    };
+    Ok(format!("{}{}{}", pool.def_name(def.parent)?, separator, name))
+}
+
+fn quoted(str: &str) -> String {
+    let mut out = String::with_capacity(str.len() + 2);
+    out.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => break,
+            '"' => {
+                let mut str = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => str.push('\n'),
+                            Some('r') => str.push('\r'),
+                            Some('t') => str.push('\t'),
+                            Some(c @ ('"' | '\\')) => str.push(c),
+                            other => return Err(format!("invalid escape sequence: {:?}", other)),
+                        },
+                        Some(c) => str.push(c),
+                        None => return Err("unterminated string".to_owned()),
+                    }
+                }
+                tokens.push(Token::Str(str));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use redscript::bundle::ScriptBundle;
+
+    use super::*;
+    use crate::source_map::Files;
+    use crate::unit::CompilationUnit;
+
+    const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+    fn compiled(source: &str) -> ConstantPool {
+        let mut pool = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap().pool;
+        let mut files = Files::new();
+        files.add(PathBuf::from("test.reds"), source.to_owned());
+        CompilationUnit::new(&mut pool)
+            .unwrap()
+            .compile_and_report(&files)
+            .unwrap();
+        pool
+    }
+
+    #[test]
+    fn reassemble_compiled_functions() {
+        let pool = compiled(
+            r#"
+            enum Direction { Up = 0, Down = 1 }
+            class Counter {
+                let count: Int32;
+                let labels: array<String>;
+
+                func Add(by: Int32, dir: Direction) -> Float {
+                    let label = "added";
+                    switch dir {
+                        case Direction.Up:
+                            this.count = by;
+                            break;
+                        default:
+                            ArrayPush(this.labels, label);
+                    }
+                    return 1.5;
+                }
+
+                static func Create() -> ref<Counter> = new Counter()
+            }
+            "#,
+        );
+        let functions: Vec<PoolIndex<Function>> = pool
+            .definitions()
+            .map(Result::unwrap)
+            .filter(|(_, def)| matches!(&def.value, AnyDefinition::Function(fun) if !fun.code.is_empty()))
+            .map(|(idx, _)| idx.cast())
+            .collect();
+        assert_eq!(functions.len(), 2);
+
+        for idx in functions {
+            let listing = disassemble(&pool, idx).unwrap();
+            let mut reassembled = pool.clone();
+            assert_eq!(assemble(&listing, &mut reassembled).unwrap(), vec![idx]);
+
+            let (fun, expected) = (reassembled.function(idx).unwrap(), pool.function(idx).unwrap());
+            assert_eq!(fun.code, expected.code, "{}", listing);
+            assert_eq!(fun.locals, expected.locals);
+        }
+    }
+
+    #[test]
+    fn assemble_hand_written_function() {
+        let mut pool = compiled("func Pick(cond: Bool) -> Int32 { return 0; }");
+        let listing = r#"
+            // returns 1 when the condition holds
+            func Pick;Bool {
+                let result: Int32
+                jumpiffalse else
+                param cond
+                return
+                i32one
+            else:
+                assign
+                local result
+                i32zero
+                return
+                local result
+            }
+            "#;
+        let functions = assemble(listing, &mut pool).unwrap();
+        let fun = pool.function(functions[0]).unwrap();
+        let result = *fun.locals.last().unwrap();
+        let cond = fun.parameters[0];
+
+        assert_eq!(pool.def_name(result).unwrap().as_str(), "result");
+        assert_eq!(fun.code.0, vec![
+            Instr::JumpIfFalse(Offset::new(14)),
+            Instr::Param(cond),
+            Instr::Return,
+            Instr::I32One,
+            Instr::Assign,
+            Instr::Local(result),
+            Instr::I32Zero,
+            Instr::Return,
+            Instr::Local(result),
+        ]);
+    }
+
+    #[test]
+    fn report_assembly_errors() {
+        let mut pool = compiled("func Pick(cond: Bool) -> Int32 { return 0; }");
+        let error = |listing: &str| assemble(listing, &mut pool.clone()).unwrap_err().to_string();
+
+        assert_eq!(
+            error("func Pick;Bool {\n jump end\n}"),
+            "line 3: label end is never defined"
+        );
+        assert_eq!(error("func Pick;Bool {\n push\n}"), "line 2: unknown instruction: push");
+        assert_eq!(error("func Pick;Bool {\n local cond\n}"), "line 2: unknown local: cond");
+        assert_eq!(error("func Pick;Bool {\n i32const 1 2\n}"), "line 2: too many operands");
+        assert_eq!(error("func Missing; {\n}"), "line 1: unknown function: Missing;");
+        assert_eq!(
+            error("func Pick;Bool {\n return"),
+            "line 2: unterminated function block"
+        );
+        assert_eq!(assemble("", &mut pool).unwrap(), vec![]);
+    }
+}
@@ -1,7 +1,7 @@
 use itertools::Itertools;
 use redscript::ast::{Constant, Expr, Literal, Seq, Span};
 use redscript::bundle::{ConstantPool, PoolIndex};
-use redscript::bytecode::{Code, Instr, IntrinsicOp, Label, Location, Offset};
+use redscript::bytecode::{Code, Instr, IntrinsicOp, Label, Offset};
 use redscript::definition::Function;
 
 use crate::error::{Cause, Error, ResultSpan};
@@ -45,6 +45,7 @@ impl Assembler {
         scope: &mut Scope,
         pool: &mut ConstantPool,
         exit: Option<Label>,
+        cont: Option<Label>,
     ) -> Result<(), Error> {
         match expr {
             Expr::Ident(reference, span) => {
@@ -99,7 +100,7 @@ impl Assembler {
             Expr::Cast(type_, expr, span) => {
                 if let TypeId::Class(class) = type_ {
                     self.emit(Instr::DynamicCast(class, 0));
-                    self.assemble(*expr, scope, pool, None)?;
+                    self.assemble(*expr, scope, pool, None, None)?;
                 } else {
                     return Err(Cause::invalid_op(type_.pretty(pool)?, "Cast").with_span(span));
                 }
@@ -108,13 +109,13 @@ impl Assembler {
                 if let Some(val) = init {
                     self.emit(Instr::Assign);
                     self.emit(Instr::Local(local));
-                    self.assemble(*val, scope, pool, None)?;
+                    self.assemble(*val, scope, pool, None, None)?;
                 }
             }
             Expr::Assign(lhs, rhs, _) => {
                 self.emit(Instr::Assign);
-                self.assemble(*lhs, scope, pool, None)?;
-                self.assemble(*rhs, scope, pool, None)?;
+                self.assemble(*lhs, scope, pool, None, None)?;
+                self.assemble(*rhs, scope, pool, None, None)?;
             }
             Expr::ArrayElem(expr, idx, span) => {
                 match type_of(&expr, scope, pool)? {
@@ -128,29 +129,29 @@ impl Assembler {
                     }
                     other => return Err(Cause::invalid_op(other.pretty(pool)?, "Indexing").with_span(span)),
                 }
-                self.assemble(*expr, scope, pool, None)?;
-                self.assemble(*idx, scope, pool, None)?;
+                self.assemble(*expr, scope, pool, None, None)?;
+                self.assemble(*idx, scope, pool, None, None)?;
             }
             Expr::New(type_, args, span) => match type_ {
                 TypeId::Class(idx) => self.emit(Instr::New(idx)),
                 TypeId::Struct(idx) => {
                     self.emit(Instr::Construct(args.len() as u8, idx));
                     for arg in args {
-                        self.assemble(arg, scope, pool, None)?;
+                        self.assemble(arg, scope, pool, None, None)?;
                     }
                 }
                 _ => return Err(Cause::invalid_op(type_.pretty(pool)?, "Constructing").with_span(span)),
             },
             Expr::Return(Some(expr), _) => {
                 self.emit(Instr::Return);
-                self.assemble(*expr, scope, pool, None)?;
+                self.assemble(*expr, scope, pool, None, None)?;
             }
             Expr::Return(None, _) => {
                 self.emit(Instr::Return);
                 self.emit(Instr::Nop);
             }
             Expr::Seq(seq) => {
-                self.assemble_seq(seq, scope, pool, exit)?;
+                self.assemble_seq(seq, scope, pool, exit, cont)?;
             }
             Expr::Switch(expr, cases, default, span) => {
                 let type_ = type_of(&expr, scope, pool)?;
@@ -159,7 +160,7 @@ impl Assembler {
                 let exit_label = self.new_label();
                 let type_idx = scope.get_type_index(&type_, pool).with_span(span)?;
                 self.emit(Instr::Switch(type_idx, first_case_label));
-                self.assemble(*expr, scope, pool, None)?;
+                self.assemble(*expr, scope, pool, None, None)?;
                 self.emit_label(first_case_label);
 
                 let mut case_iter = cases.into_iter().peekable();
@@ -170,11 +171,11 @@ impl Assembler {
                         self.emit_label(next_case_label);
                         next_case_label = self.new_label();
                         self.emit(Instr::SwitchLabel(next_case_label, body_label));
-                        self.assemble(case.matcher, scope, pool, None)?;
+                        self.assemble(case.matcher, scope, pool, None, None)?;
 
                         if !case.body.exprs.iter().all(|expr| expr.is_empty()) {
                             self.emit_label(body_label);
-                            self.assemble_seq(case.body, scope, pool, Some(exit_label))?;
+                            self.assemble_seq(case.body, scope, pool, Some(exit_label), cont)?;
                             break;
                         }
                     }
@@ -183,20 +184,20 @@ impl Assembler {
 
                 if let Some(body) = default {
                     self.emit(Instr::SwitchDefault);
-                    self.assemble_seq(body, scope, pool, Some(exit_label))?;
+                    self.assemble_seq(body, scope, pool, Some(exit_label), cont)?;
                 }
                 self.emit_label(exit_label);
             }
             Expr::If(condition, if_, else_, _) => {
                 let else_label = self.new_label();
                 self.emit(Instr::JumpIfFalse(else_label));
-                self.assemble(*condition, scope, pool, None)?;
-                self.assemble_seq(if_, scope, pool, exit)?;
+                self.assemble(*condition, scope, pool, None, None)?;
+                self.assemble_seq(if_, scope, pool, exit, cont)?;
                 if let Some(else_code) = else_ {
                     let exit_label = self.new_label();
                     self.emit(Instr::Jump(exit_label));
                     self.emit_label(else_label);
-                    self.assemble_seq(else_code, scope, pool, exit)?;
+                    self.assemble_seq(else_code, scope, pool, exit, cont)?;
                     self.emit_label(exit_label);
                 } else {
                     self.emit_label(else_label);
@@ -206,10 +207,10 @@ impl Assembler {
                 let false_label = self.new_label();
                 let exit_label = self.new_label();
                 self.emit(Instr::Conditional(false_label, exit_label));
-                self.assemble(*cond, scope, pool, None)?;
-                self.assemble(*true_, scope, pool, None)?;
+                self.assemble(*cond, scope, pool, None, None)?;
+                self.assemble(*true_, scope, pool, None, None)?;
                 self.emit_label(false_label);
-                self.assemble(*false_, scope, pool, None)?;
+                self.assemble(*false_, scope, pool, None, None)?;
                 self.emit_label(exit_label);
             }
             Expr::While(cond, body, _) => {
@@ -217,8 +218,8 @@ impl Assembler {
                 let loop_label = self.new_label();
                 self.emit_label(loop_label);
                 self.emit(Instr::JumpIfFalse(exit_label));
-                self.assemble(*cond, scope, pool, None)?;
-                self.assemble_seq(body, scope, pool, Some(exit_label))?;
+                self.assemble(*cond, scope, pool, None, None)?;
+                self.assemble_seq(body, scope, pool, Some(exit_label), Some(loop_label))?;
                 self.emit(Instr::Jump(loop_label));
                 self.emit_label(exit_label);
             }
@@ -226,13 +227,13 @@ impl Assembler {
                 Member::ClassField(field) => {
                     let exit_label = self.new_label();
                     self.emit(Instr::Context(exit_label));
-                    self.assemble(*expr, scope, pool, None)?;
+                    self.assemble(*expr, scope, pool, None, None)?;
                     self.emit(Instr::ObjectField(field));
                     self.emit_label(exit_label);
                 }
                 Member::StructField(field) => {
                     self.emit(Instr::StructField(field));
-                    self.assemble(*expr, scope, pool, None)?;
+                    self.assemble(*expr, scope, pool, None, None)?;
                 }
                 Member::EnumMember(enum_, member) => {
                     self.emit(Instr::EnumConst(enum_, member));
@@ -266,7 +267,7 @@ impl Assembler {
                         let force_static_call = matches!(&expr, Expr::Super(_));
                         let exit_label = self.new_label();
                         self.emit(Instr::Context(exit_label));
-                        self.assemble(expr, scope, pool, None)?;
+                        self.assemble(expr, scope, pool, None, None)?;
                         self.assemble_call(fun_idx, args, scope, pool, force_static_call)?;
                         self.emit_label(exit_label);
                     }
@@ -282,6 +283,9 @@ impl Assembler {
             Expr::Break(_) if exit.is_some() => {
                 self.emit(Instr::Jump(exit.unwrap()));
             }
+            Expr::Continue(_) if cont.is_some() => {
+                self.emit(Instr::Jump(cont.unwrap()));
+            }
             Expr::ArrayLit(_, _, span) => return Err(Cause::unsupported("ArrayLit").with_span(span)),
             Expr::InterpolatedString(_, _, span) => {
                 return Err(Cause::unsupported("InterpolatedString").with_span(span))
@@ -290,6 +294,7 @@ impl Assembler {
             Expr::BinOp(_, _, _, span) => return Err(Cause::unsupported("BinOp").with_span(span)),
             Expr::UnOp(_, _, span) => return Err(Cause::unsupported("UnOp").with_span(span)),
             Expr::Break(span) => return Err(Cause::unsupported("Break").with_span(span)),
+            Expr::Continue(span) => return Err(Cause::unsupported("Continue").with_span(span)),
             Expr::Goto(_, span) => return Err(Cause::unsupported("Goto").with_span(span)),
         };
         Ok(())
@@ -301,9 +306,10 @@ impl Assembler {
         scope: &mut Scope,
         pool: &mut ConstantPool,
         exit: Option<Label>,
+        cont: Option<Label>,
     ) -> Result<(), Error> {
         for expr in seq.exprs {
-            self.assemble(expr, scope, pool, exit)?;
+            self.assemble(expr, scope, pool, exit, cont)?;
         }
         Ok(())
     }
@@ -343,17 +349,14 @@ impl Assembler {
             if flags.is_short_circuit() {
                 let skip_label = self.new_label();
                 self.emit(Instr::Skip(skip_label));
-                self.assemble(arg, scope, pool, None)?;
+                self.assemble(arg, scope, pool, None, None)?;
                 self.emit_label(skip_label);
             } else {
-                self.assemble(arg, scope, pool, None)?;
+                self.assemble(arg, scope, pool, None, None)?;
             }
         }
         if param_flags.len() < args_len {
-            return Err(Error::CompileError(
-                "You've done something very naughty".to_owned(),
-                Span::ZERO,
-            ));
+            return Err(Cause::new("You've done something very naughty").with_span(Span::ZERO));
         }
         for _ in 0..param_flags.len() - args_len {
             self.emit(Instr::Nop);
@@ -501,32 +504,18 @@ impl Assembler {
             },
         };
         for arg in args {
-            self.assemble(arg, scope, pool, None)?;
+            self.assemble(arg, scope, pool, None, None)?;
         }
         Ok(())
     }
 
     fn into_code(self) -> Code<Offset> {
-        let mut locations = Vec::with_capacity(self.labels);
-        locations.resize(self.labels, Location::new(0));
-
-        let code = Code(self.instructions);
-        for (loc, instr) in code.cursor() {
-            if let Instr::Target(label) = instr {
-                locations[label.index] = loc;
-            }
-        }
-
-        let mut resolved = Vec::with_capacity(code.0.len());
-        for (loc, instr) in code.cursor().filter(|(_, instr)| !matches!(instr, Instr::Target(_))) {
-            resolved.push(instr.resolve_labels(loc, &locations));
-        }
-        Code(resolved)
+        Code(self.instructions).resolve_labels(self.labels)
     }
 
     pub fn from_body(seq: Seq<TypedAst>, scope: &mut Scope, pool: &mut ConstantPool) -> Result<Code<Offset>, Error> {
         let mut assembler = Assembler::new();
-        assembler.assemble_seq(seq, scope, pool, None)?;
+        assembler.assemble_seq(seq, scope, pool, None, None)?;
         assembler.emit(Instr::Nop);
         Ok(assembler.into_code())
     }
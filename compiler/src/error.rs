@@ -13,7 +13,7 @@ pub enum Error {
     #[error("formatter error {0}")]
     SyntaxError(String, Span),
     #[error("compilation error: {0}")]
-    CompileError(String, Span),
+    CompileError(Cause, Span),
     #[error("function argument error: {0}")]
     ArgumentError(String, Span),
     #[error("function resolution error: {0}")]
@@ -28,7 +28,7 @@ pub enum Error {
 
 impl Error {
     pub fn arg_type_error<F: Display, T: Display>(from: F, to: T, span: Span) -> Error {
-        Error::ArgumentError(Cause::type_error(from, to).0, span)
+        Error::ArgumentError(Cause::type_error(from, to).message, span)
     }
 
     pub fn no_matching_overload<N: Display>(name: N, errors: &[FunctionMatchError], span: Span) -> Error {
@@ -53,159 +53,169 @@ impl Error {
     }
 }
 
-#[derive(Debug, Error)]
-#[error("{0}")]
-pub struct Cause(pub String);
+/// A compilation error, each kind of error has its own stable code that is never reused.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("{message}")]
+pub struct Cause {
+    pub code: &'static str,
+    pub message: String,
+}
 
 impl From<PoolError> for Cause {
     #[inline]
     fn from(err: PoolError) -> Self {
-        Cause(err.0)
+        Cause::new(err.0)
     }
 }
 
 impl Cause {
+    /// An error that doesn't fall into any of the specific kinds.
     #[inline]
     pub fn new<S: ToString>(error: S) -> Cause {
-        Cause(error.to_string())
+        Cause::coded("E0002", error.to_string())
+    }
+
+    #[inline]
+    fn coded(code: &'static str, message: String) -> Cause {
+        Cause { code, message }
     }
 
     #[inline]
     pub fn with_span(self, span: Span) -> Error {
-        Error::CompileError(self.0, span)
+        Error::CompileError(self, span)
     }
 
     #[inline]
     pub fn pool_err(self) -> Error {
-        Error::PoolError(PoolError(self.0))
+        Error::PoolError(PoolError(self.message))
     }
 
     pub fn type_error<F: Display, T: Display>(from: F, to: T) -> Cause {
         let error = format!("Can't coerce {} to {}", from, to);
-        Cause(error)
+        Cause::coded("E0101", error)
     }
 
     pub fn function_not_found<F: Display>(fun_name: F) -> Cause {
         let error = format!("Function {} not found", fun_name);
-        Cause(error)
+        Cause::coded("E0102", error)
     }
 
     pub fn member_not_found<M: Display, C: Display>(member: M, context: C) -> Cause {
         let error = format!("Member {} not found on {}", member, context);
-        Cause(error)
+        Cause::coded("E0103", error)
     }
 
     pub fn class_not_found<N: Display>(class_name: N) -> Cause {
         let error = format!("Can't find class {}", class_name);
-        Cause(error)
+        Cause::coded("E0104", error)
     }
 
     pub fn class_is_abstract<N: Display>(class_name: N) -> Cause {
         let error = format!("Cannot instantiate abstract class {}", class_name);
-        Cause(error)
+        Cause::coded("E0105", error)
     }
 
     pub fn unresolved_reference<N: Display>(name: N) -> Cause {
         let error = format!("Unresolved reference {}", name);
-        Cause(error)
+        Cause::coded("E0106", error)
     }
 
     pub fn unresolved_type<N: Display>(name: N) -> Cause {
         let error = format!("Unresolved type {}", name);
-        Cause(error)
+        Cause::coded("E0107", error)
     }
 
     pub fn unresolved_import<N: Display>(import: N) -> Cause {
-        Cause(format!("Unresolved import {}", import))
+        Cause::coded("E0108", format!("Unresolved import {}", import))
     }
 
     pub fn unresolved_module<N: Display>(import: N) -> Cause {
-        Cause(format!("Module {} has no members or does not exist", import))
+        Cause::coded("E0109", format!("Module {} has no members or does not exist", import))
     }
 
     pub fn invalid_annotation_args() -> Cause {
-        Cause("Invalid arguments for annotation".to_owned())
+        Cause::coded("E0110", "Invalid arguments for annotation".to_owned())
     }
 
     pub fn type_annotation_required() -> Cause {
-        Cause("Type annotation required".to_owned())
+        Cause::coded("E0111", "Type annotation required".to_owned())
     }
 
     pub fn invalid_context<N: Display>(type_: N) -> Cause {
         let error = format!("{} doesn't have members", type_);
-        Cause(error)
+        Cause::coded("E0112", error)
     }
 
     pub fn invalid_op<N: Display>(type_: N, op: &str) -> Cause {
         let error = format!("{} is not supported on {}", op, type_);
-        Cause(error)
+        Cause::coded("E0113", error)
     }
 
     pub fn invalid_arg_count<N: Display>(name: N, expected: usize) -> Cause {
         let error = format!("Expected {} parameters for {}", expected, name);
-        Cause(error)
+        Cause::coded("E0114", error)
     }
 
     pub fn void_cannot_be_used() -> Cause {
-        Cause("Void value cannot be used".to_owned())
+        Cause::coded("E0115", "Void value cannot be used".to_owned())
     }
 
     pub fn value_expected<N: Display>(found: N) -> Cause {
-        Cause(format!("Expected a value, found {}", found))
+        Cause::coded("E0116", format!("Expected a value, found {}", found))
     }
 
     pub fn return_type_mismatch<N: Display>(type_: N) -> Cause {
         let error = format!("Function should return {}", type_);
-        Cause(error)
+        Cause::coded("E0117", error)
     }
 
     pub fn invalid_intrinsic<N: Display, T: Display>(name: N, type_: T) -> Cause {
         let err = format!("Invalid intrinsic {} call: unexpected {}", name, type_);
-        Cause(err)
+        Cause::coded("E0118", err)
     }
 
     pub fn expected_static_method<N: Display>(name: N) -> Cause {
         let err = format!("Method {} is not static", name);
-        Cause(err)
+        Cause::coded("E0119", err)
     }
 
     pub fn expected_non_static_method<N: Display>(name: N) -> Cause {
         let err = format!("Method {} is static", name);
-        Cause(err)
+        Cause::coded("E0120", err)
     }
 
     pub fn no_this_in_static_context() -> Cause {
-        Cause("No 'this' in static context".to_owned())
+        Cause::coded("E0121", "No 'this' in static context".to_owned())
     }
 
     pub fn unsupported<N: Display>(name: N) -> Cause {
         let err = format!("{} is unsupported", name);
-        Cause(err)
+        Cause::coded("E0122", err)
     }
 
     pub fn class_redefinition() -> Cause {
         let err = "Class with this name is already defined elsewhere".to_owned();
-        Cause(err)
+        Cause::coded("E0123", err)
     }
 
     pub fn expected_body() -> Cause {
         let err = "This function must have a body".to_owned();
-        Cause(err)
+        Cause::coded("E0124", err)
     }
 
     pub fn native_with_body() -> Cause {
         let err = "Native function cannot have a body".to_owned();
-        Cause(err)
+        Cause::coded("E0125", err)
     }
 
     pub fn unexpected_native() -> Cause {
         let err = "Native member is not allowed on a non-native class".to_owned();
-        Cause(err)
+        Cause::coded("E0126", err)
     }
 
     pub fn unification_failed<A: Display, B: Display>(a: A, b: B) -> Cause {
         let err = format!("Cannot unify {} and {}", a, b);
-        Cause(err)
+        Cause::coded("E0127", err)
     }
 }
 
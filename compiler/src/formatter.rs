@@ -0,0 +1,1013 @@
+use itertools::Itertools;
+use redscript::ast::{BinOp, Expr, Pos, Seq, SourceAst, Span, SwitchCase};
+
+use crate::error::Error;
+use crate::parser::*;
+use crate::source_map::File;
+use crate::symbol::Import;
+
+const INDENT: &str = "  ";
+
+/// Formats a source file in the canonical style, comments are preserved.
+pub fn format_file(file: &File) -> Result<String, Error> {
+    let module = parse_file(file).map_err(|err| syntax_error(file.byte_offset(), &err))?;
+    let mut formatter = Formatter::new(file);
+    formatter.module(&module)?;
+    let output = formatter.finish();
+
+    // make sure we never write out code that does not parse
+    if let Err(err) = parse_str(&output) {
+        let message = format!("Formatting produced invalid code, expected {}", err.expected);
+        return Err(Error::SyntaxError(
+            message,
+            Span::new(file.byte_offset(), file.byte_offset()),
+        ));
+    }
+    Ok(output)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    offset: Pos,
+    comments: Vec<Span>,
+    next_comment: usize,
+    out: String,
+    depth: usize,
+    // end of the last item written at the current depth, none at the start of a block
+    last_end: Option<usize>,
+    force_blank_line: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(file: &'a File) -> Self {
+        Formatter {
+            source: file.source(),
+            offset: file.byte_offset(),
+            comments: parse_comments(file),
+            next_comment: 0,
+            out: String::new(),
+            depth: 0,
+            last_end: None,
+            force_blank_line: false,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.comments_before(self.source.len());
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    fn module(&mut self, module: &SourceModule) -> Result<(), Error> {
+        if let Some(path) = &module.path {
+            let start = self.skip_trivia(0);
+            let end = start
+                + self.source[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | ' ' | '\t')))
+                    .unwrap_or(self.source.len() - start);
+            self.start_item(start);
+            self.push(&format!("module {}", path.render()));
+            self.end_item(end);
+            self.force_blank_line = true;
+        }
+        for import in &module.imports {
+            self.import(import)?;
+        }
+        for entry in &module.entries {
+            self.force_blank_line = self.last_end.is_some();
+            match entry {
+                SourceEntry::Class(class) => self.class(class)?,
+                SourceEntry::Function(fun) => self.function(fun)?,
+                SourceEntry::GlobalLet(field) => self.field(field)?,
+                SourceEntry::Enum(enum_) => self.enum_(enum_),
+            }
+        }
+        Ok(())
+    }
+
+    fn import(&mut self, import: &Import) -> Result<(), Error> {
+        let span = import.span();
+        self.start_item(self.rel(span.low));
+        self.annotations(import.annotations())?;
+        match import {
+            Import::Exact(_, path, _) => self.push(&format!("import {}", path.render())),
+            Import::Selected(_, path, names, _) => {
+                self.push(&format!("import {}.{{{}}}", path.render(), names.iter().format(", ")));
+            }
+            Import::All(_, path, _) => self.push(&format!("import {}.*", path.render())),
+        }
+        self.end_item(self.rel(span.high));
+        Ok(())
+    }
+
+    fn class(&mut self, class: &ClassSource) -> Result<(), Error> {
+        self.start_item(self.rel(class.span.low));
+        self.qualifiers(&class.qualifiers);
+        self.push(&format!("class {}", class.name));
+        if let Some(base) = &class.base {
+            self.push(&format!(" extends {}", base));
+        }
+        self.push(" ");
+
+        let close = self.closing_brace(self.rel(class.span.high));
+        let first = class.members.first().map(|member| match member {
+            MemberSource::Function(fun) => fun.span.low,
+            MemberSource::Field(field) => field.declaration.span.low,
+        });
+        self.open_block(first.map_or(close, |pos| self.rel(pos)));
+        for member in &class.members {
+            match member {
+                MemberSource::Function(fun) => self.function(fun)?,
+                MemberSource::Field(field) => self.field(field)?,
+            }
+        }
+        self.close_block(close);
+        self.end_item(close + 1);
+        Ok(())
+    }
+
+    fn function(&mut self, fun: &FunctionSource) -> Result<(), Error> {
+        self.start_item(self.rel(fun.span.low));
+        self.declaration(&fun.declaration, "func")?;
+
+        // parameters have no spans, locate them in the source to keep the comments in between
+        let open = self.skip_trivia(self.rel(fun.declaration.span.high));
+        let mut close = self.skip_trivia(open + 1);
+        let mut starts = vec![];
+        for i in 0..fun.parameters.len() {
+            let start = self.skip_trivia(close + usize::from(i > 0));
+            starts.push(start);
+            close = self.param_end(start);
+        }
+        // a line comment ends the line, so the parameters go on separate lines
+        let multiline = self.comments[self.next_comment..].iter().any(|span| {
+            let low = self.rel(span.low);
+            low > open && low < close && self.source[low..].starts_with("//")
+        });
+
+        self.push("(");
+        for (i, (param, start)) in fun.parameters.iter().zip(starts).enumerate() {
+            if multiline {
+                self.list_comments(start, true);
+                self.newline();
+                self.push(INDENT);
+                self.inline_comments(start);
+            } else {
+                if i > 0 {
+                    self.push(", ");
+                }
+                self.inline_comments(start);
+            }
+            let qualifiers = param.qualifiers.0.iter().map(|q| format!("{} ", q)).join("");
+            self.push(&format!("{}{}: {}", qualifiers, param.name, param.type_.pretty()));
+            if multiline && i + 1 < fun.parameters.len() {
+                self.push(",");
+            }
+        }
+        if multiline {
+            self.list_comments(close, false);
+            self.newline();
+        } else {
+            self.closing_comments(close);
+        }
+        self.push(")");
+        if let Some(type_) = &fun.type_ {
+            self.push(&format!(" -> {}", type_.pretty()));
+        }
+
+        match &fun.body {
+            None => {
+                self.push(";");
+                let end = self.trim_end(self.rel(fun.span.high));
+                self.end_item(end);
+            }
+            Some(body) => match &body.exprs[..] {
+                [Expr::Return(Some(expr), span)] if self.source[self.rel(span.low)..].starts_with('=') => {
+                    self.push(" = ");
+                    self.expr(expr, 0)?;
+                    self.end_item(self.rel(expr.span().high));
+                }
+                _ => {
+                    self.push(" ");
+                    let close = self.closing_brace(self.rel(fun.span.high));
+                    self.block(body, close)?;
+                    self.end_item(close + 1);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn field(&mut self, field: &FieldSource) -> Result<(), Error> {
+        let decl_end = self.rel(field.declaration.span.high);
+        let end = decl_end + self.source[decl_end..].find(';').map_or(0, |i| i + 1);
+
+        self.start_item(self.rel(field.declaration.span.low));
+        self.declaration(&field.declaration, "let")?;
+        self.push(&format!(": {};", field.type_.pretty()));
+        self.end_item(end);
+        Ok(())
+    }
+
+    fn enum_(&mut self, enum_: &EnumSource) {
+        self.start_item(self.rel(enum_.span.low));
+        self.push(&format!("enum {} ", enum_.name));
+
+        let close = self.closing_brace(self.rel(enum_.span.high));
+        let start = self.rel(enum_.span.low);
+        let mut pos = start + self.source[start..].find('{').unwrap_or(0);
+        self.open_block(close);
+        for member in &enum_.members {
+            // members have no spans, locate them in the source to keep their comments and literals
+            let start = self.find_word(pos, member.name.as_ref()).unwrap_or(pos);
+            let value_start = start + self.source[start..].find('=').map_or(0, |i| i + 1);
+            let value_start =
+                value_start + (self.source[value_start..].len() - self.source[value_start..].trim_start().len());
+            let value_end = value_start
+                + self.source[value_start..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(self.source.len() - value_start);
+            let value = match &self.source[value_start..value_end] {
+                "" => member.value.to_string(),
+                literal => literal.to_owned(),
+            };
+            pos = value_end
+                + (self.source[value_end..].len() - self.source[value_end..].trim_start_matches([' ', '\t']).len());
+            if self.source[pos..].starts_with(',') {
+                pos += 1;
+            }
+
+            self.start_item(start);
+            self.push(&format!("{} = {},", member.name, value));
+            self.end_item(pos);
+        }
+        self.close_block(close);
+        self.end_item(close + 1);
+    }
+
+    fn declaration(&mut self, decl: &Declaration, keyword: &str) -> Result<(), Error> {
+        self.annotations(&decl.annotations)?;
+        self.qualifiers(&decl.qualifiers);
+        self.push(&format!("{} {}", keyword, decl.name));
+        Ok(())
+    }
+
+    fn annotations(&mut self, annotations: &[Annotation]) -> Result<(), Error> {
+        for ann in annotations {
+            self.push(&format!("@{}(", ann.kind));
+            self.exprs(&ann.args)?;
+            self.closing_comments(self.rel(ann.span.high) - 1);
+            self.push(")");
+            self.newline();
+        }
+        Ok(())
+    }
+
+    fn qualifiers(&mut self, qualifiers: &Qualifiers) {
+        for qualifier in &qualifiers.0 {
+            self.push(&format!("{} ", qualifier));
+        }
+    }
+
+    fn block(&mut self, seq: &Seq<SourceAst>, close: usize) -> Result<(), Error> {
+        let first = seq.exprs.first().map_or(close, |expr| self.rel(expr.span().low));
+        self.open_block(first);
+        for stmt in &seq.exprs {
+            self.stmt(stmt)?;
+        }
+        self.close_block(close);
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Expr<SourceAst>) -> Result<(), Error> {
+        let span = stmt.span();
+        self.start_item(self.rel(span.low));
+        match stmt {
+            Expr::If(cond, if_, else_, span) => {
+                let close = self.if_(cond, if_, else_.as_ref(), *span)?;
+                self.end_item(close + 1);
+            }
+            Expr::While(cond, body, span) => {
+                self.push("while ");
+                self.expr(cond, 0)?;
+                self.push(" ");
+                let close = self.closing_brace(self.rel(span.high));
+                self.block(body, close)?;
+                self.end_item(close + 1);
+            }
+            Expr::ForIn(name, array, body, span) => {
+                self.push(&format!("for {} in ", name));
+                self.expr(array, 0)?;
+                self.push(" ");
+                let close = self.closing_brace(self.rel(span.high));
+                self.block(body, close)?;
+                self.end_item(close + 1);
+            }
+            Expr::Switch(matched, cases, default, span) => {
+                let close = self.switch(matched, cases, default.as_ref(), *span)?;
+                self.end_item(close + 1);
+            }
+            _ => {
+                self.expr(stmt, 0)?;
+                let end = self.trim_end(self.rel(span.high));
+                // the semicolon is only included in the span of some statements
+                let semicolon = if self.source[..end].ends_with(';') {
+                    end - 1
+                } else {
+                    self.skip_trivia(end)
+                };
+                self.closing_comments(semicolon);
+                self.push(";");
+                self.end_item(end);
+            }
+        }
+        Ok(())
+    }
+
+    fn if_(
+        &mut self,
+        cond: &Expr<SourceAst>,
+        if_: &Seq<SourceAst>,
+        else_: Option<&Seq<SourceAst>>,
+        span: Span,
+    ) -> Result<usize, Error> {
+        self.push("if ");
+        self.expr(cond, 0)?;
+        self.push(" ");
+        let last = if_.exprs.last().unwrap_or(cond);
+        let if_close = self.next_closing_brace(self.rel(last.span().high));
+        self.block(if_, if_close)?;
+
+        let close = self.closing_brace(self.rel(span.high));
+        match else_ {
+            None => Ok(if_close),
+            Some(else_) => {
+                self.push(" else ");
+                let else_pos = self.skip_trivia(if_close + 1) + "else".len();
+                let is_block = self.source[self.skip_trivia(else_pos)..].starts_with('{');
+                match &else_.exprs[..] {
+                    [Expr::If(cond, if_, else_, span)] if !is_block => self.if_(cond, if_, else_.as_ref(), *span),
+                    _ => {
+                        self.block(else_, close)?;
+                        Ok(close)
+                    }
+                }
+            }
+        }
+    }
+
+    fn switch(
+        &mut self,
+        matched: &Expr<SourceAst>,
+        cases: &[SwitchCase<SourceAst>],
+        default: Option<&Seq<SourceAst>>,
+        span: Span,
+    ) -> Result<usize, Error> {
+        self.push("switch ");
+        self.expr(matched, 0)?;
+        self.push(" ");
+        let close = self.closing_brace(self.rel(span.high));
+        let first = cases.first().map_or(close, |case| self.rel(case.matcher.span().low));
+        self.open_block(first);
+
+        let mut last_end = self.rel(matched.span().high);
+        for SwitchCase { matcher, body } in cases {
+            self.start_item(self.rel(matcher.span().low));
+            self.push("case ");
+            self.expr(matcher, 0)?;
+            self.push(":");
+            self.end_item(self.rel(matcher.span().high));
+            self.case_body(body)?;
+            last_end = self.rel(body.exprs.last().unwrap_or(matcher).span().high);
+        }
+        if let Some(body) = default {
+            let start = self.find_word(last_end, "default").unwrap_or(last_end);
+            self.start_item(start);
+            self.push("default:");
+            self.end_item(start + "default".len());
+            self.case_body(body)?;
+        }
+
+        self.close_block(close);
+        Ok(close)
+    }
+
+    fn case_body(&mut self, body: &Seq<SourceAst>) -> Result<(), Error> {
+        self.depth += 1;
+        for stmt in &body.exprs {
+            self.stmt(stmt)?;
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr<SourceAst>, min_level: u8) -> Result<(), Error> {
+        self.inline_comments(self.rel(expr.span().low));
+        if level(expr) < min_level {
+            self.push("(");
+            self.expr_unwrapped(expr)?;
+            self.push(")");
+        } else {
+            self.expr_unwrapped(expr)?;
+        }
+        Ok(())
+    }
+
+    fn expr_unwrapped(&mut self, expr: &Expr<SourceAst>) -> Result<(), Error> {
+        match expr {
+            Expr::Ident(name, _) => self.push(name.as_ref()),
+            // literals are kept as written
+            Expr::Constant(_, span) | Expr::InterpolatedString(_, _, span) => {
+                let text = &self.source[self.rel(span.low)..self.rel(span.high)];
+                self.push(text);
+            }
+            Expr::ArrayLit(exprs, _, span) => {
+                self.push("[");
+                self.exprs(exprs)?;
+                self.closing_comments(self.rel(span.high) - 1);
+                self.push("]");
+            }
+            Expr::Declare(name, type_, init, _) => {
+                self.push(&format!("let {}", name));
+                if let Some(type_) = type_ {
+                    self.push(&format!(": {}", type_.pretty()));
+                }
+                if let Some(init) = init {
+                    self.push(" = ");
+                    self.expr(init, 0)?;
+                }
+            }
+            Expr::Cast(type_, expr, _) => {
+                self.expr(expr, POSTFIX_LEVEL)?;
+                self.push(&format!(" as {}", type_.pretty()));
+            }
+            Expr::Assign(lhs, rhs, _) => {
+                self.expr(lhs, 1)?;
+                self.push(" = ");
+                self.expr(rhs, 0)?;
+            }
+            Expr::Call(name, type_args, args, span) => {
+                self.push(name.as_ref());
+                if !type_args.is_empty() {
+                    self.push(&format!("<{}>", type_args.iter().map(|typ| typ.pretty()).format(", ")));
+                }
+                self.push("(");
+                self.exprs(args)?;
+                self.closing_comments(self.rel(span.high) - 1);
+                self.push(")");
+            }
+            Expr::MethodCall(context, name, args, span) => {
+                self.expr(context, POSTFIX_LEVEL)?;
+                self.push(&format!(".{}(", name));
+                self.exprs(args)?;
+                self.closing_comments(self.rel(span.high) - 1);
+                self.push(")");
+            }
+            Expr::Member(context, name, _) => {
+                self.expr(context, POSTFIX_LEVEL)?;
+                self.push(&format!(".{}", name));
+            }
+            Expr::ArrayElem(array, index, span) => {
+                self.expr(array, POSTFIX_LEVEL)?;
+                self.push("[");
+                self.expr(index, 0)?;
+                self.closing_comments(self.rel(span.high) - 1);
+                self.push("]");
+            }
+            Expr::New(type_, args, span) => {
+                self.push(&format!("new {}(", type_.pretty()));
+                self.exprs(args)?;
+                self.closing_comments(self.rel(span.high) - 1);
+                self.push(")");
+            }
+            Expr::Return(Some(expr), _) => {
+                self.push("return ");
+                self.expr(expr, 0)?;
+            }
+            Expr::Return(None, _) => self.push("return"),
+            Expr::Conditional(cond, true_, false_, _) => {
+                self.expr(cond, 1)?;
+                self.push(" ? ");
+                self.expr(true_, 0)?;
+                self.push(" : ");
+                self.expr(false_, 0)?;
+            }
+            Expr::BinOp(lhs, rhs, op, _) => {
+                let level = binop_level(*op);
+                // assignments are right-associative, the other operators are left-associative
+                let (lhs_level, rhs_level) = if level == 0 { (1, 0) } else { (level, level + 1) };
+                self.expr(lhs, lhs_level)?;
+                self.push(&format!(" {} ", op.symbol()));
+                self.expr(rhs, rhs_level)?;
+            }
+            Expr::UnOp(expr, op, _) => {
+                self.push(op.symbol());
+                self.expr(expr, POSTFIX_LEVEL)?;
+            }
+            Expr::This(_) => self.push("this"),
+            Expr::Super(_) => self.push("super"),
+            Expr::Break(_) => self.push("break"),
+            Expr::Continue(_) => self.push("continue"),
+            Expr::Null(_) => self.push("null"),
+            Expr::Seq(_)
+            | Expr::Switch(_, _, _, _)
+            | Expr::Goto(_, _)
+            | Expr::If(_, _, _, _)
+            | Expr::While(_, _, _)
+            | Expr::ForIn(_, _, _, _) => {
+                let message = "Statement in an expression position".to_owned();
+                return Err(Error::SyntaxError(message, expr.span()));
+            }
+        }
+        Ok(())
+    }
+
+    fn exprs(&mut self, exprs: &[Expr<SourceAst>]) -> Result<(), Error> {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            self.expr(expr, 0)?;
+        }
+        Ok(())
+    }
+
+    fn start_item(&mut self, pos: usize) {
+        self.comments_before(pos);
+        self.separate(pos);
+        self.out.push_str(&INDENT.repeat(self.depth));
+    }
+
+    fn end_item(&mut self, end: usize) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            let (low, high) = (self.rel(comment.low), self.rel(comment.high));
+            let text = self.source[low..high].trim_end();
+            let on_same_line = low >= end
+                && self.source[end..low]
+                    .chars()
+                    .all(|c| matches!(c, ' ' | '\t' | ';' | ':' | ','));
+            if on_same_line && !text.contains('\n') {
+                self.out.push(' ');
+                self.out.push_str(text);
+                self.next_comment += 1;
+                self.newline_after(high);
+                return;
+            }
+        }
+        self.newline_after(end);
+    }
+
+    fn comments_before(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            let (low, high) = (self.rel(comment.low), self.rel(comment.high));
+            if low >= pos {
+                break;
+            }
+            self.separate(low);
+            let text = self.source[low..high].trim_end();
+            self.out.push_str(&INDENT.repeat(self.depth));
+            self.out.push_str(text);
+            self.next_comment += 1;
+            self.newline_after(high);
+        }
+    }
+
+    // writes out the comments that start before a position in the middle of an item,
+    // so that they stay in front of the token that follows them
+    fn inline_comments(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            let (low, high) = (self.rel(comment.low), self.rel(comment.high));
+            if low >= pos {
+                break;
+            }
+            let text = self.source[low..high].trim_end();
+            if !self.out.ends_with([' ', '(', '[', '\n']) {
+                self.out.push(' ');
+            }
+            self.out.push_str(text);
+            if text.starts_with("//") {
+                self.out.push('\n');
+                self.out.push_str(&INDENT.repeat(self.depth + 1));
+            } else {
+                self.out.push(' ');
+            }
+            self.next_comment += 1;
+        }
+    }
+
+    // writes out the comments before a position in a list laid out one item per line,
+    // the ones that follow an item on the same line stay there and, unless the flag is off,
+    // block comments on the same line as the next item are left to be written in front of it
+    fn list_comments(&mut self, pos: usize, keep_leading: bool) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            let (low, high) = (self.rel(comment.low), self.rel(comment.high));
+            if low >= pos {
+                break;
+            }
+            let is_leading = !self.source[low..].starts_with("//") && !self.source[high..pos].contains('\n');
+            if keep_leading && is_leading {
+                break;
+            }
+            let line = self.source[..low].rsplit('\n').next().unwrap_or_default();
+            if line.trim().is_empty() {
+                self.newline();
+                self.push(INDENT);
+            } else {
+                self.push(" ");
+            }
+            self.out.push_str(self.source[low..high].trim_end());
+            self.next_comment += 1;
+        }
+    }
+
+    // writes out the comments that come before a closing delimiter
+    fn closing_comments(&mut self, pos: usize) {
+        self.inline_comments(pos);
+        if self.out.ends_with(' ') {
+            self.out.pop();
+        }
+    }
+
+    // keeps at most one blank line between items
+    fn separate(&mut self, pos: usize) {
+        let blank = match self.last_end {
+            Some(end) if end <= pos => self.force_blank_line || self.source[end..pos].matches('\n').count() > 1,
+            _ => false,
+        };
+        if blank {
+            self.out.push('\n');
+        }
+        self.force_blank_line = false;
+    }
+
+    // the bound is the start of the first item in the block
+    fn open_block(&mut self, bound: usize) {
+        self.out.push('{');
+        self.depth += 1;
+        self.last_end = None;
+
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            let (low, high) = (self.rel(comment.low), self.rel(comment.high));
+            let text = self.source[low..high].trim_end();
+            let after_brace = self.source[..low].trim_end_matches([' ', '\t']).ends_with('{');
+            if low < bound && after_brace && !text.contains('\n') {
+                self.out.push(' ');
+                self.out.push_str(text);
+                self.next_comment += 1;
+            }
+        }
+        self.out.push('\n');
+    }
+
+    fn close_block(&mut self, close: usize) {
+        self.comments_before(close);
+        self.depth -= 1;
+        if self.out.ends_with("{\n") {
+            self.out.pop();
+        } else {
+            self.out.push_str(&INDENT.repeat(self.depth));
+        }
+        self.out.push('}');
+    }
+
+    fn push(&mut self, str: &str) {
+        self.out.push_str(str);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&INDENT.repeat(self.depth));
+    }
+
+    fn newline_after(&mut self, end: usize) {
+        self.out.push('\n');
+        self.last_end = Some(end);
+    }
+
+    fn rel(&self, pos: Pos) -> usize {
+        usize::from(pos) - usize::from(self.offset)
+    }
+
+    fn comment_at(&self, pos: usize) -> Option<Span> {
+        self.comments.iter().find(|span| self.rel(span.low) == pos).copied()
+    }
+
+    fn skip_trivia(&self, mut pos: usize) -> usize {
+        loop {
+            pos += self.source[pos..].len() - self.source[pos..].trim_start().len();
+            match self.comment_at(pos) {
+                Some(comment) => pos = self.rel(comment.high),
+                None => return pos,
+            }
+        }
+    }
+
+    fn trim_end(&self, end: usize) -> usize {
+        self.source[..end].trim_end().len()
+    }
+
+    // finds the brace closing a block that ends at the given position
+    fn closing_brace(&self, end: usize) -> usize {
+        let mut end = end;
+        loop {
+            end = self.source[..end]
+                .trim_end_matches(|c: char| c.is_whitespace() || c == ';')
+                .len();
+            match self.comments.iter().find(|span| self.rel(span.high) == end) {
+                Some(comment) => end = self.rel(comment.low),
+                None => return end.saturating_sub(1),
+            }
+        }
+    }
+
+    // finds the next closing brace after a position, skipping over comments
+    fn next_closing_brace(&self, from: usize) -> usize {
+        let mut pos = from;
+        loop {
+            pos = self.skip_trivia(pos);
+            match self.source[pos..].chars().next() {
+                Some('{' | ';') => pos += 1,
+                _ => return pos,
+            }
+        }
+    }
+
+    // finds the comma or the parenthesis that ends a parameter
+    fn param_end(&self, from: usize) -> usize {
+        let mut pos = from;
+        let mut depth = 0;
+        loop {
+            pos = self.skip_trivia(pos);
+            match self.source[pos..].chars().next() {
+                Some('<') => depth += 1,
+                Some('>') => depth -= 1,
+                Some(',' | ')') if depth == 0 => return pos,
+                Some(_) => {}
+                None => return pos,
+            }
+            pos += 1;
+        }
+    }
+
+    fn find_word(&self, from: usize, word: &str) -> Option<usize> {
+        let mut pos = from;
+        while let Some(i) = self.source[pos..].find(word) {
+            let start = pos + i;
+            let end = start + word.len();
+            let in_comment = self
+                .comments
+                .iter()
+                .any(|span| self.rel(span.low) <= start && start < self.rel(span.high));
+            let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+            let bounded =
+                !self.source[..start].ends_with(is_ident_char) && !self.source[end..].starts_with(is_ident_char);
+            if !in_comment && bounded {
+                return Some(start);
+            }
+            pos = end;
+        }
+        None
+    }
+}
+
+const POSTFIX_LEVEL: u8 = 7;
+
+// mirrors the precedence groups of the expression grammar in the parser,
+// which groups the logical and bitwise operators together unlike BinOp::precedence
+fn level(expr: &Expr<SourceAst>) -> u8 {
+    match expr {
+        Expr::Conditional(_, _, _, _) | Expr::Assign(_, _, _) => 0,
+        Expr::BinOp(_, _, op, _) => binop_level(*op),
+        Expr::UnOp(_, _, _) | Expr::New(_, _, _) => 6,
+        Expr::ArrayElem(_, _, _) | Expr::MethodCall(_, _, _, _) | Expr::Member(_, _, _) | Expr::Cast(_, _, _) => {
+            POSTFIX_LEVEL
+        }
+        _ => 8,
+    }
+}
+
+fn binop_level(op: BinOp) -> u8 {
+    match op {
+        BinOp::AssignAdd
+        | BinOp::AssignSubtract
+        | BinOp::AssignMultiply
+        | BinOp::AssignDivide
+        | BinOp::AssignOr
+        | BinOp::AssignAnd => 0,
+        BinOp::LogicOr | BinOp::LogicAnd | BinOp::Or | BinOp::Xor | BinOp::And => 1,
+        BinOp::Equal | BinOp::NotEqual => 2,
+        BinOp::Less | BinOp::LessEqual | BinOp::Greater | BinOp::GreaterEqual => 3,
+        BinOp::Add | BinOp::Subtract => 4,
+        BinOp::Multiply | BinOp::Divide | BinOp::Modulo => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::source_map::Files;
+
+    fn format(source: &str) -> String {
+        let mut files = Files::new();
+        files.add(PathBuf::from("test.reds"), source.to_owned());
+        let file = files.files().next().unwrap();
+        let output = format_file(file).unwrap();
+        assert_eq!(
+            without_spans(&parse_str(&output).unwrap()),
+            without_spans(&parse_file(file).unwrap()),
+            "formatting changed the AST"
+        );
+        output
+    }
+
+    // renders a module with the spans removed, so that modules can be compared regardless of layout
+    fn without_spans(module: &SourceModule) -> String {
+        let mut debug = format!("{:?}", module);
+        while let Some(start) = debug.find("Span {") {
+            let end = start + debug[start..].find('}').unwrap() + 1;
+            debug.replace_range(start..end, "_");
+        }
+        debug
+    }
+
+    #[test]
+    fn format_declarations() {
+        let source = r#"
+module   Test.Module
+import Other.*
+import Another.{A,B}
+@replaceMethod(Class)   private  final func Method(opt a:Int32,b : array< String >)->Bool{return a>0;}
+public class Foo extends Bar{let x:Int32;
+
+
+func Get()->Int32=this.x
+native func Native();
+}
+enum Kind{First=0,Second=0x10}
+"#;
+        let expected = r#"module Test.Module
+
+import Other.*
+import Another.{A, B}
+
+@replaceMethod(Class)
+private final func Method(opt a: Int32, b: array<String>) -> Bool {
+  return a > 0;
+}
+
+public class Foo extends Bar {
+  let x: Int32;
+
+  func Get() -> Int32 = this.x
+  native func Native();
+}
+
+enum Kind {
+  First = 0,
+  Second = 0x10,
+}
+"#;
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn format_statements() {
+        let source = r#"
+func Test(items: array<Int32>) {
+    let a=(1+2)*3-(4-5);
+  if a==1||a==2&&!true{ a+=1; } else if a>2 {a = -(a+1);}
+  else {
+      a=a as Int32;
+  }
+  for item in items{ if item>0 { continue; } }
+  while(a>0){a-=1;}
+  switch a{case 1: case 2: a=0; break; default: return;}
+  let s = s"value: \(a)";
+  (new Foo()).Bar()[0].x = a>0?1:2;
+}
+"#;
+        let expected = r#"func Test(items: array<Int32>) {
+  let a = (1 + 2) * 3 - (4 - 5);
+  if a == 1 || a == 2 && !true {
+    a += 1;
+  } else if a > 2 {
+    a = -(a + 1);
+  } else {
+    a = a as Int32;
+  }
+  for item in items {
+    if item > 0 {
+      continue;
+    }
+  }
+  while a > 0 {
+    a -= 1;
+  }
+  switch a {
+    case 1:
+    case 2:
+      a = 0;
+      break;
+    default:
+      return;
+  }
+  let s = s"value: \(a)";
+  (new Foo()).Bar()[0].x = a > 0 ? 1 : 2;
+}
+"#;
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn format_comments() {
+        let source = r#"// header comment
+
+/* documentation */
+func Test() -> Int32 { // trailing
+  // leading
+  let a = 1; // after a
+
+
+  /* block */ let b = "// not a comment";
+  if a > 0 {
+    return a;
+    // end of if
+  }
+  return b; }
+// the end
+"#;
+        let expected = r#"// header comment
+
+/* documentation */
+func Test() -> Int32 { // trailing
+  // leading
+  let a = 1; // after a
+
+  /* block */
+  let b = "// not a comment";
+  if a > 0 {
+    return a;
+    // end of if
+  }
+  return b;
+}
+// the end
+"#;
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn keep_comments_in_place() {
+        let source = r#"
+@wrapMethod(/* target */ Foo)
+func Test(/* first */ a: Int32, // second
+  b: array<Int32> /* last */) -> Int32 {
+  let c = /* one */ 1 + /* two */ Call(a, /* arg */ b /* end */);
+  return c /* before semicolon */;
+}
+"#;
+        let expected = r#"@wrapMethod(/* target */ Foo)
+func Test(
+  /* first */ a: Int32, // second
+  b: array<Int32> /* last */
+) -> Int32 {
+  let c = /* one */ 1 + /* two */ Call(a, /* arg */ b /* end */);
+  return c /* before semicolon */;
+}
+"#;
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn format_parameters_with_line_comments() {
+        let source = "func A(\n a: Int32, // first\n b: Int32 /* second */\n) -> Int32 {\n  return a;\n}\n";
+        let expected = "func A(\n  a: Int32, // first\n  b: Int32 /* second */\n) -> Int32 {\n  return a;\n}\n";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+
+        let source = "class A {\n  func B(a: Int32, // first\n    b: Int32) {}\n}\n";
+        let expected = "class A {\n  func B(\n    a: Int32, // first\n    b: Int32\n  ) {}\n}\n";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn reject_statement_in_expression_position() {
+        let mut files = Files::new();
+        files.add(PathBuf::from("test.reds"), String::new());
+        let file = files.files().next().unwrap();
+        let mut formatter = Formatter::new(file);
+
+        let stmt = Expr::While(Box::new(Expr::Null(Span::ZERO)), Seq::new(vec![]), Span::ZERO);
+        assert!(matches!(formatter.expr(&stmt, 0), Err(Error::SyntaxError(_, _))));
+    }
+}
@@ -1,8 +1,11 @@
 #![feature(stmt_expr_attributes)]
 
+pub mod asm;
 pub mod assembler;
 pub mod cte;
 pub mod error;
+pub mod formatter;
+pub mod lint;
 #[allow(clippy::redundant_closure_call)]
 pub mod parser;
 pub mod scope;
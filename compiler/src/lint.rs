@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use redscript::ast::{Expr, Seq, Span};
+use redscript::bundle::{ConstantPool, PoolIndex};
+use redscript::definition::Local;
+use strum::{Display, EnumString};
+
+use crate::error::Error;
+use crate::scope::{Reference, Value};
+use crate::typechecker::TypedAst;
+use crate::unit::Diagnostic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Lint {
+    UnusedLocal,
+    UnusedImport,
+    UnreachableCode,
+    SelfAssignment,
+}
+
+impl Lint {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UnusedLocal => "W0002",
+            Lint::UnusedImport => "W0003",
+            Lint::UnreachableCode => "W0004",
+            Lint::SelfAssignment => "W0005",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    allowed: HashSet<Lint>,
+}
+
+impl LintConfig {
+    pub fn allow(mut self, lint: Lint) -> Self {
+        self.allowed.insert(lint);
+        self
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        !self.allowed.contains(&lint)
+    }
+}
+
+/// Runs the function body lints on a type-checked function body.
+pub fn check_function(body: &Seq<TypedAst>, pool: &ConstantPool) -> Result<Vec<Diagnostic>, Error> {
+    let mut linter = Linter::default();
+    linter.on_seq(body);
+
+    let mut diagnostics = vec![];
+    for (local, span) in linter.declared {
+        let mangled = pool.def_name(local)?;
+        let name = local_name(&mangled);
+        if !name.starts_with('_') && !linter.used.contains(&local) {
+            let msg = format!("Local variable '{}' is never used", name);
+            diagnostics.push(Diagnostic::Lint(Lint::UnusedLocal, msg, span));
+        }
+    }
+    for span in linter.unreachable {
+        let msg = "Unreachable code".to_owned();
+        diagnostics.push(Diagnostic::Lint(Lint::UnreachableCode, msg, span));
+    }
+    for (value, span) in linter.self_assigned {
+        let name = match value {
+            Value::Local(idx) => local_name(&pool.def_name(idx)?).to_owned(),
+            Value::Parameter(idx) => pool.def_name(idx)?.to_string(),
+        };
+        let msg = format!("Variable '{}' is assigned to itself", name);
+        diagnostics.push(Diagnostic::Lint(Lint::SelfAssignment, msg, span));
+    }
+    Ok(diagnostics)
+}
+
+#[derive(Default)]
+struct Linter {
+    declared: Vec<(PoolIndex<Local>, Span)>,
+    used: HashSet<PoolIndex<Local>>,
+    unreachable: Vec<Span>,
+    self_assigned: Vec<(Value, Span)>,
+}
+
+impl Linter {
+    fn on_seq(&mut self, seq: &Seq<TypedAst>) {
+        let mut exprs = seq.exprs.iter();
+        for expr in exprs.by_ref() {
+            self.on_expr(expr);
+            if diverges(expr) {
+                break;
+            }
+        }
+        let rest = exprs.fold(None, |acc: Option<Span>, expr| {
+            self.on_expr(expr);
+            Some(acc.map_or(expr.span(), |span| span.merge(expr.span())))
+        });
+        if let Some(span) = rest {
+            self.unreachable.push(span);
+        }
+    }
+
+    fn on_expr(&mut self, expr: &Expr<TypedAst>) {
+        match expr {
+            Expr::Ident(Reference::Value(Value::Local(local)), _) => {
+                self.used.insert(*local);
+            }
+            Expr::Declare(local, _, init, span) => {
+                self.declared.push((*local, *span));
+                if let Some(init) = init {
+                    self.on_expr(init);
+                }
+            }
+            Expr::Assign(lhs, rhs, span) => {
+                match (lhs.as_ref(), rhs.as_ref()) {
+                    (Expr::Ident(Reference::Value(lhs), _), Expr::Ident(Reference::Value(rhs), _)) if lhs == rhs => {
+                        self.self_assigned.push((lhs.clone(), *span));
+                    }
+                    _ => {}
+                }
+                // assigning to a local does not count as using it
+                if !matches!(lhs.as_ref(), Expr::Ident(Reference::Value(Value::Local(_)), _)) {
+                    self.on_expr(lhs);
+                }
+                self.on_expr(rhs);
+            }
+            Expr::ArrayLit(exprs, _, _) | Expr::Call(_, _, exprs, _) | Expr::New(_, exprs, _) => {
+                exprs.iter().for_each(|expr| self.on_expr(expr));
+            }
+            Expr::InterpolatedString(_, parts, _) => {
+                parts.iter().for_each(|(expr, _)| self.on_expr(expr));
+            }
+            Expr::MethodCall(context, _, args, _) => {
+                self.on_expr(context);
+                args.iter().for_each(|expr| self.on_expr(expr));
+            }
+            Expr::Cast(_, expr, _) | Expr::Member(expr, _, _) | Expr::UnOp(expr, _, _) => self.on_expr(expr),
+            Expr::ArrayElem(lhs, rhs, _) | Expr::BinOp(lhs, rhs, _, _) => {
+                self.on_expr(lhs);
+                self.on_expr(rhs);
+            }
+            Expr::Return(Some(expr), _) => self.on_expr(expr),
+            Expr::Seq(seq) => self.on_seq(seq),
+            Expr::Switch(matched, cases, default, _) => {
+                self.on_expr(matched);
+                for case in cases {
+                    self.on_expr(&case.matcher);
+                    self.on_seq(&case.body);
+                }
+                if let Some(default) = default {
+                    self.on_seq(default);
+                }
+            }
+            Expr::If(cond, if_, else_, _) => {
+                self.on_expr(cond);
+                self.on_seq(if_);
+                if let Some(else_) = else_ {
+                    self.on_seq(else_);
+                }
+            }
+            Expr::Conditional(cond, true_, false_, _) => {
+                self.on_expr(cond);
+                self.on_expr(true_);
+                self.on_expr(false_);
+            }
+            Expr::While(cond, body, _) => {
+                self.on_expr(cond);
+                self.on_seq(body);
+            }
+            Expr::ForIn(_, array, body, _) => {
+                self.on_expr(array);
+                self.on_seq(body);
+            }
+            Expr::Ident(_, _)
+            | Expr::Constant(_, _)
+            | Expr::Return(None, _)
+            | Expr::Goto(_, _)
+            | Expr::This(_)
+            | Expr::Super(_)
+            | Expr::Break(_)
+            | Expr::Continue(_)
+            | Expr::Null(_) => {}
+        }
+    }
+}
+
+fn local_name(mangled: &str) -> &str {
+    mangled.split('$').next().unwrap_or(mangled)
+}
+
+fn diverges(expr: &Expr<TypedAst>) -> bool {
+    match expr {
+        Expr::Return(_, _) | Expr::Break(_) | Expr::Continue(_) => true,
+        Expr::Seq(seq) => seq.exprs.iter().any(diverges),
+        Expr::If(_, if_, Some(else_), _) => if_.exprs.iter().any(diverges) && else_.exprs.iter().any(diverges),
+        _ => false,
+    }
+}
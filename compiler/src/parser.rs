@@ -5,12 +5,13 @@ use peg::str::LineCol;
 use redscript::ast::{BinOp, Constant, Expr, Ident, Literal, Pos, Seq, SourceAst, Span, SwitchCase, TypeName, UnOp};
 use redscript::definition::Visibility;
 use redscript::Ref;
-use strum::EnumString;
+use strum::{Display, EnumString};
 
+use crate::error::Error;
 use crate::source_map::File;
 use crate::symbol::{Import, ModulePath};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SourceModule {
     pub path: Option<ModulePath>,
     pub imports: Vec<Import>,
@@ -85,7 +86,8 @@ pub struct EnumMember {
     pub value: i64,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Display)]
+#[strum(serialize_all = "lowercase")]
 pub enum Qualifier {
     Public,
     Protected,
@@ -96,8 +98,10 @@ pub enum Qualifier {
     Const,
     Native,
     Exec,
+    #[strum(serialize = "cb")]
     Callback,
     Out,
+    #[strum(serialize = "opt")]
     Optional,
     Quest,
     ImportOnly,
@@ -105,7 +109,7 @@ pub enum Qualifier {
 }
 
 #[derive(Debug)]
-pub struct Qualifiers(Vec<Qualifier>);
+pub struct Qualifiers(pub(crate) Vec<Qualifier>);
 
 impl Qualifiers {
     pub fn visibility(&self) -> Option<Visibility> {
@@ -137,7 +141,7 @@ pub struct Annotation {
     pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, EnumString)]
+#[derive(Debug, PartialEq, Eq, EnumString, Display)]
 #[strum(serialize_all = "camelCase")]
 pub enum AnnotationKind {
     ReplaceMethod,
@@ -156,6 +160,77 @@ pub fn parse_str(str: &str) -> Result<SourceModule, ParseError<LineCol>> {
     lang::module(str, Pos::ZERO)
 }
 
+/// Returns the spans of all comments in a file.
+pub fn parse_comments(file: &File) -> Vec<Span> {
+    lang::comments(file.source(), file.byte_offset()).unwrap_or_default()
+}
+
+/// Parses a file skipping over top-level declarations that fail to parse, returns all syntax errors encountered.
+pub fn parse_file_with_recovery(file: &File) -> (SourceModule, Vec<Error>) {
+    if let Ok(module) = parse_file(file) {
+        return (module, vec![]);
+    }
+    let source = file.source();
+    let mut bounds = declaration_starts(source);
+    bounds.push(source.len());
+
+    let mut module = SourceModule::default();
+    let mut errors = vec![];
+    let mut i = 0;
+    while i + 1 < bounds.len() {
+        let mut j = i + 1;
+        loop {
+            let chunk = &source[bounds[i]..bounds[j]];
+            let offset = file.byte_offset() + bounds[i];
+            match lang::module_items(chunk, offset) {
+                Ok(items) => {
+                    for item in items {
+                        match item {
+                            ModuleItem::Path(path) => module.path = module.path.or(Some(path)),
+                            ModuleItem::Import(import) => module.imports.push(import),
+                            ModuleItem::Entry(entry) => module.entries.push(entry),
+                        }
+                    }
+                    break;
+                }
+                // the declaration might span multiple chunks, try again with the next one included
+                Err(err) if j + 1 < bounds.len() && err.location.offset >= chunk.trim_end().len() => j += 1,
+                Err(err) => {
+                    errors.push(syntax_error(offset, &err));
+                    break;
+                }
+            }
+        }
+        i = j;
+    }
+    (module, errors)
+}
+
+pub(crate) fn syntax_error(offset: Pos, err: &ParseError<LineCol>) -> Error {
+    let message = format!("Syntax error, expected {}", err.expected);
+    let pos = offset + err.location.offset;
+    Error::SyntaxError(message, Span::new(pos, pos + 1))
+}
+
+// unindented lines that could start a declaration are used as recovery points
+fn declaration_starts(source: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut line_start = 0;
+    while let Some(len) = source[line_start..].find('\n') {
+        line_start += len + 1;
+        if source[line_start..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '@') {
+            starts.push(line_start);
+        }
+    }
+    starts
+}
+
+enum ModuleItem {
+    Path(ModulePath),
+    Import(Import),
+    Entry(SourceEntry),
+}
+
 peg::parser! {
     grammar lang(offset: Pos) for str {
         use peg::ParseLiteral;
@@ -174,6 +249,14 @@ peg::parser! {
 
         rule line_comment() = "//" $(!['\n'] [_])*
 
+        rule comment_or_literal() -> Option<Span>
+            = pos:pos() (comment() / line_comment()) end:pos() { Some(Span::new(pos, end)) }
+            / interpolated_string() { None }
+            / escaped_string() { None }
+            / [_] { None }
+
+        pub rule comments() -> Vec<Span> = spans:comment_or_literal()* { spans.into_iter().flatten().collect() }
+
         rule qualifier() -> Qualifier
             = keyword("public") { Qualifier::Public }
             / keyword("protected") { Qualifier::Protected }
@@ -214,14 +297,20 @@ peg::parser! {
         rule keyword(id: &'static str) -> () =
             ##parse_string_literal(id) !['0'..='9' | 'a'..='z' | 'A'..='Z' | '_']
 
+        rule int_postfix() -> &'input str = quiet!{ $("ul" / ['u' | 'l']) }
+
         rule number() -> Constant
-            = n:$(['0'..='9' | '.']+) postfix:$(['u' | 'l' | 'd'])?
-            {? if postfix == Some("d") { n.parse::<f64>().or(Err("valid double")).map(Constant::F64) }
-               else if n.contains('.') { n.parse::<f32>().or(Err("valid float")).map(Constant::F32) }
-               else if postfix == Some("l") { n.parse::<i64>().or(Err("valid 64-bit int")).map(Constant::I64) }
-               else if postfix == Some("u") { n.parse::<u32>().or(Err("valid 32-bit uint")).map(Constant::U32) }
-               else { n.parse::<i32>().or(Err("valid 32-bit int")).map(Constant::I32) }
-            }
+            = quiet!{ "0x" } n:quiet!{ $(['0'..='9' | 'a'..='f' | 'A'..='F'] ['0'..='9' | 'a'..='f' | 'A'..='F' | '_']*) }
+              postfix:int_postfix()?
+            {? int_literal(n, 16, postfix) }
+            / quiet!{ "0b" } n:quiet!{ $(['0' | '1'] ['0' | '1' | '_']*) } postfix:int_postfix()?
+            {? int_literal(n, 2, postfix) }
+            / n:$(['0'..='9' | '.'] quiet!{ ['0'..='9' | '.' | '_']* }) postfix:quiet!{ $("ul" / ['u' | 'l' | 'd']) }?
+            {? decimal_literal(n, postfix) }
+
+        rule signed_number() -> Constant
+            = "-" n:number() {? negated(n) }
+            / n:number() { n }
 
         rule escaped_char() -> String
             = !['\\' | '\"'] c:$([_]) { String::from(c) }
@@ -253,7 +342,7 @@ peg::parser! {
         rule constant() -> Constant
             = keyword("true") { Constant::Bool(true) }
             / keyword("false") { Constant::Bool(false) }
-            / n:number() { n }
+            / n:signed_number() { n }
             / type_:literal_type()? str:escaped_string()
                 { Constant::String(type_.unwrap_or(Literal::String), Ref::new(str)) }
 
@@ -306,7 +395,7 @@ peg::parser! {
             { EnumSource { name, members, span: Span::new(pos, end) } }
 
         rule enum_member() -> EnumMember
-            = name:ident() _ "=" _ value:number()
+            = name:ident() _ "=" _ value:signed_number()
             {? match value {
                  Constant::I32(value) => Ok(EnumMember { name, value: value.into() }),
                  Constant::I64(value) => Ok(EnumMember { name, value }),
@@ -335,6 +424,13 @@ peg::parser! {
             _ path:module_path()? _ imports:(import() ** _) _ entries:(source_entry() ** _) _
             { SourceModule { path, imports, entries } }
 
+        rule module_item() -> ModuleItem
+            = path:module_path() { ModuleItem::Path(path) }
+            / import:import() { ModuleItem::Import(import) }
+            / entry:source_entry() { ModuleItem::Entry(entry) }
+
+        pub rule module_items() -> Vec<ModuleItem> = _ items:(module_item() ** _) _ { items }
+
         rule switch() -> Expr<SourceAst>
             = pos:pos() keyword("switch") _ matcher:expr() _ "{" _ cases:(case() ** _) _ default:default()? _ "}" _ ";"? end:pos()
             { Expr::Switch(Box::new(matcher), cases, default, Span::new(pos, end)) }
@@ -359,6 +455,7 @@ peg::parser! {
             { Expr::If(Box::new(cond), if_, else_, Span::new(pos, end)) }
         rule else_() -> Seq<SourceAst>
             = keyword("else") _ "{" _ body:seq() _ "}" { body }
+            / keyword("else") _ if_:if_() { Seq::new(vec![if_]) }
 
         pub rule stmt() -> Expr<SourceAst>
             = while_: while_() { while_ }
@@ -367,6 +464,7 @@ peg::parser! {
             / switch: switch() { switch }
             / pos:pos() keyword("return") _ val:expr()? _ ";" end:pos() { Expr::Return(val.map(Box::new), Span::new(pos, end)) }
             / pos:pos() keyword("break") _ ";" end:pos() { Expr::Break(Span::new(pos, end)) }
+            / pos:pos() keyword("continue") _ ";" end:pos() { Expr::Continue(Span::new(pos, end)) }
             / let_:let() { let_ }
             / expr:expr() _ ";" { expr }
 
@@ -409,7 +507,7 @@ peg::parser! {
             --
             "!" _ expr:@ { unop(expr, UnOp::LogicNot) }
             "~" _ expr:@ { unop(expr, UnOp::BitNot) }
-            "-" _ expr:@ { unop(expr, UnOp::Neg) }
+            "-" !['0'..='9' | '.'] _ expr:@ { unop(expr, UnOp::Neg) }
 
             pos:pos() keyword("new") _ id:ident() _ "(" _ params:commasep(<expr()>) _ ")" end:pos() {
                 Expr::New(TypeName::basic_owned(id.to_owned()), params, Span::new(pos, end))
@@ -459,12 +557,71 @@ peg::parser! {
     }
 }
 
+fn decimal_literal(digits: &str, postfix: Option<&str>) -> Result<Constant, &'static str> {
+    if digits.contains("_.") || digits.ends_with('_') {
+        return Err("a digit after '_'");
+    }
+    if postfix == Some("d") {
+        digits
+            .replace('_', "")
+            .parse::<f64>()
+            .or(Err("valid double"))
+            .map(Constant::F64)
+    } else if digits.contains('.') {
+        digits
+            .replace('_', "")
+            .parse::<f32>()
+            .or(Err("valid float"))
+            .map(Constant::F32)
+    } else {
+        int_literal(digits, 10, postfix)
+    }
+}
+
+fn int_literal(digits: &str, radix: u32, postfix: Option<&str>) -> Result<Constant, &'static str> {
+    if digits.ends_with('_') {
+        return Err("a digit after '_'");
+    }
+    let digits = digits.replace('_', "");
+    match postfix {
+        Some("l") => i64::from_str_radix(&digits, radix)
+            .map(Constant::I64)
+            .or(Err("an Int64 literal no greater than 9223372036854775807")),
+        Some("u") => u32::from_str_radix(&digits, radix).map(Constant::U32).or(Err(
+            "a Uint32 literal no greater than 4294967295 (use the 'ul' suffix for Uint64)",
+        )),
+        Some("ul") => u64::from_str_radix(&digits, radix)
+            .map(Constant::U64)
+            .or(Err("a Uint64 literal no greater than 18446744073709551615")),
+        // hex and binary literals wrap around like in C, so that bit masks can use the sign bit
+        _ if radix != 10 => u32::from_str_radix(&digits, radix)
+            .map(|n| Constant::I32(n as i32))
+            .or(Err(
+                "an Int32 literal no greater than 0xFFFF_FFFF (use the 'l' suffix for Int64)",
+            )),
+        _ => i32::from_str_radix(&digits, radix).map(Constant::I32).or(Err(
+            "an Int32 literal no greater than 2147483647 (use the 'l' suffix for Int64)",
+        )),
+    }
+}
+
 #[inline]
 fn binop(lhs: Expr<SourceAst>, rhs: Expr<SourceAst>, op: BinOp) -> Expr<SourceAst> {
     let span = lhs.span().merge(rhs.span());
     Expr::BinOp(Box::new(lhs), Box::new(rhs), op, span)
 }
 
+fn negated(constant: Constant) -> Result<Constant, &'static str> {
+    match constant {
+        Constant::I32(n) => Ok(Constant::I32(n.wrapping_neg())),
+        Constant::I64(n) => Ok(Constant::I64(n.wrapping_neg())),
+        Constant::F32(n) => Ok(Constant::F32(-n)),
+        Constant::F64(n) => Ok(Constant::F64(-n)),
+        Constant::U32(_) | Constant::U64(_) => Err("a signed literal, unsigned literals can't be negated"),
+        _ => Err("signed number"),
+    }
+}
+
 #[inline]
 fn unop(expr: Expr<SourceAst>, op: UnOp) -> Expr<SourceAst> {
     let span = expr.span();
@@ -474,6 +631,7 @@ fn unop(expr: Expr<SourceAst>, op: UnOp) -> Expr<SourceAst> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::source_map::Files;
 
     #[test]
     fn parse_ternary_op() {
@@ -551,6 +709,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_if_else_chain() {
+        let stmt = lang::stmt("if a { A(); } else if b { B(); } else { C(); }", Pos::ZERO).unwrap();
+        assert_eq!(
+            format!("{:?}", stmt),
+            r#"If(Ident(Owned("a"), Span { low: Pos(3), high: Pos(4) }), Seq { exprs: [Call(Owned("A"), [], [], Span { low: Pos(7), high: Pos(10) })] }, Some(Seq { exprs: [If(Ident(Owned("b"), Span { low: Pos(22), high: Pos(23) }), Seq { exprs: [Call(Owned("B"), [], [], Span { low: Pos(26), high: Pos(29) })] }, Some(Seq { exprs: [Call(Owned("C"), [], [], Span { low: Pos(40), high: Pos(43) })] }), Span { low: Pos(19), high: Pos(46) })] }), Span { low: Pos(0), high: Pos(46) })"#
+        );
+    }
+
+    #[test]
+    fn parse_while_continue() {
+        let stmt = lang::stmt("while a { continue; }", Pos::ZERO).unwrap();
+        assert_eq!(
+            format!("{:?}", stmt),
+            r#"While(Ident(Owned("a"), Span { low: Pos(6), high: Pos(7) }), Seq { exprs: [Continue(Span { low: Pos(10), high: Pos(19) })] }, Span { low: Pos(0), high: Pos(21) })"#
+        );
+    }
+
+    #[test]
+    fn parse_number_literals() {
+        let literals = [
+            ("0xFF", Constant::I32(255)),
+            ("0b1010_1010", Constant::I32(170)),
+            ("1_000_000", Constant::I32(1_000_000)),
+            ("0x7FFF_FFFF_FFFF_FFFFl", Constant::I64(i64::MAX)),
+            ("0xFFu", Constant::U32(255)),
+            ("18446744073709551615ul", Constant::U64(u64::MAX)),
+            ("-42", Constant::I32(-42)),
+            ("-0x10l", Constant::I64(-16)),
+            ("-1.5", Constant::F32(-1.5)),
+            ("0x8000_0000", Constant::I32(i32::MIN)),
+            ("0xFFFF_FFFF", Constant::I32(-1)),
+            ("0b1000_0000_0000_0000_0000_0000_0000_0001", Constant::I32(i32::MIN + 1)),
+            ("-0x8000_0000", Constant::I32(i32::MIN)),
+        ];
+        for (source, expected) in literals {
+            let expr = lang::expr(source, Pos::ZERO).unwrap();
+            assert_eq!(
+                format!("{:?}", expr.as_constant().unwrap().0),
+                format!("{:?}", expected)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_negation() {
+        let expr = lang::expr("x -1", Pos::ZERO).unwrap();
+        assert!(matches!(expr, Expr::BinOp(_, _, BinOp::Subtract, _)));
+        let expr = lang::expr("- 1", Pos::ZERO).unwrap();
+        assert!(matches!(expr, Expr::UnOp(_, UnOp::Neg, _)));
+    }
+
+    #[test]
+    fn parse_out_of_range_literal() {
+        let err = lang::expr("2147483648", Pos::ZERO).unwrap_err();
+        assert_eq!(
+            err.expected.to_string(),
+            "an Int32 literal no greater than 2147483647 (use the 'l' suffix for Int64)"
+        );
+        let err = lang::expr("0x1_0000_0000", Pos::ZERO).unwrap_err();
+        assert_eq!(
+            err.expected.to_string(),
+            "an Int32 literal no greater than 0xFFFF_FFFF (use the 'l' suffix for Int64)"
+        );
+    }
+
+    #[test]
+    fn parse_negated_unsigned_literal() {
+        for source in ["-1u", "-1ul"] {
+            let err = lang::expr(source, Pos::ZERO).unwrap_err();
+            assert_eq!(
+                err.expected.to_string(),
+                "a signed literal, unsigned literals can't be negated",
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn parse_trailing_underscore() {
+        for source in ["1_", "0xFF_", "1_.5", "1.5_", "1.5_d", "1_000_u"] {
+            let err = lang::expr(source, Pos::ZERO).unwrap_err();
+            assert_eq!(err.expected.to_string(), "a digit after '_'", "{}", source);
+        }
+    }
+
+    #[test]
+    fn parse_with_recovery() {
+        let mut files = Files::new();
+        files.add(
+            "test.reds".into(),
+            "module Test\n@addMethod(A)\nfunc A() -> Int32 { return 1 }\n@addMethod(A)\nfunc B() {}\nfunc C( {}"
+                .to_owned(),
+        );
+        let (module, errors) = parse_file_with_recovery(files.files().next().unwrap());
+
+        assert_eq!(module.path.unwrap().parts.len(), 1);
+        assert_eq!(module.entries.len(), 1);
+        assert_eq!(module.entries[0].annotations().len(), 1);
+        assert_eq!(errors.len(), 2);
+    }
+
     #[test]
     fn parse_switch_case() {
         let stmt = lang::stmt(
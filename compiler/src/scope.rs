@@ -1,7 +1,13 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use hamt_sync::Map;
 use redscript::ast::{Ident, TypeName};
 use redscript::bundle::{ConstantPool, PoolError, PoolIndex};
-use redscript::definition::{AnyDefinition, Class, Definition, Enum, Field, Function, Local, Parameter, Type};
+use redscript::definition::{
+    AnyDefinition, BitField, Class, Definition, Enum, Field, Function, Local, Parameter, Type
+};
 
 use crate::error::{Cause, Error};
 use crate::symbol::{FunctionSignature, Symbol};
@@ -11,6 +17,7 @@ pub struct Scope {
     symbols: Map<Ident, Symbol>,
     references: Map<Ident, Value>,
     types: Map<Ident, PoolIndex<Type>>,
+    usage: SymbolUsage,
 
     pub this: Option<PoolIndex<Class>>,
     pub function: Option<PoolIndex<Function>>,
@@ -19,7 +26,8 @@ pub struct Scope {
 impl Scope {
     pub fn new(pool: &ConstantPool) -> Result<Self, Error> {
         let mut types = Map::new();
-        for (idx, def) in pool.roots() {
+        for entry in pool.roots() {
+            let (idx, def) = entry?;
             if let AnyDefinition::Type(_) = def.value {
                 let ident = Ident::Owned(pool.def_name(idx)?);
                 types = types.insert(ident, idx.cast());
@@ -30,6 +38,7 @@ impl Scope {
             symbols: Map::new(),
             references: Map::new(),
             types,
+            usage: SymbolUsage::default(),
             this: None,
             function: None,
         };
@@ -75,8 +84,36 @@ impl Scope {
         }
     }
 
+    /// Starts recording the symbols resolved through this scope and all scopes derived from it.
+    pub fn track_usage(&mut self) -> SymbolUsage {
+        self.usage = SymbolUsage::default();
+        self.usage.clone()
+    }
+
+    /// Records the overload picked for a call, only the overload counts as used and not the others with the same name.
+    pub fn use_function(&self, index: PoolIndex<Function>) {
+        self.usage.0.borrow_mut().insert(index.cast());
+    }
+
+    fn find_symbol(&self, name: &Ident) -> Option<&Symbol> {
+        let res = self.symbols.find(name);
+        match res {
+            Some(Symbol::Functions(_)) | None => {}
+            Some(symbol) => self.usage.0.borrow_mut().extend(symbol.definitions()),
+        }
+        res
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = (&Ident, &Symbol)> {
+        self.symbols.into_iter()
+    }
+
+    pub fn references(&self) -> impl Iterator<Item = (&Ident, &Value)> {
+        self.references.into_iter()
+    }
+
     pub fn resolve_function(&self, name: Ident) -> Result<FunctionCandidates, Cause> {
-        if let Some(Symbol::Functions(functions)) = self.symbols.find(&name) {
+        if let Some(Symbol::Functions(functions)) = self.find_symbol(&name) {
             Ok(FunctionCandidates {
                 functions: functions.iter().map(|(idx, _)| idx).copied().collect(),
             })
@@ -153,8 +190,7 @@ impl Scope {
     }
 
     pub fn resolve_symbol(&self, name: Ident) -> Result<Symbol, Cause> {
-        self.symbols
-            .find(&name)
+        self.find_symbol(&name)
             .cloned()
             .ok_or_else(|| Cause::unresolved_reference(name))
     }
@@ -173,7 +209,7 @@ impl Scope {
             let name_idx = pool.names.add(name.to_owned());
             let value = match type_ {
                 TypeId::Prim(_) | TypeId::Variant => Type::Prim,
-                TypeId::Class(_) | TypeId::Struct(_) | TypeId::Enum(_) => Type::Class,
+                TypeId::Class(_) | TypeId::Struct(_) | TypeId::Enum(_) | TypeId::BitField(_) => Type::Class,
                 TypeId::Ref(inner) => Type::Ref(self.get_type_index(inner, pool)?),
                 TypeId::WeakRef(inner) => Type::WeakRef(self.get_type_index(inner, pool)?),
                 TypeId::Array(inner) => Type::Array(self.get_type_index(inner, pool)?),
@@ -196,10 +232,11 @@ impl Scope {
                 ("wref", [nested]) => TypeId::WeakRef(Box::new(self.resolve_type(nested, pool)?)),
                 ("script_ref", [nested]) => TypeId::ScriptRef(Box::new(self.resolve_type(nested, pool)?)),
                 ("array", [nested]) => TypeId::Array(Box::new(self.resolve_type(nested, pool)?)),
-                _ => match self.symbols.find(&name.repr()) {
+                _ => match self.find_symbol(&name.repr()) {
                     Some(Symbol::Class(idx, _)) => TypeId::Class(*idx),
                     Some(Symbol::Struct(idx, _)) => TypeId::Struct(*idx),
                     Some(Symbol::Enum(idx)) => TypeId::Enum(*idx),
+                    Some(Symbol::BitField(idx)) => TypeId::BitField(*idx),
                     _ => return Err(Cause::unresolved_type(name)),
                 },
             }
@@ -216,10 +253,11 @@ impl Scope {
             Type::Class => {
                 let name = pool.def_name(index)?;
                 let ident = Ident::new(name.split('.').last().unwrap().to_owned());
-                match self.symbols.find(&ident) {
+                match self.find_symbol(&ident) {
                     Some(Symbol::Class(class_idx, _)) => TypeId::Class(*class_idx),
                     Some(Symbol::Struct(struct_idx, _)) => TypeId::Struct(*struct_idx),
                     Some(Symbol::Enum(enum_idx)) => TypeId::Enum(*enum_idx),
+                    Some(Symbol::BitField(bit_field_idx)) => TypeId::BitField(*bit_field_idx),
                     _ => return Err(Cause::unresolved_type(ident)),
                 }
             }
@@ -248,7 +286,17 @@ impl Scope {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
+pub struct SymbolUsage(Rc<RefCell<HashSet<PoolIndex<Definition>>>>);
+
+impl SymbolUsage {
+    pub fn contains(&self, symbol: &Symbol) -> bool {
+        let used = self.0.borrow();
+        symbol.definitions().iter().any(|index| used.contains(index))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     Local(PoolIndex<Local>),
     Parameter(PoolIndex<Parameter>),
@@ -266,6 +314,7 @@ pub enum TypeId {
     Class(PoolIndex<Class>),
     Struct(PoolIndex<Class>),
     Enum(PoolIndex<Enum>),
+    BitField(PoolIndex<BitField>),
     Ref(Box<TypeId>),
     WeakRef(Box<TypeId>),
     Array(Box<TypeId>),
@@ -292,6 +341,7 @@ impl TypeId {
             TypeId::Class(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
             TypeId::Struct(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
             TypeId::Enum(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
+            TypeId::BitField(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
             TypeId::Ref(idx) => Ok(Ident::new(format!("ref:{}", idx.repr(pool)?))),
             TypeId::WeakRef(idx) => Ok(Ident::new(format!("wref:{}", idx.repr(pool)?))),
             TypeId::Array(idx) => Ok(Ident::new(format!("array:{}", idx.repr(pool)?))),
@@ -309,6 +359,7 @@ impl TypeId {
             TypeId::Class(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
             TypeId::Struct(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
             TypeId::Enum(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
+            TypeId::BitField(idx) => Ok(Ident::Owned(pool.def_name(*idx)?)),
             TypeId::Ref(idx) => Ok(Ident::new(format!("ref<{}>", idx.pretty(pool)?))),
             TypeId::WeakRef(idx) => Ok(Ident::new(format!("wref<{}>", idx.pretty(pool)?))),
             TypeId::Array(idx) => Ok(Ident::new(format!("array<{}>", idx.pretty(pool)?))),
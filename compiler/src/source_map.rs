@@ -116,6 +116,18 @@ impl File {
         File { source, ..self }
     }
 
+    pub fn lookup_pos(&self, pos: FilePos) -> Option<Pos> {
+        let low = if pos.line == 0 {
+            self.lines.0
+        } else {
+            *self.lines.1.get(pos.line - 1)?
+        };
+        let high = self.lines.1.get(pos.line).cloned().unwrap_or(self.high);
+        let line = self.source_slice(Span { low, high });
+        let offset: usize = line.chars().take(pos.col).map(char::len_utf8).sum();
+        Some(low + offset)
+    }
+
     fn lookup(&self, pos: Pos) -> Option<FilePos> {
         let res = self.lines.1.binary_search(&pos).map(|p| p + 1);
         let index = res.err().or_else(|| res.ok()).unwrap();
@@ -45,6 +45,7 @@ impl<'a> Desugar<'a> {
             .resolve_function(Ident::new(signature.name().to_owned()))?
             .by_id(&signature, self.pool)
             .ok_or_else(|| Cause::function_not_found(signature.as_ref()))?;
+        self.scope.use_function(fun_idx);
 
         Ok(Callable::Function(fun_idx))
     }
@@ -135,7 +136,7 @@ impl<'a> ExprTransformer<TypedAst> for Desugar<'a> {
         seq: Seq<TypedAst>,
         span: Span,
     ) -> Result<Expr<TypedAst>, Error> {
-        let mut seq = self.on_seq(seq)?;
+        let seq = self.on_seq(seq)?;
 
         let array = self.on_expr(array)?;
         let arr_type = type_of(&array, self.scope, self.pool)?;
@@ -186,16 +187,15 @@ impl<'a> ExprTransformer<TypedAst> for Desugar<'a> {
             )),
             span,
         );
-        let increment_counter = Expr::Call(
+        let mut increment = CounterIncrement {
+            counter: counter_local,
             assign_add,
-            vec![],
-            vec![Expr::Ident(counter_local, span), Expr::Constant(Constant::I32(1), span)],
             span,
-        );
+        };
 
         let mut body = vec![assign_iter_value];
-        body.append(&mut seq.exprs);
-        body.push(increment_counter);
+        body.append(&mut increment.on_seq(seq)?.exprs);
+        body.push(increment.expr());
 
         Ok(Expr::While(Box::new(condition), Seq::new(body), span))
     }
@@ -212,3 +212,35 @@ impl<'a> ExprTransformer<TypedAst> for Desugar<'a> {
         Ok(Seq::new(processed))
     }
 }
+
+/// Makes `continue` statements in a desugared for-in body increment the counter before jumping back.
+struct CounterIncrement {
+    counter: Reference,
+    assign_add: Callable,
+    span: Span,
+}
+
+impl CounterIncrement {
+    fn expr(&self) -> Expr<TypedAst> {
+        Expr::Call(
+            self.assign_add.clone(),
+            vec![],
+            vec![
+                Expr::Ident(self.counter.clone(), self.span),
+                Expr::Constant(Constant::I32(1), self.span),
+            ],
+            self.span,
+        )
+    }
+}
+
+impl ExprTransformer<TypedAst> for CounterIncrement {
+    fn on_while(&mut self, cond: Expr<TypedAst>, body: Seq<TypedAst>, pos: Span) -> Result<Expr<TypedAst>, Error> {
+        // continue statements in nested loops refer to the inner loop
+        Ok(Expr::While(Box::new(cond), body, pos))
+    }
+
+    fn on_continue(&mut self, pos: Span) -> Result<Expr<TypedAst>, Error> {
+        Ok(Expr::Seq(Seq::new(vec![self.expr(), Expr::Continue(pos)])))
+    }
+}
@@ -4,7 +4,7 @@ use std::str::FromStr;
 use itertools::Itertools;
 use redscript::ast::{BinOp, Ident, Span, TypeName};
 use redscript::bundle::{ConstantPool, PoolIndex};
-use redscript::definition::{AnyDefinition, Class, Enum, Function, Visibility};
+use redscript::definition::{AnyDefinition, BitField, Class, Definition, Enum, Function, Visibility};
 use sequence_trie::SequenceTrie;
 
 use crate::error::{Cause, Error, ResultSpan};
@@ -19,7 +19,8 @@ impl SymbolMap {
     pub fn new(pool: &ConstantPool) -> Result<SymbolMap, Error> {
         let mut symbols: SequenceTrie<Ident, Symbol> = SequenceTrie::new();
 
-        for (idx, def) in pool.roots() {
+        for entry in pool.roots() {
+            let (idx, def) = entry?;
             let name = pool.def_name(idx)?;
             let symbol = match def.value {
                 AnyDefinition::Class(ref class) if class.flags.is_struct() => {
@@ -27,6 +28,7 @@ impl SymbolMap {
                 }
                 AnyDefinition::Class(ref class) => Symbol::Class(idx.cast(), class.visibility),
                 AnyDefinition::Enum(_) => Symbol::Enum(idx.cast()),
+                AnyDefinition::BitField(_) => Symbol::BitField(idx.cast()),
                 AnyDefinition::Function(ref fun) => Symbol::Functions(vec![(idx.cast(), fun.visibility)]),
                 _ => continue,
             };
@@ -63,17 +65,25 @@ impl SymbolMap {
         }
     }
 
-    pub fn populate_import(&self, import: Import, scope: &mut Scope, visibility: Visibility) -> Result<(), Error> {
+    pub fn populate_import(
+        &self,
+        import: Import,
+        scope: &mut Scope,
+        visibility: Visibility,
+    ) -> Result<Vec<Symbol>, Error> {
+        let mut added = vec![];
         match import {
             Import::Exact(_, path, span) => {
                 if let Some(symbol) = self.get_symbol(&path).with_span(span)?.visible(visibility) {
+                    added.push(symbol.clone());
                     scope.add_symbol(path.last().unwrap(), symbol);
                 }
             }
             Import::All(_, path, span) => {
                 for (ident, symbol) in self.get_direct_children(&path).with_span(span)? {
                     if let Some(symbol) = symbol.clone().visible(visibility) {
-                        scope.add_symbol(ident, symbol.clone());
+                        added.push(symbol.clone());
+                        scope.add_symbol(ident, symbol);
                     }
                 }
             }
@@ -81,12 +91,13 @@ impl SymbolMap {
                 for name in names {
                     let path = path.with_child(name);
                     if let Some(symbol) = self.get_symbol(&path).with_span(span)?.visible(visibility) {
+                        added.push(symbol.clone());
                         scope.add_symbol(path.last().unwrap(), symbol);
                     }
                 }
             }
         };
-        Ok(())
+        Ok(added)
     }
 
     pub fn get_symbol(&self, path: &ModulePath) -> Result<Symbol, Cause> {
@@ -114,15 +125,26 @@ pub enum Symbol {
     Class(PoolIndex<Class>, Visibility),
     Struct(PoolIndex<Class>, Visibility),
     Enum(PoolIndex<Enum>),
+    BitField(PoolIndex<BitField>),
     Functions(Vec<(PoolIndex<Function>, Visibility)>),
 }
 
 impl Symbol {
+    /// The definitions that the symbol stands for, all of the overloads in case of functions.
+    pub fn definitions(&self) -> Vec<PoolIndex<Definition>> {
+        match self {
+            Symbol::Class(idx, _) | Symbol::Struct(idx, _) => vec![idx.cast()],
+            Symbol::Enum(idx) => vec![idx.cast()],
+            Symbol::BitField(idx) => vec![idx.cast()],
+            Symbol::Functions(funs) => funs.iter().map(|(idx, _)| idx.cast()).collect(),
+        }
+    }
+
     pub fn visible(self, visibility: Visibility) -> Option<Symbol> {
         match self {
             Symbol::Class(_, v) if v <= visibility => Some(self),
             Symbol::Struct(_, v) if v <= visibility => Some(self),
-            Symbol::Enum(_) => Some(self),
+            Symbol::Enum(_) | Symbol::BitField(_) => Some(self),
             Symbol::Functions(funs) => {
                 let visible_funs: Vec<_> = funs.into_iter().filter(|(_, v)| *v <= visibility).collect();
                 if visible_funs.is_empty() {
@@ -151,6 +173,14 @@ impl Import {
             Import::All(anns, _, _) => anns,
         }
     }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Import::Exact(_, _, span) => *span,
+            Import::Selected(_, _, _, span) => *span,
+            Import::All(_, _, span) => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
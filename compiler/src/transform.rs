@@ -194,6 +194,10 @@ where
         Ok(Expr::Break(pos))
     }
 
+    fn on_continue(&mut self, pos: Span) -> Result<Expr<N>, Error> {
+        Ok(Expr::Continue(pos))
+    }
+
     fn on_null(&mut self, pos: Span) -> Result<Expr<N>, Error> {
         Ok(Expr::Null(pos))
     }
@@ -225,6 +229,7 @@ where
             Expr::This(pos) => self.on_this(pos),
             Expr::Super(pos) => self.on_super(pos),
             Expr::Break(pos) => self.on_break(pos),
+            Expr::Continue(pos) => self.on_continue(pos),
             Expr::Null(pos) => self.on_null(pos),
         }
     }
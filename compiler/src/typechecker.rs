@@ -330,6 +330,7 @@ impl<'a> TypeChecker<'a> {
             Expr::This(span) => Expr::This(*span),
             Expr::Super(span) => Expr::Super(*span),
             Expr::Break(span) => Expr::Break(*span),
+            Expr::Continue(span) => Expr::Continue(*span),
             Expr::Null(span) => Expr::Null(*span),
         };
         Ok(res)
@@ -573,9 +574,16 @@ impl<'a> TypeChecker<'a> {
             }
         };
         match match_ {
-            Ok(match_) => Ok(match_),
+            Ok(match_) => {
+                scope.use_function(match_.index);
+                Ok(match_)
+            }
             Err(err) if self.permissive => {
                 self.report(err)?;
+                // the call can't be resolved, so none of the overloads should be reported as unused
+                for overload in &overloads.functions {
+                    scope.use_function(*overload);
+                }
 
                 let dummy_args: Vec<_> = args.map(|expr| self.check(expr, None, scope)).try_collect()?;
                 let convs = iter::repeat(ArgConversion::identity()).take(dummy_args.len()).collect();
@@ -681,6 +689,7 @@ pub fn type_of(expr: &Expr<TypedAst>, scope: &Scope, pool: &ConstantPool) -> Res
             Reference::Symbol(Symbol::Class(idx, _)) => TypeId::Class(*idx),
             Reference::Symbol(Symbol::Struct(idx, _)) => TypeId::Struct(*idx),
             Reference::Symbol(Symbol::Enum(idx)) => TypeId::Enum(*idx),
+            Reference::Symbol(Symbol::BitField(idx)) => TypeId::BitField(*idx),
             Reference::Symbol(Symbol::Functions(_)) => return Err(Cause::value_expected("function").with_span(*span)),
         },
         Expr::Constant(cons, span) => match cons {
@@ -760,6 +769,7 @@ pub fn type_of(expr: &Expr<TypedAst>, scope: &Scope, pool: &ConstantPool) -> Res
             None => return Err(Cause::no_this_in_static_context().with_span(*span)),
         },
         Expr::Break(_) => TypeId::Void,
+        Expr::Continue(_) => TypeId::Void,
         Expr::Null(_) => TypeId::Null,
         Expr::BinOp(_, _, _, span) => return Err(Cause::unsupported("BinOp").with_span(*span)),
         Expr::UnOp(_, _, span) => return Err(Cause::unsupported("UnOp").with_span(*span)),
@@ -1,19 +1,23 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::path::PathBuf;
 
 use redscript::ast::{Expr, Ident, Seq, SourceAst, Span, TypeName};
 use redscript::bundle::{ConstantPool, PoolIndex};
 use redscript::bytecode::{Code, Instr};
 use redscript::definition::*;
 use redscript::mapper::{MultiMapper, PoolMapper};
+use redscript::verify::verify_code;
 use redscript::Ref;
+use strum::Display;
 
 use crate::assembler::Assembler;
 use crate::cte;
 use crate::error::{Cause, Error, ResultSpan};
+use crate::lint::{self, Lint, LintConfig};
 use crate::parser::*;
 use crate::scope::{Reference, Scope, Value};
-use crate::source_map::Files;
+use crate::source_map::{FilePos, Files};
 use crate::sugar::Desugar;
 use crate::symbol::{FunctionSignature, Import, ModulePath, Symbol, SymbolMap};
 use crate::transform::ExprTransformer;
@@ -28,7 +32,10 @@ pub struct CompilationUnit<'a> {
     function_bodies: Vec<FunctionBody>,
     wrappers: ProxyMap,
     proxies: ProxyMap,
+    function_spans: Vec<(PoolIndex<Function>, Span)>,
     diagnostics: Vec<Diagnostic>,
+    lints: LintConfig,
+    verify_bytecode: bool,
 }
 
 impl<'a> CompilationUnit<'a> {
@@ -49,17 +56,35 @@ impl<'a> CompilationUnit<'a> {
             function_bodies: Vec::new(),
             wrappers: HashMap::new(),
             proxies: HashMap::new(),
+            function_spans: vec![],
             diagnostics: vec![],
+            lints: LintConfig::default(),
+            verify_bytecode: false,
         })
     }
 
+    pub fn with_lints(self, lints: LintConfig) -> Self {
+        CompilationUnit { lints, ..self }
+    }
+
+    /// Runs the bytecode verifier on every compiled function and reports the issues it finds as errors.
+    pub fn with_bytecode_verification(self, verify_bytecode: bool) -> Self {
+        CompilationUnit {
+            verify_bytecode,
+            ..self
+        }
+    }
+
     pub fn compile(mut self, modules: Vec<SourceModule>) -> Result<Vec<Diagnostic>, Error> {
         let funcs = self.compile_modules(modules, true, false)?;
         self.finish(funcs)
     }
 
-    pub fn compile_files(self, files: &Files) -> Result<Vec<Diagnostic>, Error> {
-        self.compile(Self::parse(files)?)
+    pub fn compile_files(mut self, files: &Files) -> Result<Vec<Diagnostic>, Error> {
+        let modules = self.parse(files)?;
+        let funcs = self.compile_modules(modules, true, false)?;
+        self.define_source_files(files)?;
+        self.finish(funcs)
     }
 
     pub fn typecheck(
@@ -73,48 +98,41 @@ impl<'a> CompilationUnit<'a> {
     }
 
     pub fn typecheck_files(
-        self,
+        mut self,
         files: &Files,
         desugar: bool,
         permissive: bool,
     ) -> Result<(Vec<CompiledFunction>, Vec<Diagnostic>), Error> {
-        self.typecheck(Self::parse(files)?, desugar, permissive)
+        let modules = self.parse(files)?;
+        self.typecheck(modules, desugar, permissive)
     }
 
     pub fn compile_and_report(self, files: &Files) -> Result<(), Error> {
         log::info!("Compiling files: {}", files);
 
-        match self.compile_files(files) {
-            Ok(diagnostics) => {
-                let is_fatal = diagnostics.iter().any(Diagnostic::is_fatal);
-                for diagnostic in &diagnostics {
-                    Self::print_diagnostic(files, diagnostic);
-                }
+        let diagnostics = self.compile_and_collect(files).map_err(|err| {
+            log::error!("{}: {}", "Unexpected error during compilation", err);
+            err
+        })?;
+        for diagnostic in &diagnostics {
+            Self::print_diagnostic(files, diagnostic);
+        }
 
-                if is_fatal {
-                    Err(Error::MultipleErrors(
-                        diagnostics.iter().map(Diagnostic::span).collect(),
-                    ))
-                } else {
-                    log::info!("Compilation complete");
-                    Ok(())
-                }
-            }
-            Err(err) => match Diagnostic::from_error(err) {
-                Ok(diagnostic) => {
-                    Self::print_diagnostic(files, &diagnostic);
+        if diagnostics.iter().any(Diagnostic::is_fatal) {
+            Err(Error::MultipleErrors(
+                diagnostics.iter().map(Diagnostic::span).collect(),
+            ))
+        } else {
+            log::info!("Compilation complete");
+            Ok(())
+        }
+    }
 
-                    if diagnostic.is_fatal() {
-                        Err(Error::MultipleErrors(vec![diagnostic.span()]))
-                    } else {
-                        Ok(())
-                    }
-                }
-                Err(other) => {
-                    log::error!("{}: {}", "Unexpected error during compilation", other);
-                    Err(other)
-                }
-            },
+    /// Compiles the files and returns all diagnostics, including the error that stopped the compilation.
+    pub fn compile_and_collect(self, files: &Files) -> Result<Vec<Diagnostic>, Error> {
+        match self.compile_files(files) {
+            Ok(diagnostics) => Ok(diagnostics),
+            Err(err) => Diagnostic::from_error(err).map(|diagnostic| vec![diagnostic]),
         }
     }
 
@@ -123,12 +141,12 @@ impl<'a> CompilationUnit<'a> {
             Diagnostic::MethodConflict(_, pos) => {
                 let loc = files.lookup(*pos).unwrap();
                 Self::print_message(
-                    format_args!("At {}:\n Conflicting method replacement", loc),
+                    format_args!("At {}:\n {}", loc, diagnostic.message()),
                     diagnostic.is_fatal(),
                 );
             }
-            Diagnostic::CompileError(err, pos) => {
-                let loc = files.lookup(*pos).expect("Unknown file");
+            _ => {
+                let loc = files.lookup(diagnostic.span()).expect("Unknown file");
                 let line = loc.enclosing_line().trim_end().replace('\t', " ");
                 let padding = " ".repeat(loc.start.col);
                 let underscore_len = if loc.start.line == loc.end.line {
@@ -137,6 +155,7 @@ impl<'a> CompilationUnit<'a> {
                     3
                 };
                 let underscore = "^".repeat(underscore_len);
+                let err = diagnostic.message();
 
                 Self::print_message(
                     format_args!("At {}:\n {}\n {}{}\n {}", loc, line, padding, underscore, err),
@@ -154,15 +173,14 @@ impl<'a> CompilationUnit<'a> {
         }
     }
 
-    fn parse(files: &Files) -> Result<Vec<SourceModule>, Error> {
+    fn parse(&mut self, files: &Files) -> Result<Vec<SourceModule>, Error> {
         let mut modules = vec![];
         for file in files.files() {
-            let parsed = parse_file(file).map_err(|err| {
-                let message = format!("Syntax error, expected {}", err.expected);
-                let pos = file.byte_offset() + err.location.offset;
-                Error::SyntaxError(message, Span::new(pos, pos + 1))
-            })?;
-            modules.push(parsed);
+            let (module, errors) = parse_file_with_recovery(file);
+            for err in errors {
+                self.diagnostics.push(Diagnostic::from_error(err)?);
+            }
+            modules.push(module);
         }
         Ok(modules)
     }
@@ -194,8 +212,12 @@ impl<'a> CompilationUnit<'a> {
             queue.push((path, module.imports, slots));
         }
 
+        let mut import_usages = Vec::with_capacity(queue.len());
+
         for (path, imports, slots) in queue {
             let mut module_scope = self.scope.clone();
+            let usage = module_scope.track_usage();
+            let mut module_imports = vec![];
 
             if !path.is_empty() {
                 self.symbols
@@ -209,10 +231,14 @@ impl<'a> CompilationUnit<'a> {
 
             for import in imports {
                 if eval_conditions(&cte, import.annotations())? {
-                    self.symbols
+                    let span = import.span();
+                    let symbols = self
+                        .symbols
                         .populate_import(import, &mut module_scope, Visibility::Public)?;
+                    module_imports.push((span, symbols));
                 }
             }
+            import_usages.push((usage, module_imports));
 
             for slot in slots {
                 let res = match slot {
@@ -283,15 +309,41 @@ impl<'a> CompilationUnit<'a> {
             }
         }
 
+        for (usage, imports) in import_usages {
+            for (span, symbols) in imports {
+                if !symbols.iter().any(|symbol| usage.contains(symbol)) {
+                    let msg = "Unused import".to_owned();
+                    self.diagnostics.push(Diagnostic::Lint(Lint::UnusedImport, msg, span));
+                }
+            }
+        }
+
+        let lints = &self.lints;
+        self.diagnostics.retain(|diagnostic| match diagnostic {
+            Diagnostic::Lint(lint, _, _) => lints.is_enabled(*lint),
+            _ => true,
+        });
+
         Ok(compiled_funcs)
     }
 
-    fn finish(self, functions: Vec<CompiledFunction>) -> Result<Vec<Diagnostic>, Error> {
+    fn finish(mut self, functions: Vec<CompiledFunction>) -> Result<Vec<Diagnostic>, Error> {
+        let mut compiled = Vec::with_capacity(functions.len());
         for mut func in functions {
             let code = Assembler::from_body(func.code, &mut func.scope, self.pool)?;
             let function = self.pool.function_mut(func.index)?;
             function.code = code;
             function.locals = func.locals;
+            compiled.push((func.index, func.span));
+        }
+
+        if self.verify_bytecode {
+            for (index, span) in compiled {
+                for issue in verify_code(self.pool, index)? {
+                    let msg = format!("Invalid bytecode in {}: {}", self.pool.def_name(index)?, issue);
+                    self.diagnostics.push(Diagnostic::BytecodeError(msg, span));
+                }
+            }
         }
 
         // swap proxies with the functions they wrap
@@ -309,7 +361,7 @@ impl<'a> CompilationUnit<'a> {
             self.pool.swap_definition(wrapped, proxy);
         }
 
-        Self::cleanup_pool(self.pool);
+        Self::cleanup_pool(self.pool)?;
         Ok(self.diagnostics)
     }
 
@@ -556,6 +608,40 @@ impl<'a> CompilationUnit<'a> {
         }
 
         self.pool.put_definition(fun_idx, definition);
+        self.function_spans.push((fun_idx, decl.span));
+        Ok(())
+    }
+
+    fn define_source_files(&mut self, files: &Files) -> Result<(), Error> {
+        // files that are already in the pool keep their entries, new ones get ids past the highest one in use
+        let mut file_indexes: HashMap<PathBuf, PoolIndex<Definition>> = HashMap::new();
+        let mut next_id = 0;
+        for entry in self.pool.definitions() {
+            let (idx, def) = entry?;
+            if let AnyDefinition::SourceFile(file) = &def.value {
+                file_indexes.insert(file.path.clone(), idx);
+                next_id = next_id.max(file.id);
+            }
+        }
+
+        for (fun_idx, span) in self.function_spans.drain(..) {
+            let loc = match files.lookup(span) {
+                Some(loc) => loc,
+                None => continue,
+            };
+            let file_idx = *file_indexes.entry(loc.file.path().to_path_buf()).or_insert_with(|| {
+                next_id += 1;
+                let name_idx = self.pool.names.add(Ref::new(loc.file.path().display().to_string()));
+                let file = SourceFile::new(next_id, loc.file.path().to_path_buf());
+                self.pool.add_definition(Definition::source_file(name_idx, file))
+            });
+            if let Ok(fun) = self.pool.function_mut(fun_idx) {
+                fun.source = Some(SourceReference {
+                    file: file_idx,
+                    line: loc.start.line as u32 + 1,
+                });
+            }
+        }
         Ok(())
     }
 
@@ -857,7 +943,8 @@ impl<'a> CompilationUnit<'a> {
 
         let mut checker = TypeChecker::new(pool, permissive);
         let checked = checker.check_seq(&item.code, &mut local_scope)?;
-        let (diagnostics, mut locals) = checker.finish();
+        let (mut diagnostics, mut locals) = checker.finish();
+        diagnostics.extend(lint::check_function(&checked, pool)?);
 
         let ast = if desugar {
             let mut desugar = Desugar::new(&mut local_scope, pool);
@@ -946,13 +1033,14 @@ impl<'a> CompilationUnit<'a> {
         Ok(())
     }
 
-    fn cleanup_pool(pool: &mut ConstantPool) {
+    fn cleanup_pool(pool: &mut ConstantPool) -> Result<(), Error> {
         // this is a workaround for a game crash which happens when the game loads
         // a class which has a base class that is placed after the subclass in the pool
         let mut unsorted = BTreeSet::new();
 
         // find any classes that appear before their base class in the pool
-        for (def_idx, def) in pool.definitions() {
+        for entry in pool.definitions() {
+            let (def_idx, def) = entry?;
             if let AnyDefinition::Class(class) = &def.value {
                 let pos: u32 = def_idx.into();
                 if pos < class.base.into() {
@@ -963,7 +1051,8 @@ impl<'a> CompilationUnit<'a> {
         }
 
         // additional iteration to find classes that extend the ones that need sorting
-        for (def_idx, def) in pool.definitions() {
+        for entry in pool.definitions() {
+            let (def_idx, def) = entry?;
             if let AnyDefinition::Class(class) = &def.value {
                 if unsorted.contains(&class.base) {
                     unsorted.insert(def_idx.cast());
@@ -988,8 +1077,9 @@ impl<'a> CompilationUnit<'a> {
             let mappings = sorted.into_iter().zip(unsorted).collect();
             PoolMapper::default()
                 .with_class_mapper(MultiMapper::new(mappings))
-                .map(pool);
+                .map(pool)?;
         }
+        Ok(())
     }
 
     fn report(&mut self, err: Error) -> Result<(), Error> {
@@ -1044,34 +1134,107 @@ enum Slot {
 #[derive(Debug, PartialEq, Eq)]
 pub enum Diagnostic {
     MethodConflict(PoolIndex<Function>, Span),
-    CompileError(String, Span),
+    SyntaxError(String, Span),
+    CompileError(Cause, Span),
+    ArgumentError(String, Span),
+    ResolutionError(String, Span),
+    CteError(String, Span),
+    BytecodeError(String, Span),
+    Lint(Lint, String, Span),
 }
 
 impl Diagnostic {
     pub fn from_error(error: Error) -> Result<Diagnostic, Error> {
         match error {
-            Error::SyntaxError(msg, pos) => Ok(Diagnostic::CompileError(msg, pos)),
-            Error::CompileError(msg, pos) => Ok(Diagnostic::CompileError(msg, pos)),
-            Error::ArgumentError(msg, pos) => Ok(Diagnostic::CompileError(msg, pos)),
-            Error::ResolutionError(msg, pos) => Ok(Diagnostic::CompileError(msg, pos)),
-            Error::CteError(msg, pos) => Ok(Diagnostic::CompileError(msg, pos)),
+            Error::SyntaxError(msg, pos) => Ok(Diagnostic::SyntaxError(msg, pos)),
+            Error::CompileError(cause, pos) => Ok(Diagnostic::CompileError(cause, pos)),
+            Error::ArgumentError(msg, pos) => Ok(Diagnostic::ArgumentError(msg, pos)),
+            Error::ResolutionError(msg, pos) => Ok(Diagnostic::ResolutionError(msg, pos)),
+            Error::CteError(msg, pos) => Ok(Diagnostic::CteError(msg, pos)),
             other => Err(other),
         }
     }
 
     pub fn is_fatal(&self) -> bool {
+        self.severity() == Severity::Error
+    }
+
+    pub fn severity(&self) -> Severity {
         match self {
-            Diagnostic::MethodConflict(_, _) => false,
-            Diagnostic::CompileError(_, _) => true,
+            Diagnostic::MethodConflict(_, _) | Diagnostic::Lint(_, _, _) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// A stable identifier of the kind of the diagnostic, these are never reused.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Diagnostic::MethodConflict(_, _) => "W0001",
+            Diagnostic::SyntaxError(_, _) => "E0001",
+            Diagnostic::CompileError(cause, _) => cause.code,
+            Diagnostic::ArgumentError(_, _) => "E0003",
+            Diagnostic::ResolutionError(_, _) => "E0004",
+            Diagnostic::CteError(_, _) => "E0005",
+            Diagnostic::BytecodeError(_, _) => "E0006",
+            Diagnostic::Lint(lint, _, _) => lint.code(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Diagnostic::MethodConflict(_, _) => "Conflicting method replacement",
+            Diagnostic::CompileError(cause, _) => &cause.message,
+            Diagnostic::SyntaxError(msg, _)
+            | Diagnostic::ArgumentError(msg, _)
+            | Diagnostic::ResolutionError(msg, _)
+            | Diagnostic::CteError(msg, _)
+            | Diagnostic::BytecodeError(msg, _)
+            | Diagnostic::Lint(_, msg, _) => msg,
         }
     }
 
     pub fn span(&self) -> Span {
         match self {
-            Diagnostic::MethodConflict(_, pos) => *pos,
-            Diagnostic::CompileError(_, pos) => *pos,
+            Diagnostic::MethodConflict(_, pos)
+            | Diagnostic::SyntaxError(_, pos)
+            | Diagnostic::CompileError(_, pos)
+            | Diagnostic::ArgumentError(_, pos)
+            | Diagnostic::ResolutionError(_, pos)
+            | Diagnostic::CteError(_, pos)
+            | Diagnostic::BytecodeError(_, pos)
+            | Diagnostic::Lint(_, _, pos) => *pos,
         }
     }
+
+    pub fn locate(&self, files: &Files) -> Option<LocatedDiagnostic> {
+        let loc = files.lookup(self.span())?;
+        Some(LocatedDiagnostic {
+            code: self.code(),
+            severity: self.severity(),
+            message: self.message().to_owned(),
+            path: loc.file.path().to_path_buf(),
+            start: loc.start,
+            end: loc.end,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A diagnostic resolved to a source file, the positions are zero-based.
+#[derive(Debug, Clone)]
+pub struct LocatedDiagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub path: PathBuf,
+    pub start: FilePos,
+    pub end: FilePos,
 }
 
 fn eval_conditions(cte: &cte::Context, anns: &[Annotation]) -> Result<bool, Error> {
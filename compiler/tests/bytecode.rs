@@ -174,6 +174,92 @@ fn compile_for_loop() {
     TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
 }
 
+#[test]
+fn compile_for_loop_continue() {
+    let sources = "
+        func Testing(arr: array<Int32>, skip: Bool) {
+            for i in arr {
+                if skip {
+                    continue;
+                }
+                Log(ToString(i));
+            }
+        }
+
+        func Log(str: String) {}
+        func OperatorAssignAdd(out l: Int32, r: Int32) -> Int32 = 0
+        func OperatorLess(l: Int32, r: Int32) -> Bool = true
+        ";
+
+    let check = check_code![
+        pat!(Assign),
+        mem!(Local(array)),
+        mem!(Param(arr)),
+        pat!(Assign),
+        mem!(Local(counter)),
+        pat!(I32Const(0)),
+        pat!(JumpIfFalse(Offset { value: 195 })),
+        pat!(InvokeStatic(Offset { value: 43 }, 0, _, 0)),
+        mem!(Local(counter)),
+        mem!(ArraySize(elem_type)),
+        mem!(Local(array)),
+        pat!(ParamEnd),
+        pat!(Assign),
+        mem!(Local(i)),
+        mem!(ArrayElement(elem_type)),
+        mem!(Local(array)),
+        mem!(Local(counter)),
+        pat!(JumpIfFalse(Offset { value: 45 })),
+        mem!(Param(skip)),
+        pat!(InvokeStatic(Offset { value: 30 }, 0, _, 0)),
+        mem!(Local(counter)),
+        pat!(I32Const(1)),
+        pat!(ParamEnd),
+        pat!(Jump(Offset { value: -125 })),
+        pat!(InvokeStatic(Offset { value: 34 }, 0, _, 0)),
+        pat!(ToString(_)),
+        mem!(Local(i)),
+        pat!(ParamEnd),
+        pat!(InvokeStatic(Offset { value: 30 }, 0, _, 0)),
+        mem!(Local(counter)),
+        pat!(I32Const(1)),
+        pat!(ParamEnd),
+        pat!(Jump(Offset { value: -192 })),
+        pat!(Nop)
+    ];
+    TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
+}
+
+#[test]
+fn compile_while_continue() {
+    let sources = "
+        func Testing(cond: Bool, skip: Bool) {
+            while cond {
+                if skip {
+                    continue;
+                }
+                Log(\"test\");
+            }
+        }
+
+        func Log(str: String) {}
+        ";
+
+    let check = check_code![
+        pat!(JumpIfFalse(Offset { value: 51 })),
+        mem!(Param(cond)),
+        pat!(JumpIfFalse(Offset { value: 15 })),
+        mem!(Param(skip)),
+        pat!(Jump(Offset { value: -24 })),
+        pat!(InvokeStatic(Offset { value: 21 }, 0, _, 0)),
+        pat!(StringConst(_)),
+        pat!(ParamEnd),
+        pat!(Jump(Offset { value: -48 })),
+        pat!(Nop)
+    ];
+    TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
+}
+
 #[test]
 fn compile_nested_array_literals() {
     let sources = "
@@ -430,6 +516,38 @@ fn compile_if_else() {
     TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
 }
 
+#[test]
+fn compile_if_else_chain() {
+    let sources = "
+        func Testing(a: Bool, b: Bool) -> Int32 {
+            if a {
+                return 1;
+            } else if b {
+                return 2;
+            } else {
+                return 0;
+            }
+        }
+        ";
+
+    let check = check_code![
+        pat!(JumpIfFalse(Offset { value: 21 })),
+        mem!(Param(a)),
+        pat!(Return),
+        pat!(I32Const(1)),
+        pat!(Jump(Offset { value: 30 })),
+        pat!(JumpIfFalse(Offset { value: 21 })),
+        mem!(Param(b)),
+        pat!(Return),
+        pat!(I32Const(2)),
+        pat!(Jump(Offset { value: 9 })),
+        pat!(Return),
+        pat!(I32Const(0)),
+        pat!(Nop)
+    ];
+    TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
+}
+
 #[test]
 fn compile_method_overload_call() {
     let sources = "
@@ -521,6 +639,39 @@ fn compile_number_literals() {
     TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
 }
 
+#[test]
+fn compile_number_literal_formats() {
+    let sources = "
+        func Testing() {
+            let a: Int32 = 0xFF;
+            let b: Int32 = 0b1010;
+            let c: Int64 = 1_000_000l;
+            let d: Uint64 = 0xFFFF_FFFF_FFFFul;
+            let e: Int32 = 0x8000_0000;
+        }
+        ";
+
+    let check = check_code![
+        pat!(Assign),
+        mem!(Local(a)),
+        pat!(I32Const(255)),
+        pat!(Assign),
+        mem!(Local(b)),
+        pat!(I32Const(10)),
+        pat!(Assign),
+        mem!(Local(c)),
+        pat!(I64Const(1_000_000)),
+        pat!(Assign),
+        mem!(Local(d)),
+        pat!(U64Const(0xFFFF_FFFF_FFFF)),
+        pat!(Assign),
+        mem!(Local(e)),
+        pat!(I32Const(i32::MIN)),
+        pat!(Nop)
+    ];
+    TestContext::compiled(vec![sources]).unwrap().run("Testing", check)
+}
+
 #[test]
 fn compile_string_literals() {
     let sources = r#"
@@ -1,4 +1,12 @@
-use redscript::definition::ClassFlags;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use redscript::bundle::ScriptBundle;
+use redscript::definition::{BitField, ClassFlags, Definition, SourceFile};
+use redscript::Ref;
+use redscript_compiler::lint::{Lint, LintConfig};
+use redscript_compiler::source_map::{FilePos, Files};
+use redscript_compiler::unit::{CompilationUnit, Diagnostic, Severity};
 
 #[allow(unused)]
 mod utils;
@@ -138,7 +146,8 @@ fn compile_lub_types() {
         class D extends A {}
     ";
 
-    let (_, errs) = compiled(vec![sources]).unwrap();
+    let lints = LintConfig::default().allow(Lint::UnusedLocal);
+    let (_, errs) = compiled_with_lints(vec![sources], lints).unwrap();
     assert_eq!(errs, vec![]);
 }
 
@@ -157,6 +166,329 @@ fn compile_casts() {
         native func Cast(i: Float) -> Double;
     ";
 
-    let (_, errs) = compiled(vec![sources]).unwrap();
+    let lints = LintConfig::default().allow(Lint::UnusedLocal);
+    let (_, errs) = compiled_with_lints(vec![sources], lints).unwrap();
+    assert_eq!(errs, vec![]);
+}
+
+#[test]
+fn compile_source_references() {
+    let sources = "
+        func Testing() {}
+
+        class A {
+            func Method() {}
+        }
+    ";
+
+    let mut files = Files::new();
+    files.add(PathBuf::from("mods/test.reds"), sources.to_owned());
+
+    let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    let errs = CompilationUnit::new(&mut scripts.pool)
+        .unwrap()
+        .compile_files(&files)
+        .unwrap();
+    assert_eq!(errs, vec![]);
+
+    let pool = &scripts.pool;
+    let (file_idx, file) = pool
+        .definitions()
+        .map(Result::unwrap)
+        .find_map(|(idx, def)| def.value.as_source_file().map(|file| (idx, file)))
+        .expect("Source file not found in the pool");
+    assert_eq!(file.path, PathBuf::from("mods/test.reds"));
+
+    let lines: Vec<_> = pool
+        .definitions()
+        .map(Result::unwrap)
+        .filter_map(|(_, def)| def.source())
+        .filter(|source| source.file == file_idx)
+        .map(|source| source.line)
+        .collect();
+    assert_eq!(lines, vec![2, 5]);
+}
+
+#[test]
+fn compile_source_references_into_existing_files() {
+    let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    let pool = &mut scripts.pool;
+    // ids don't have to be contiguous, the next one has to be past the highest
+    for (id, path) in [(7, "mods/test.reds"), (3, "mods/other.reds")] {
+        let name = pool.names.add(Ref::new(path.to_owned()));
+        pool.add_definition::<Definition>(Definition::source_file(name, SourceFile::new(id, PathBuf::from(path))));
+    }
+
+    let mut files = Files::new();
+    files.add(PathBuf::from("mods/test.reds"), "func Testing() {}".to_owned());
+    files.add(PathBuf::from("mods/new.reds"), "func Other() {}".to_owned());
+    let errs = CompilationUnit::new(pool).unwrap().compile_files(&files).unwrap();
+    assert_eq!(errs, vec![]);
+
+    let mut ids: Vec<_> = pool
+        .definitions()
+        .map(Result::unwrap)
+        .filter_map(|(_, def)| def.value.as_source_file())
+        .map(|file| (file.path.display().to_string(), file.id))
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec![
+        ("mods/new.reds".to_owned(), 8),
+        ("mods/other.reds".to_owned(), 3),
+        ("mods/test.reds".to_owned(), 7),
+    ]);
+}
+
+#[test]
+fn compile_located_diagnostics() {
+    let sources = "
+        func Testing() -> Int32 {
+            return Missing();
+        }
+    ";
+
+    let mut files = Files::new();
+    files.add(PathBuf::from("mods/test.reds"), sources.to_owned());
+
+    let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    let diagnostics = CompilationUnit::new(&mut scripts.pool)
+        .unwrap()
+        .compile_and_collect(&files)
+        .unwrap();
+    assert_eq!(diagnostics.len(), 1);
+
+    let located = diagnostics[0].locate(&files).unwrap();
+    assert_eq!(located.code, "E0102");
+    assert_eq!(located.severity, Severity::Error);
+    assert_eq!(located.message, "Function Missing not found");
+    assert_eq!(located.path, PathBuf::from("mods/test.reds"));
+    assert_eq!(located.start, FilePos { line: 2, col: 19 });
+    assert_eq!(located.end, FilePos { line: 2, col: 28 });
+}
+
+#[test]
+fn compile_with_syntax_errors() {
+    let first = "
+func Broken() -> Int32 {
+    return 1
+}
+
+func Valid() -> Int32 = 1
+";
+    let second = "
+func Valid2() -> Int32 = Valid()
+
+func Broken2( {}
+";
+
+    let mut files = Files::new();
+    files.add(PathBuf::from("first.reds"), first.to_owned());
+    files.add(PathBuf::from("second.reds"), second.to_owned());
+
+    let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    let diagnostics = CompilationUnit::new(&mut scripts.pool)
+        .unwrap()
+        .compile_and_collect(&files)
+        .unwrap();
+
+    let errors: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let located = diagnostic.locate(&files).unwrap();
+            (located.code, located.path, located.start.line)
+        })
+        .collect();
+    assert_eq!(errors, vec![
+        ("E0001", PathBuf::from("first.reds"), 3),
+        ("E0001", PathBuf::from("second.reds"), 3)
+    ]);
+
+    let pool = &scripts.pool;
+    let compiled = pool
+        .definitions()
+        .map(Result::unwrap)
+        .filter_map(|(_, def)| def.value.as_function().map(|_| pool.names.get(def.name).unwrap()))
+        .filter(|name| name.starts_with("Valid"))
+        .count();
+    assert_eq!(compiled, 2);
+}
+
+#[test]
+fn compile_lint_warnings() {
+    let sources = "
+        import Module.Helper
+        import Module.Unused
+
+        func Testing(arg: Int32) -> Int32 {
+            let unused = 1;
+            let _ignored = 2;
+            let assigned: Int32;
+            assigned = 3;
+            arg = arg;
+            return Helper();
+            arg = 4;
+            arg = 5;
+        }
+    ";
+    let module = "
+        module Module
+
+        public func Helper() -> Int32 = 1
+        public func Unused() -> Int32 = 2
+    ";
+
+    let (_, errs) = compiled(vec![sources, module]).unwrap();
+    let warnings: Vec<_> = errs
+        .iter()
+        .map(|diagnostic| (diagnostic.code(), diagnostic.message()))
+        .collect();
+    assert_eq!(warnings, vec![
+        ("W0002", "Local variable 'unused' is never used"),
+        ("W0002", "Local variable 'assigned' is never used"),
+        ("W0004", "Unreachable code"),
+        ("W0005", "Variable 'arg' is assigned to itself"),
+        ("W0003", "Unused import"),
+    ]);
+    assert!(errs.iter().all(|diagnostic| !diagnostic.is_fatal()));
+}
+
+#[test]
+fn compile_unused_import_with_global_name() {
+    let sources = "
+        import Module.Helper
+
+        func Helper() -> Int32 = 1
+
+        func Testing() -> Int32 {
+            return Helper();
+        }
+    ";
+    let module = "
+        module Module
+
+        public func Helper(x: Int32) -> Int32 = x
+    ";
+
+    let (_, errs) = compiled(vec![sources, module]).unwrap();
+    assert!(matches!(errs[..], [Diagnostic::Lint(Lint::UnusedImport, _, _)]));
+}
+
+#[test]
+fn compile_with_allowed_lints() {
+    let sources = "
+        func Testing() -> Int32 {
+            let unused = 1;
+            return 2;
+            unused = 3;
+        }
+    ";
+
+    let lints = LintConfig::default().allow(Lint::UnreachableCode);
+    let (_, errs) = compiled_with_lints(vec![sources], lints).unwrap();
+    assert!(matches!(errs[..], [Diagnostic::Lint(Lint::UnusedLocal, _, _)]));
+}
+
+#[test]
+fn compile_bit_field_types() {
+    let sources = "
+        class Holder {
+            let flags: EFlags;
+
+            func GetFlags() -> EFlags {
+                return this.flags;
+            }
+        }
+
+        func PassFlags(flags: EFlags) -> EFlags = flags
+        ";
+    let mut files = Files::new();
+    files.add(PathBuf::from("test.reds"), sources.to_owned());
+
+    let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    let name = scripts.pool.names.add("EFlags".to_owned().into());
+    let bit_field = BitField {
+        flags: 0,
+        size: 4,
+        members: vec![],
+        unk1: false,
+    };
+    scripts
+        .pool
+        .add_definition::<BitField>(Definition::bit_field(name, bit_field));
+
+    let errs = CompilationUnit::new(&mut scripts.pool)
+        .unwrap()
+        .compile_files(&files)
+        .unwrap();
     assert_eq!(errs, vec![]);
 }
+
+#[test]
+fn save_compiled_bundle_byte_exact() {
+    let sources = r#"
+        enum Direction {
+            Up = 0,
+            Down = 1,
+        }
+
+        public class Counter {
+            private let count: Int32;
+            private let labels: array<String>;
+
+            public func Increment(by: Int32) -> Int32 {
+                this.count += by;
+                ArrayPush(this.labels, s"added \(by)");
+                return this.count;
+            }
+
+            public static func Describe(dir: Direction) -> String {
+                switch dir {
+                    case Direction.Up:
+                        return "up";
+                    default:
+                        return "down";
+                }
+            }
+        }
+        "#;
+    let (pool, _) = compiled(vec![sources]).unwrap();
+    let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    scripts.pool = pool;
+
+    let mut first = Cursor::new(Vec::new());
+    scripts.save(&mut first).unwrap();
+    first.set_position(0);
+    let reloaded = ScriptBundle::load(&mut first).unwrap();
+    let mut second = Cursor::new(Vec::new());
+    reloaded.save(&mut second).unwrap();
+
+    assert_eq!(first.into_inner(), second.into_inner());
+}
+
+#[test]
+fn report_missing_returns_in_bytecode() {
+    let sources = "
+        func Testing(flag: Bool) -> Int32 {
+            if flag {
+                return 1;
+            }
+        }
+
+        func Branches(flag: Bool) -> Int32 {
+            if flag {
+                return 1;
+            } else {
+                return 2;
+            }
+        }
+
+        func Loops(x: Int32) -> Int32 {
+            while true {
+                return x;
+            }
+        }
+    ";
+
+    let (_, errs) = compiled_with_verification(vec![sources]).unwrap();
+    assert!(matches!(&errs[..], [Diagnostic::BytecodeError(msg, _)] if msg.contains("Testing;Bool")));
+}
@@ -5,6 +5,7 @@ use redscript::bundle::{ConstantPool, PoolIndex, ScriptBundle};
 use redscript::bytecode::{Code, Offset};
 use redscript::definition::{AnyDefinition, ClassFlags, Definition};
 use redscript_compiler::error::Error;
+use redscript_compiler::lint::LintConfig;
 use redscript_compiler::parser;
 use redscript_compiler::unit::{CompilationUnit, Diagnostic};
 
@@ -42,6 +43,7 @@ impl TestContext {
         let fun = self
             .pool
             .definitions()
+            .map(Result::unwrap)
             .find_map(|(_, def)| match &def.value {
                 AnyDefinition::Function(fun) => {
                     let fun_name = self.pool.names.get(def.name).ok()?;
@@ -111,12 +113,31 @@ macro_rules! check_code {
 }
 
 pub fn compiled(sources: Vec<&str>) -> Result<(ConstantPool, Vec<Diagnostic>), Error> {
+    compiled_with_lints(sources, LintConfig::default())
+}
+
+pub fn compiled_with_lints(sources: Vec<&str>, lints: LintConfig) -> Result<(ConstantPool, Vec<Diagnostic>), Error> {
+    compiled_with_options(sources, lints, false)
+}
+
+pub fn compiled_with_verification(sources: Vec<&str>) -> Result<(ConstantPool, Vec<Diagnostic>), Error> {
+    compiled_with_options(sources, LintConfig::default(), true)
+}
+
+fn compiled_with_options(
+    sources: Vec<&str>,
+    lints: LintConfig,
+    verify: bool,
+) -> Result<(ConstantPool, Vec<Diagnostic>), Error> {
     let modules = sources
         .iter()
         .map(|source| parser::parse_str(&source).unwrap())
         .collect();
     let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF))?;
-    let res = CompilationUnit::new(&mut scripts.pool)?.compile(modules)?;
+    let res = CompilationUnit::new(&mut scripts.pool)?
+        .with_lints(lints)
+        .with_bytecode_verification(verify)
+        .compile(modules)?;
 
     Ok((scripts.pool, res))
 }
@@ -125,6 +146,7 @@ pub fn check_class_flags(pool: &ConstantPool, name: &str, flags: ClassFlags) ->
     let name_index = pool.names.get_index(&String::from(name))?;
     let match_ = pool
         .definitions()
+        .map(Result::unwrap)
         .filter(|(_, def)| matches!(def.value, AnyDefinition::Class(_)))
         .find(|(_, def)| def.name == name_index)
         .map(|(_, def)| &def.value);
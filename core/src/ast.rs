@@ -9,7 +9,7 @@ use strum::{Display, EnumString, IntoStaticStr};
 
 use crate::Ref;
 
-#[derive(Debug, EnumAsInner)]
+#[derive(Debug, Clone, EnumAsInner)]
 pub enum Expr<Name: NameKind>
 where
     Name: NameKind,
@@ -45,6 +45,7 @@ where
     This(Span),
     Super(Span),
     Break(Span),
+    Continue(Span),
     Null(Span),
 }
 
@@ -57,7 +58,7 @@ pub trait NameKind {
     type Type;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SourceAst;
 
 impl NameKind for SourceAst {
@@ -120,6 +121,7 @@ where
             Expr::This(span) => *span,
             Expr::Super(span) => *span,
             Expr::Break(span) => *span,
+            Expr::Continue(span) => *span,
             Expr::Null(span) => *span,
         }
     }
@@ -292,6 +294,33 @@ impl BinOp {
     pub fn does_associate(self, parent: BinOp) -> bool {
         parent.precedence() > self.precedence() || (parent.precedence() == self.precedence() && parent.associative())
     }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::AssignAdd => "+=",
+            BinOp::AssignSubtract => "-=",
+            BinOp::AssignMultiply => "*=",
+            BinOp::AssignDivide => "/=",
+            BinOp::AssignOr => "|=",
+            BinOp::AssignAnd => "&=",
+            BinOp::LogicOr => "||",
+            BinOp::LogicAnd => "&&",
+            BinOp::Or => "|",
+            BinOp::Xor => "^",
+            BinOp::Equal => "==",
+            BinOp::NotEqual => "!=",
+            BinOp::And => "&",
+            BinOp::Less => "<",
+            BinOp::LessEqual => "<=",
+            BinOp::Greater => ">",
+            BinOp::GreaterEqual => ">=",
+            BinOp::Add => "+",
+            BinOp::Subtract => "-",
+            BinOp::Multiply => "*",
+            BinOp::Divide => "/",
+            BinOp::Modulo => "%",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Display, EnumString, IntoStaticStr)]
@@ -304,6 +333,16 @@ pub enum UnOp {
     Neg,
 }
 
+impl UnOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnOp::BitNot => "~",
+            UnOp::LogicNot => "!",
+            UnOp::Neg => "-",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SwitchCase<N>
 where
@@ -319,6 +358,24 @@ where
     pub body: Seq<N>,
 }
 
+impl<N> Clone for SwitchCase<N>
+where
+    N: NameKind + Clone,
+    N::Reference: Debug + Clone,
+    N::Callable: Debug + Clone,
+    N::Local: Debug + Clone,
+    N::Function: Debug + Clone,
+    N::Member: Debug + Clone,
+    N::Type: Debug + Clone,
+{
+    fn clone(&self) -> Self {
+        SwitchCase {
+            matcher: self.matcher.clone(),
+            body: self.body.clone(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Seq<N>
 where
@@ -348,6 +405,23 @@ where
     }
 }
 
+impl<N> Clone for Seq<N>
+where
+    N: NameKind + Clone,
+    N::Reference: Debug + Clone,
+    N::Callable: Debug + Clone,
+    N::Local: Debug + Clone,
+    N::Function: Debug + Clone,
+    N::Member: Debug + Clone,
+    N::Type: Debug + Clone,
+{
+    fn clone(&self) -> Self {
+        Seq {
+            exprs: self.exprs.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Literal {
     String,
@@ -435,7 +509,7 @@ impl Target {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TypeName {
     pub name: Ident,
     pub arguments: Vec<TypeName>,
@@ -3,18 +3,20 @@ use std::collections::hash_map::{Entry, HashMap};
 use std::hash::Hash;
 use std::io::Seek;
 use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock};
 use std::{fmt, io};
 
-use itertools::chain;
+use itertools::{chain, Itertools};
 use modular_bitfield::prelude::*;
 use thiserror::Error;
 
 use crate::decode::{Decode, DecodeExt};
-use crate::definition::{AnyDefinition, Class, Definition, Enum, Field, Function, Local, Parameter, Type};
+use crate::definition::{AnyDefinition, BitField, Class, Definition, Enum, Field, Function, Local, Parameter, Type};
 use crate::encode::{Encode, EncodeExt};
 use crate::Ref;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ScriptBundle {
     header: Header,
     pub pool: ConstantPool,
@@ -28,6 +30,22 @@ impl ScriptBundle {
         Ok(cache)
     }
 
+    /// Loads the header and the string tables of a bundle, the definitions are decoded from the source
+    /// when they're first accessed. Definitions that can't be decoded are reported as errors when they're accessed.
+    pub fn load_lazy<S: AsRef<[u8]> + 'static>(source: S) -> io::Result<Self> {
+        let source: Arc<dyn AsRef<[u8]>> = Arc::new(source);
+        let mut input = io::Cursor::new((*source).as_ref());
+        let header: Header = input.decode()?;
+        let (mut pool, headers) = ConstantPool::decode_tables(&mut input, &header)?;
+        pool.definitions = Definitions::lazy(headers, Source(source.clone()));
+        let cache = ScriptBundle { header, pool };
+        Ok(cache)
+    }
+
+    pub fn version(&self) -> u32 {
+        self.header.version
+    }
+
     pub fn save<O: io::Write + io::Seek>(&self, output: &mut O) -> io::Result<()> {
         output.seek(io::SeekFrom::Start(Header::SIZE as u64))?;
         let header = self.pool.encode(output, &self.header)?;
@@ -39,25 +57,45 @@ impl ScriptBundle {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     version: u32,
     flags: u32,
     unk1: u32,
     unk2: u32,
     unk3: u32,
-    hash: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) hash: u32,
     chunks: u32,
-    data: TableHeader,
-    names: TableHeader,
-    tweakdb_indexes: TableHeader,
-    resources: TableHeader,
-    strings: TableHeader,
-    definitions: TableHeader,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) data: TableHeader,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) names: TableHeader,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) tweakdb_indexes: TableHeader,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) resources: TableHeader,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) strings: TableHeader,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) definitions: TableHeader,
 }
 
 impl Header {
     const MAGIC: u32 = 0x53444552;
-    const SIZE: usize = 104;
+    pub(crate) const SIZE: usize = 104;
+    /// Versions of the format this library can read and write.
+    pub const SUPPORTED_VERSIONS: &'static [u32] = &[13];
+
+    pub(crate) fn computed_hash(&self) -> io::Result<u32> {
+        let header_for_hash = Header {
+            hash: 0xDEADBEEF,
+            ..self.clone()
+        };
+        let mut buffer = io::Cursor::new(Vec::with_capacity(Header::SIZE));
+        buffer.encode(&header_for_hash)?;
+        Ok(crc(buffer.get_ref()))
+    }
 }
 
 impl Decode for Header {
@@ -68,6 +106,14 @@ impl Decode for Header {
         }
 
         let version: u32 = input.decode()?;
+        if !Header::SUPPORTED_VERSIONS.contains(&version) {
+            let msg = format!(
+                "Unsupported bundle version {} (supported versions: {})",
+                version,
+                Header::SUPPORTED_VERSIONS.iter().join(", ")
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
         let flags: u32 = input.decode()?;
         let unk1: u32 = input.decode()?;
         let unk2: u32 = input.decode()?;
@@ -120,16 +166,37 @@ impl Encode for Header {
 }
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPool {
     pub names: Names<String>,
     pub tweakdb_ids: Names<TweakDbId>,
     pub resources: Names<Resource>,
     pub strings: Names<String>,
-    pub(crate) definitions: Vec<Definition>,
+    pub(crate) definitions: Definitions,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    data: Vec<u8>,
 }
 
 impl ConstantPool {
     pub fn decode<I: io::Read + io::Seek>(input: &mut I, header: &Header) -> io::Result<Self> {
+        let (mut pool, headers) = Self::decode_tables(input, header)?;
+
+        let mut definitions = Vec::with_capacity(headers.len());
+        definitions.push(Definition::DEFAULT);
+
+        for (index, header) in headers.iter().enumerate().skip(1) {
+            let definition = Definition::decode(input, header)
+                .map_err(|err| decode_error(err, "definition", index as u32, header.offset.into()))?;
+            definitions.push(definition);
+        }
+        pool.definitions = definitions.into();
+        Ok(pool)
+    }
+
+    fn decode_tables<I: io::Read + io::Seek>(
+        input: &mut I,
+        header: &Header,
+    ) -> io::Result<(Self, Vec<DefinitionHeader>)> {
         let buffer = input.decode_bytes(header.data.count)?;
 
         let mut cursor = io::Cursor::new(buffer);
@@ -137,30 +204,42 @@ impl ConstantPool {
         let names = Names::decode_from(&mut cursor, &input.decode_vec(header.names.count)?)?;
         let tweakdb_ids = Names::decode_from(&mut cursor, &input.decode_vec(header.tweakdb_indexes.count)?)?;
         let resources = Names::decode_from(&mut cursor, &input.decode_vec(header.resources.count)?)?;
-        let headers: Vec<DefinitionHeader> = input.decode_vec(header.definitions.count)?;
-        let strings = Names::decode_from(&mut cursor, &input.decode_vec(header.strings.count)?)?;
-
-        let mut definitions = Vec::with_capacity(headers.len());
-        definitions.push(Definition::DEFAULT);
-
-        for header in headers.iter().skip(1) {
-            let definition = Definition::decode(input, header)?;
-            definitions.push(definition);
+        let def_headers_pos = input.stream_position()?;
+        let mut headers = vec![];
+        for index in 0..header.definitions.count {
+            let position = def_headers_pos + u64::from(index) * DefinitionHeader::SIZE as u64;
+            let header: DefinitionHeader = input
+                .decode()
+                .map_err(|err| decode_error(err, "definition header", index, position))?;
+            headers.push(header);
         }
+        let strings = Names::decode_from(&mut cursor, &input.decode_vec(header.strings.count)?)?;
 
         let result = ConstantPool {
             names,
             tweakdb_ids,
             resources,
             strings,
-            definitions,
+            definitions: Definitions::default(),
+            data: cursor.into_inner(),
         };
-        Ok(result)
+        Ok((result, headers))
     }
 
+    /// Encodes the pool. Strings that were decoded keep their original offsets in the string data,
+    /// so an unmodified pool is encoded into exactly the bytes it was decoded from.
     pub fn encode<O: io::Write + io::Seek>(&self, output: &mut O, header: &Header) -> io::Result<Header> {
-        let mut buffer = io::Cursor::new(Vec::with_capacity(header.data.count as usize));
+        let mut buffer = io::Cursor::new(self.data.clone());
+        buffer.seek(io::SeekFrom::End(0))?;
         let mut dedup_map = HashMap::new();
+        for (str, offset) in chain!(
+            self.names.retained_offsets(&self.data),
+            self.tweakdb_ids.retained_offsets(&self.data),
+            self.resources.retained_offsets(&self.data),
+            self.strings.retained_offsets(&self.data)
+        ) {
+            dedup_map.entry(str.clone()).or_insert(offset);
+        }
         for str in chain!(
             &self.names.strings,
             &self.tweakdb_ids.strings,
@@ -180,17 +259,17 @@ impl ConstantPool {
         let data = TableHeader::new(buffer.get_ref(), buffer.position() as u32, position);
         output.write_all(buffer.get_ref())?;
 
-        let name_offsets = self.names.encoded_offsets(&dedup_map)?;
+        let name_offsets = self.names.encoded_offsets(&self.data, &dedup_map)?;
         let position = output.stream_position()? as u32;
         let names = TableHeader::new(&name_offsets, self.names.strings.len() as u32, position);
         output.write_all(&name_offsets)?;
 
-        let tweakdb_offsets = self.tweakdb_ids.encoded_offsets(&dedup_map)?;
+        let tweakdb_offsets = self.tweakdb_ids.encoded_offsets(&self.data, &dedup_map)?;
         let position = output.stream_position()? as u32;
         let tweakdb_indexes = TableHeader::new(&tweakdb_offsets, self.tweakdb_ids.strings.len() as u32, position);
         output.write_all(&tweakdb_offsets)?;
 
-        let resource_offsets = self.resources.encoded_offsets(&dedup_map)?;
+        let resource_offsets = self.resources.encoded_offsets(&self.data, &dedup_map)?;
         let position = output.stream_position()? as u32;
         let resources = TableHeader::new(&resource_offsets, self.resources.strings.len() as u32, position);
         output.write_all(&resource_offsets)?;
@@ -199,7 +278,7 @@ impl ConstantPool {
         let def_header_size = DefinitionHeader::SIZE as u64 * self.definitions.len() as u64;
         output.seek(io::SeekFrom::Current(def_header_size as i64))?;
 
-        let string_offsets = self.strings.encoded_offsets(&dedup_map)?;
+        let string_offsets = self.strings.encoded_offsets(&self.data, &dedup_map)?;
         let position = output.stream_position()? as u32;
         let strings = TableHeader::new(&string_offsets, self.strings.strings.len() as u32, position);
         output.write_all(&string_offsets)?;
@@ -207,7 +286,11 @@ impl ConstantPool {
         let mut buffer = io::Cursor::new(Vec::with_capacity(def_header_size as usize));
         buffer.encode(&DefinitionHeader::DEFAULT)?;
 
-        for definition in self.definitions.iter().skip(1) {
+        for index in 1..self.definitions.len() {
+            let definition = self
+                .definitions
+                .get(index)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.0))?;
             let header = DefinitionHeader::encode_definition(output, definition)?;
             buffer.encode(&header)?;
         }
@@ -215,23 +298,18 @@ impl ConstantPool {
         output.write_all(buffer.get_ref())?;
 
         let definitions = TableHeader::new(buffer.get_ref(), self.definitions.len() as u32, def_header_pos as u32);
-        let header_for_hash = Header {
+        let header = Header {
             data,
             names,
             tweakdb_indexes,
             resources,
             strings,
             definitions,
-            hash: 0xDEADBEEF,
             ..header.clone()
         };
-
-        let mut buffer = io::Cursor::new(Vec::with_capacity(Header::SIZE));
-        buffer.encode(&header_for_hash)?;
-
         let header = Header {
-            hash: crc(buffer.get_ref()),
-            ..header_for_hash
+            hash: header.computed_hash()?,
+            ..header
         };
         Ok(header)
     }
@@ -241,16 +319,12 @@ impl ConstantPool {
         index: PoolIndex<A>,
         get: F,
     ) -> Result<&A, PoolError> {
-        self.definitions
-            .get(index.value as usize)
-            .and_then(|def| get(&def.value))
+        get(&self.definition(index)?.value)
             .ok_or_else(|| PoolError(format!("Definition not found in the pool ({})", index)))
     }
 
     pub fn definition<A>(&self, index: PoolIndex<A>) -> Result<&Definition, PoolError> {
-        self.definitions
-            .get(index.value as usize)
-            .ok_or_else(|| PoolError(format!("Definition not found in the pool ({})", index)))
+        self.definitions.get(index.value as usize)
     }
 
     pub fn function(&self, index: PoolIndex<Function>) -> Result<&Function, PoolError> {
@@ -260,6 +334,7 @@ impl ConstantPool {
     pub fn function_mut(&mut self, index: PoolIndex<Function>) -> Result<&mut Function, PoolError> {
         self.definitions
             .get_mut(index.value as usize)
+            .ok()
             .and_then(|def| def.value.as_function_mut())
             .ok_or_else(|| PoolError(format!("Function not found in the pool ({})", index)))
     }
@@ -287,6 +362,7 @@ impl ConstantPool {
     pub fn class_mut(&mut self, index: PoolIndex<Class>) -> Result<&mut Class, PoolError> {
         self.definitions
             .get_mut(index.value as usize)
+            .ok()
             .and_then(|def| def.value.as_class_mut())
             .ok_or_else(|| PoolError(format!("Class not found in the pool ({})", index)))
     }
@@ -295,20 +371,26 @@ impl ConstantPool {
         self.definition_by(index, AnyDefinition::as_enum)
     }
 
+    pub fn bit_field(&self, index: PoolIndex<BitField>) -> Result<&BitField, PoolError> {
+        self.definition_by(index, AnyDefinition::as_bit_field)
+    }
+
     pub fn enum_value(&self, index: PoolIndex<i64>) -> Result<i64, PoolError> {
         self.definition_by(index, AnyDefinition::as_enum_value).cloned()
     }
 
     pub fn def_name<A>(&self, index: PoolIndex<A>) -> Result<Ref<String>, PoolError> {
-        self.names.get(self.definition(index)?.name)
+        self.names.get(self.definitions.name(index.value as usize)?)
     }
 
-    pub fn definitions(&self) -> impl Iterator<Item = (PoolIndex<Definition>, &Definition)> {
-        self.definitions
-            .iter()
-            .enumerate()
-            .skip(1)
-            .map(|(index, def)| (PoolIndex::new(index as u32), def))
+    pub fn definitions(&self) -> impl Iterator<Item = Result<(PoolIndex<Definition>, &Definition), PoolError>> {
+        (1..self.definitions.len()).map(|index| Ok((PoolIndex::new(index as u32), self.definitions.get(index)?)))
+    }
+
+    /// Iterates over the types of the definitions without decoding them.
+    pub fn definition_types(&self) -> impl Iterator<Item = (PoolIndex<Definition>, DefinitionType)> + '_ {
+        (1..self.definitions.len())
+            .filter_map(|index| Some((PoolIndex::new(index as u32), self.definitions.type_(index).ok()?)))
     }
 
     pub fn reserve<A>(&mut self) -> PoolIndex<A> {
@@ -316,16 +398,16 @@ impl ConstantPool {
     }
 
     pub fn put_definition<A>(&mut self, index: PoolIndex<A>, definition: Definition) {
-        self.definitions[index.value as usize] = definition;
+        self.definitions.slots[index.value as usize] = DefinitionSlot::from(definition);
     }
 
     pub fn swap_definition<A>(&mut self, lhs: PoolIndex<A>, rhs: PoolIndex<A>) {
-        self.definitions.swap(lhs.value as usize, rhs.value as usize)
+        self.definitions.slots.swap(lhs.value as usize, rhs.value as usize)
     }
 
     pub fn add_definition<A>(&mut self, definition: Definition) -> PoolIndex<A> {
         let position = self.definitions.len();
-        self.definitions.push(definition);
+        self.definitions.slots.push(DefinitionSlot::from(definition));
         PoolIndex::new(position as u32)
     }
 
@@ -334,11 +416,158 @@ impl ConstantPool {
     }
 
     pub fn rename<A>(&mut self, index: PoolIndex<A>, name: PoolIndex<String>) {
-        self.definitions[index.value as usize].name = name;
+        if let Ok(definition) = self.definitions.get_mut(index.value as usize) {
+            definition.name = name;
+        }
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = Result<(PoolIndex<Definition>, &Definition), PoolError>> {
+        self.definitions()
+            .filter(|res| !matches!(res, Ok((_, def)) if !def.parent.is_undefined()))
+    }
+}
+
+/// The definitions of a pool, those loaded lazily are decoded from the source on first access.
+/// Clones share the definitions with the original until they're modified, so taking a snapshot of a pool is cheap.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Definitions {
+    slots: Vec<DefinitionSlot>,
+    source: Option<Source>,
+}
+
+impl Definitions {
+    fn lazy(headers: Vec<DefinitionHeader>, source: Source) -> Self {
+        let mut slots: Vec<_> = headers
+            .into_iter()
+            .map(|header| DefinitionSlot {
+                header: Some(header),
+                value: Arc::default(),
+            })
+            .collect();
+        if let Some(slot) = slots.first_mut() {
+            *slot = DefinitionSlot::from(Definition::DEFAULT);
+        }
+        Definitions {
+            slots,
+            source: Some(source),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, index: usize) -> Result<&DefinitionSlot, PoolError> {
+        self.slots
+            .get(index)
+            .ok_or_else(|| PoolError(format!("Definition not found in the pool ({})", index)))
+    }
+
+    fn get(&self, index: usize) -> Result<&Definition, PoolError> {
+        let slot = self.slot(index)?;
+        if let Some(definition) = slot.value.get() {
+            return Ok(definition);
+        }
+        let (header, source) = slot
+            .header
+            .as_ref()
+            .zip(self.source.as_ref())
+            .ok_or_else(|| PoolError(format!("Definition not found in the pool ({})", index)))?;
+        let definition = Definition::decode(&mut io::Cursor::new((*source.0).as_ref()), header)
+            .map_err(|err| decode_error(err, "definition", index as u32, header.offset.into()))
+            .map_err(|err| PoolError(err.to_string()))?;
+        Ok(slot.value.get_or_init(|| definition))
+    }
+
+    fn get_mut(&mut self, index: usize) -> Result<&mut Definition, PoolError> {
+        self.get(index)?;
+        Arc::make_mut(&mut self.slots[index].value)
+            .get_mut()
+            .ok_or_else(|| PoolError(format!("Definition not found in the pool ({})", index)))
+    }
+
+    fn name(&self, index: usize) -> Result<PoolIndex<String>, PoolError> {
+        let slot = self.slot(index)?;
+        match (slot.value.get(), &slot.header) {
+            (Some(definition), _) => Ok(definition.name),
+            (None, Some(header)) => Ok(header.name),
+            (None, None) => Err(PoolError(format!("Definition not found in the pool ({})", index))),
+        }
+    }
+
+    fn type_(&self, index: usize) -> Result<DefinitionType, PoolError> {
+        let slot = self.slot(index)?;
+        match (slot.value.get(), &slot.header) {
+            (Some(definition), _) => Ok(definition.value.type_()),
+            (None, Some(header)) => Ok(header.type_),
+            (None, None) => Err(PoolError(format!("Definition not found in the pool ({})", index))),
+        }
+    }
+
+    /// Decodes all of the definitions and iterates over them, fails on the first one that can't be decoded.
+    pub(crate) fn iter_mut(&mut self) -> Result<impl Iterator<Item = &mut Definition>, PoolError> {
+        for index in 0..self.len() {
+            self.get(index)?;
+        }
+        let iter = self
+            .slots
+            .iter_mut()
+            .filter_map(|slot| Arc::make_mut(&mut slot.value).get_mut());
+        Ok(iter)
+    }
+}
+
+impl From<Vec<Definition>> for Definitions {
+    fn from(definitions: Vec<Definition>) -> Self {
+        Definitions {
+            slots: definitions.into_iter().map(DefinitionSlot::from).collect(),
+            source: None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Definitions {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeSeq};
+
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for index in 0..self.len() {
+            seq.serialize_element(self.get(index).map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Definitions {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let definitions: Vec<Definition> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(definitions.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DefinitionSlot {
+    header: Option<DefinitionHeader>,
+    value: Arc<OnceLock<Definition>>,
+}
+
+impl From<Definition> for DefinitionSlot {
+    fn from(definition: Definition) -> Self {
+        DefinitionSlot {
+            header: None,
+            value: Arc::new(OnceLock::from(definition)),
+        }
     }
+}
 
-    pub fn roots(&self) -> impl Iterator<Item = (PoolIndex<Definition>, &Definition)> {
-        self.definitions().filter(|(_, def)| def.parent.is_undefined())
+#[derive(Clone)]
+struct Source(Arc<dyn AsRef<[u8]>>);
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Source({} bytes)", (*self.0).as_ref().len())
     }
 }
 
@@ -346,6 +575,7 @@ impl ConstantPool {
 pub struct Names<K> {
     pub strings: Vec<Ref<String>>,
     mappings: HashMap<Ref<String>, PoolIndex<K>>,
+    offsets: Vec<u32>,
     phantom: PhantomData<K>,
 }
 
@@ -355,22 +585,38 @@ impl<K> Names<K> {
         let mut mappings = HashMap::new();
         for (idx, offset) in offsets.iter().enumerate() {
             input.seek(io::SeekFrom::Start((*offset).into()))?;
-            let str: Ref<String> = Ref::new(input.decode()?);
+            let str: Ref<String> = Ref::new(
+                input
+                    .decode()
+                    .map_err(|err| decode_error(err, "string", idx as u32, (*offset).into()))?,
+            );
             strings.push(str.clone());
             mappings.insert(str, PoolIndex::new(idx as u32));
         }
         let result = Names {
             strings,
             mappings,
+            offsets: offsets.to_vec(),
             phantom: PhantomData,
         };
         Ok(result)
     }
 
-    fn encoded_offsets(&self, str_map: &HashMap<Ref<String>, u32>) -> io::Result<Vec<u8>> {
+    fn retained_offsets<'a>(&'a self, data: &'a [u8]) -> impl Iterator<Item = (&'a Ref<String>, u32)> + 'a {
+        self.strings
+            .iter()
+            .zip(self.offsets.iter().copied())
+            .filter(move |(str, offset)| is_stored_at(data, str, *offset))
+    }
+
+    fn encoded_offsets(&self, data: &[u8], str_map: &HashMap<Ref<String>, u32>) -> io::Result<Vec<u8>> {
         let mut offsets = io::Cursor::new(Vec::new());
-        for string in &self.strings {
-            offsets.encode(str_map.get(string).unwrap())?;
+        for (idx, string) in self.strings.iter().enumerate() {
+            let offset = match self.offsets.get(idx) {
+                Some(offset) if is_stored_at(data, string, *offset) => *offset,
+                _ => *str_map.get(string).unwrap(),
+            };
+            offsets.encode(&offset)?;
         }
         Ok(offsets.into_inner())
     }
@@ -402,21 +648,49 @@ impl<K> Names<K> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<K> serde::Serialize for Names<K> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.strings.iter().map(|str| str.as_str()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K> serde::Deserialize<'de> for Names<K> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let strings: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+        let strings: Vec<Ref<String>> = strings.into_iter().map(Ref::new).collect();
+        let mappings = strings
+            .iter()
+            .enumerate()
+            .map(|(idx, str)| (str.clone(), PoolIndex::new(idx as u32)))
+            .collect();
+        let result = Names {
+            strings,
+            mappings,
+            offsets: vec![],
+            phantom: PhantomData,
+        };
+        Ok(result)
+    }
+}
+
 impl<K> Default for Names<K> {
     fn default() -> Self {
         Self {
             strings: vec![],
             mappings: HashMap::new(),
+            offsets: vec![],
             phantom: PhantomData,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-struct TableHeader {
-    offset: u32,
-    count: u32,
-    hash: u32,
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TableHeader {
+    pub(crate) offset: u32,
+    pub(crate) count: u32,
+    pub(crate) hash: u32,
 }
 
 impl TableHeader {
@@ -447,7 +721,7 @@ impl Encode for TableHeader {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DefinitionHeader {
     pub name: PoolIndex<String>,
     pub parent: PoolIndex<Definition>,
@@ -460,7 +734,7 @@ pub struct DefinitionHeader {
 }
 
 impl DefinitionHeader {
-    const SIZE: usize = 20;
+    pub(crate) const SIZE: usize = 20;
 
     const DEFAULT: DefinitionHeader = DefinitionHeader {
         name: PoolIndex::UNDEFINED,
@@ -533,7 +807,7 @@ impl Encode for DefinitionHeader {
 
 #[derive(BitfieldSpecifier)]
 #[bits = 8]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DefinitionType {
     Type = 0,
     Class = 1,
@@ -549,7 +823,11 @@ pub enum DefinitionType {
 
 impl Decode for DefinitionType {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
-        Ok(DefinitionType::from_bytes(input.decode()?).expect("Invalid DefinitionType enum value"))
+        let byte: u8 = input.decode()?;
+        DefinitionType::from_bytes(byte).map_err(|_| {
+            let msg = format!("Invalid definition type: {}", byte);
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        })
     }
 }
 
@@ -604,6 +882,21 @@ impl<A> Encode for PoolIndex<A> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<A> serde::Serialize for PoolIndex<A> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, A> serde::Deserialize<'de> for PoolIndex<A> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde::Deserialize::deserialize(deserializer)?;
+        Ok(PoolIndex::new(value))
+    }
+}
+
 impl<A> Clone for PoolIndex<A> {
     fn clone(&self) -> Self {
         *self
@@ -666,7 +959,18 @@ pub struct TweakDbId(String);
 #[error("{0}")]
 pub struct PoolError(pub String);
 
-fn crc(bytes: &[u8]) -> u32 {
+fn is_stored_at(data: &[u8], str: &str, offset: u32) -> bool {
+    data.get(offset as usize..)
+        .and_then(|rest| rest.strip_prefix(str.as_bytes()))
+        .is_some_and(|rest| rest.first() == Some(&0))
+}
+
+fn decode_error(err: io::Error, what: &str, index: u32, offset: u64) -> io::Error {
+    let msg = format!("Failed to decode {} {} at offset {}: {}", what, index, offset, err);
+    io::Error::new(err.kind(), msg)
+}
+
+pub(crate) fn crc(bytes: &[u8]) -> u32 {
     let mut hasher = crc32fast::Hasher::new();
     hasher.update(bytes);
     hasher.finalize()
@@ -675,8 +979,14 @@ fn crc(bytes: &[u8]) -> u32 {
 #[cfg(test)]
 mod tests {
     use std::io::{self, Cursor};
+    use std::sync::Arc;
 
-    use super::ScriptBundle;
+    use super::{crc, Header, PoolIndex, ScriptBundle};
+    use crate::decode::DecodeExt;
+    use crate::definition::{BitField, Definition};
+    use crate::encode::EncodeExt;
+    use crate::verify::verify;
+    use crate::Ref;
 
     const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
 
@@ -690,4 +1000,176 @@ mod tests {
         assert_eq!(scripts.pool.definitions.len(), scripts2.pool.definitions.len());
         Ok(())
     }
+
+    #[test]
+    fn save_unmodified_scripts_byte_exact() -> io::Result<()> {
+        let scripts = ScriptBundle::load(&mut Cursor::new(PREDEF))?;
+        let mut tmp = Cursor::new(Vec::new());
+        scripts.save(&mut tmp)?;
+        assert_eq!(tmp.get_ref().as_slice(), PREDEF);
+        Ok(())
+    }
+
+    #[test]
+    fn save_preserves_string_layout() -> io::Result<()> {
+        // swap the offsets of the first two names, so that the string data is no longer in name order
+        let mut bytes = PREDEF.to_vec();
+        let mut header: Header = Cursor::new(&bytes).decode()?;
+        let names = header.names.offset as usize;
+        let (first, second) = bytes[names..names + 8].split_at_mut(4);
+        first.swap_with_slice(second);
+        header.names.hash = crc(&bytes[names..names + header.names.count as usize * 4]);
+        header.hash = header.computed_hash()?;
+        let mut encoded_header = Cursor::new(Vec::new());
+        encoded_header.encode(&header)?;
+        bytes[..Header::SIZE].copy_from_slice(encoded_header.get_ref());
+        assert!(verify(&bytes).is_valid());
+
+        let scripts = ScriptBundle::load(&mut Cursor::new(&bytes))?;
+        let mut tmp = Cursor::new(Vec::new());
+        scripts.save(&mut tmp)?;
+        assert_eq!(tmp.into_inner(), bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn reload_bit_fields() -> io::Result<()> {
+        let mut scripts = ScriptBundle::load(&mut Cursor::new(PREDEF))?;
+        let pool = &mut scripts.pool;
+        let name = pool.names.add("EFlags".to_owned().into());
+        let bit_field_idx: PoolIndex<BitField> = pool.reserve();
+        let members = ["First", "Second"]
+            .iter()
+            .enumerate()
+            .map(|(bit, member)| {
+                let name = pool.names.add(member.to_string().into());
+                pool.add_definition(Definition::enum_value(name, bit_field_idx.cast(), bit as i64))
+            })
+            .collect();
+        let bit_field = BitField {
+            flags: 0,
+            size: 4,
+            members,
+            unk1: false,
+        };
+        pool.put_definition(bit_field_idx, Definition::bit_field(name, bit_field));
+
+        let mut tmp = Cursor::new(Vec::new());
+        scripts.save(&mut tmp)?;
+        assert!(verify(tmp.get_ref()).is_valid());
+
+        tmp.set_position(0);
+        let reloaded = ScriptBundle::load(&mut tmp)?;
+        let bit_field = reloaded.pool.bit_field(bit_field_idx).unwrap();
+        let members: Vec<_> = bit_field
+            .members
+            .iter()
+            .map(|idx| {
+                (
+                    reloaded.pool.def_name(*idx).unwrap().to_string(),
+                    reloaded.pool.enum_value(*idx).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(members, vec![("First".to_owned(), 0), ("Second".to_owned(), 1)]);
+        assert_eq!(reloaded.pool.def_name(bit_field_idx).unwrap().as_str(), "EFlags");
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unknown_versions() {
+        let mut bytes = PREDEF.to_vec();
+        bytes[4] = 14;
+        let err = ScriptBundle::load(&mut Cursor::new(&bytes)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported bundle version 14 (supported versions: 13)"
+        );
+    }
+
+    #[test]
+    fn load_corrupted_scripts() {
+        // every corrupted variant has to be rejected with an error instead of a panic
+        for offset in 0..PREDEF.len() {
+            for mask in [0x01, 0x80, 0xFF] {
+                let mut bytes = PREDEF.to_vec();
+                bytes[offset] ^= mask;
+                let _ = ScriptBundle::load(&mut Cursor::new(&bytes));
+                let _ = verify(&bytes);
+            }
+        }
+        for len in 0..PREDEF.len() {
+            assert!(ScriptBundle::load(&mut Cursor::new(&PREDEF[..len])).is_err());
+            assert!(!verify(&PREDEF[..len]).is_valid());
+        }
+    }
+
+    #[test]
+    fn report_definition_errors() {
+        let mut bytes = PREDEF.to_vec();
+        // the definition type of the first entry after the default one
+        bytes[362 + 20 + 16] = 0xFF;
+        let err = ScriptBundle::load(&mut Cursor::new(&bytes)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to decode definition header 1 at offset 382: Invalid definition type: 255"
+        );
+    }
+
+    #[test]
+    fn load_definitions_lazily() -> io::Result<()> {
+        let scripts = ScriptBundle::load_lazy(PREDEF)?;
+        let eager = ScriptBundle::load(&mut Cursor::new(PREDEF))?;
+        for entry in eager.pool.definitions() {
+            let (index, def) = entry.unwrap();
+            assert_eq!(
+                scripts.pool.def_name(index).unwrap(),
+                eager.pool.def_name(index).unwrap()
+            );
+            assert_eq!(
+                format!("{:?}", scripts.pool.definition(index).unwrap()),
+                format!("{:?}", def)
+            );
+        }
+
+        let mut tmp = Cursor::new(Vec::new());
+        scripts.save(&mut tmp)?;
+        assert_eq!(tmp.get_ref().as_slice(), PREDEF);
+        Ok(())
+    }
+
+    #[test]
+    fn share_definitions_between_clones() -> io::Result<()> {
+        let scripts = ScriptBundle::load(&mut Cursor::new(PREDEF))?;
+        let index = PoolIndex::<Definition>::new(1);
+        let mut copy = scripts.pool.clone();
+        assert!(Arc::ptr_eq(
+            &scripts.pool.definitions.slots[1].value,
+            &copy.definitions.slots[1].value
+        ));
+
+        let name = copy.names.add(Ref::new("Renamed".to_owned()));
+        copy.rename(index, name);
+        assert_eq!(copy.def_name(index).unwrap().as_str(), "Renamed");
+        assert_ne!(scripts.pool.def_name(index).unwrap().as_str(), "Renamed");
+        Ok(())
+    }
+
+    #[test]
+    fn report_lazy_definition_errors() -> io::Result<()> {
+        let mut scripts = ScriptBundle::load_lazy(PREDEF[..PREDEF.len() - 1].to_vec())?;
+        let last = PoolIndex::<Definition>::new(20);
+        assert!(scripts.pool.def_name(last).is_ok());
+        assert!(scripts
+            .pool
+            .definition(last)
+            .unwrap_err()
+            .0
+            .starts_with("Failed to decode definition 20"));
+        let errors: Vec<_> = scripts.pool.definitions().filter_map(Result::err).collect();
+        assert!(matches!(&errors[..], [err] if err.0.starts_with("Failed to decode definition 20")));
+        assert!(scripts.pool.definitions.iter_mut().is_err());
+        assert!(scripts.save(&mut Cursor::new(Vec::new())).is_err());
+        Ok(())
+    }
 }
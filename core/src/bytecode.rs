@@ -8,7 +8,9 @@ use crate::decode::{Decode, DecodeExt};
 use crate::definition::{Class, Enum, Field, Function, Local, Parameter, Type};
 use crate::encode::{Encode, EncodeExt};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instr<Loc> {
     Nop,
     Null,
@@ -882,6 +884,8 @@ impl Location {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Offset {
     pub value: i16,
 }
@@ -917,6 +921,7 @@ pub struct Label {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Code<Loc>(pub Vec<Instr<Loc>>);
 
 impl<Loc: Clone> Code<Loc> {
@@ -931,6 +936,24 @@ impl<Loc: Clone> Code<Loc> {
     }
 }
 
+impl Code<Label> {
+    /// Replaces the labels with offsets relative to the instructions using them and removes the label targets.
+    pub fn resolve_labels(self, labels: usize) -> Code<Offset> {
+        let mut locations = vec![Location::new(0); labels];
+        for (loc, instr) in self.cursor() {
+            if let Instr::Target(label) = instr {
+                locations[label.index] = loc;
+            }
+        }
+
+        let mut resolved = Vec::with_capacity(self.0.len());
+        for (loc, instr) in self.cursor().filter(|(_, instr)| !matches!(instr, Instr::Target(_))) {
+            resolved.push(instr.resolve_labels(loc, &locations));
+        }
+        Code(resolved)
+    }
+}
+
 impl Decode for Code<Offset> {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         let max_offset: u32 = input.decode()?;
@@ -106,6 +106,8 @@ impl<const N: usize> Decode for [u8; N] {
     }
 }
 
+const MAX_PREALLOCATED: usize = 0x10000;
+
 pub trait DecodeExt: io::Read + Sized {
     #[inline]
     fn decode<A: Decode>(&mut self) -> io::Result<A> {
@@ -114,7 +116,8 @@ pub trait DecodeExt: io::Read + Sized {
 
     fn decode_vec<S: Into<u32>, A: Decode>(&mut self, count: S) -> io::Result<Vec<A>> {
         let size = count.into() as usize;
-        let mut vec = Vec::with_capacity(size);
+        // the count comes from the input, so it's not trusted for preallocation
+        let mut vec = Vec::with_capacity(size.min(MAX_PREALLOCATED));
         for _ in 0..size {
             vec.push(self.decode()?);
         }
@@ -128,8 +131,11 @@ pub trait DecodeExt: io::Read + Sized {
 
     fn decode_bytes<S: Into<u32>>(&mut self, count: S) -> io::Result<Vec<u8>> {
         let size = count.into() as usize;
-        let mut vec = vec![0; size];
-        self.read_exact(&mut vec)?;
+        let mut vec = Vec::with_capacity(size.min(MAX_PREALLOCATED));
+        io::Read::read_to_end(&mut io::Read::take(self, size as u64), &mut vec)?;
+        if vec.len() != size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected end of input"));
+        }
         Ok(vec)
     }
 
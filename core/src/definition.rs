@@ -1,6 +1,6 @@
 use std::fmt::Display;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use enum_as_inner::EnumAsInner;
 use modular_bitfield::prelude::*;
@@ -11,6 +11,7 @@ use crate::decode::{Decode, DecodeExt};
 use crate::encode::{Encode, EncodeExt};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Definition {
     pub name: PoolIndex<String>,
     pub parent: PoolIndex<Definition>,
@@ -38,7 +39,7 @@ impl Definition {
             DefinitionType::Class => AnyDefinition::Class(input.decode()?),
             DefinitionType::EnumValue => AnyDefinition::EnumValue(input.decode()?),
             DefinitionType::Enum => AnyDefinition::Enum(input.decode()?),
-            DefinitionType::BitField => panic!("Bit field not supported"),
+            DefinitionType::BitField => AnyDefinition::BitField(input.decode()?),
             DefinitionType::Function => AnyDefinition::Function(input.decode()?),
             DefinitionType::Parameter => AnyDefinition::Parameter(input.decode()?),
             DefinitionType::Local => AnyDefinition::Local(input.decode()?),
@@ -119,17 +120,27 @@ impl Definition {
         Definition::default(name, PoolIndex::UNDEFINED, AnyDefinition::Enum(enum_))
     }
 
+    pub fn bit_field(name: PoolIndex<String>, bit_field: BitField) -> Definition {
+        Definition::default(name, PoolIndex::UNDEFINED, AnyDefinition::BitField(bit_field))
+    }
+
     pub fn enum_value(name: PoolIndex<String>, parent: PoolIndex<Enum>, value: i64) -> Definition {
         Definition::default(name, parent.cast(), AnyDefinition::EnumValue(value))
     }
+
+    pub fn source_file(name: PoolIndex<String>, file: SourceFile) -> Definition {
+        Definition::default(name, PoolIndex::UNDEFINED, AnyDefinition::SourceFile(file))
+    }
 }
 
 #[derive(Debug, Clone, EnumAsInner)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AnyDefinition {
     Type(Type),
     Class(Class),
     EnumValue(i64),
     Enum(Enum),
+    BitField(BitField),
     Function(Function),
     Parameter(Parameter),
     Local(Local),
@@ -144,6 +155,7 @@ impl AnyDefinition {
             AnyDefinition::Class(_) => DefinitionType::Class,
             AnyDefinition::EnumValue(_) => DefinitionType::EnumValue,
             AnyDefinition::Enum(_) => DefinitionType::Enum,
+            AnyDefinition::BitField(_) => DefinitionType::BitField,
             AnyDefinition::Function(_) => DefinitionType::Function,
             AnyDefinition::Parameter(_) => DefinitionType::Parameter,
             AnyDefinition::Local(_) => DefinitionType::Local,
@@ -160,6 +172,7 @@ impl Encode for AnyDefinition {
             AnyDefinition::Class(class) => output.encode(class),
             AnyDefinition::EnumValue(value) => output.encode(value),
             AnyDefinition::Enum(enum_) => output.encode(enum_),
+            AnyDefinition::BitField(bit_field) => output.encode(bit_field),
             AnyDefinition::Function(fun) => output.encode(fun),
             AnyDefinition::Parameter(param) => output.encode(param),
             AnyDefinition::Local(local) => output.encode(local),
@@ -170,6 +183,7 @@ impl Encode for AnyDefinition {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Class {
     pub visibility: Visibility,
     pub flags: ClassFlags,
@@ -238,6 +252,7 @@ impl Encode for Class {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Enum {
     pub flags: u8,
     pub size: u8,
@@ -271,7 +286,44 @@ impl Encode for Enum {
     }
 }
 
+/// A set of named bits, the members are enum values holding the bit positions.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitField {
+    pub flags: u8,
+    pub size: u8,
+    pub members: Vec<PoolIndex<i64>>,
+    pub unk1: bool,
+}
+
+impl Decode for BitField {
+    fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
+        let flags = input.decode()?;
+        let size = input.decode()?;
+        let members = input.decode_vec_prefixed::<u32, PoolIndex<i64>>()?;
+        let unk1 = input.decode()?;
+        let result = BitField {
+            flags,
+            size,
+            members,
+            unk1,
+        };
+
+        Ok(result)
+    }
+}
+
+impl Encode for BitField {
+    fn encode<O: io::Write>(output: &mut O, value: &Self) -> io::Result<()> {
+        output.encode(&value.flags)?;
+        output.encode(&value.size)?;
+        output.encode_slice_prefixed::<u32, PoolIndex<i64>>(&value.members)?;
+        output.encode(&value.unk1)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     pub visibility: Visibility,
     pub flags: FunctionFlags,
@@ -387,6 +439,7 @@ impl Encode for Function {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field {
     pub visibility: Visibility,
     pub type_: PoolIndex<Type>,
@@ -437,6 +490,7 @@ impl Encode for Field {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Prim,
     Class,
@@ -458,7 +512,10 @@ impl Decode for Type {
             4 => Ok(Type::Array(input.decode()?)),
             5 => Ok(Type::StaticArray(input.decode()?, input.decode()?)),
             6 => Ok(Type::ScriptRef(input.decode()?)),
-            _ => panic!("Unknown Type enum value {}", tag),
+            other => {
+                let msg = format!("Invalid type tag: {}", other);
+                Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+            }
         }
     }
 }
@@ -494,6 +551,7 @@ impl Encode for Type {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Local {
     pub type_: PoolIndex<Type>,
     pub flags: LocalFlags,
@@ -521,6 +579,7 @@ impl Encode for Local {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parameter {
     pub type_: PoolIndex<Type>,
     pub flags: ParameterFlags,
@@ -542,12 +601,29 @@ impl Encode for Parameter {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SourceFile {
     pub id: u32,
     pub path_hash: u64,
     pub path: PathBuf,
 }
 
+impl SourceFile {
+    pub fn new(id: u32, path: PathBuf) -> SourceFile {
+        // FNV-1a hash of the path in the format it's stored in
+        let path_hash = Self::encoded_path(&path)
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |acc, byte| {
+                (acc ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        SourceFile { id, path_hash, path }
+    }
+
+    fn encoded_path(path: &Path) -> String {
+        path.to_string_lossy().replace('/', "\\")
+    }
+}
+
 impl Decode for SourceFile {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         let id = input.decode()?;
@@ -563,10 +639,51 @@ impl Encode for SourceFile {
     fn encode<O: io::Write>(output: &mut O, value: &Self) -> io::Result<()> {
         output.encode(&value.id)?;
         output.encode(&value.path_hash)?;
-        output.encode_str_prefixed::<u16>(&value.path.to_str().unwrap().replace('/', "\\"))
+        output.encode_str_prefixed::<u16>(&SourceFile::encoded_path(&value.path))
     }
 }
 
+/// Flags are serialized as the names of the bits that are set, bits without a name are listed as `bitN`.
+#[cfg(feature = "serde")]
+macro_rules! serde_flags {
+    ($ty:ident, $repr:ty, [$($name:literal),* $(,)?]) => {
+        impl $ty {
+            const BIT_NAMES: &'static [&'static str] = &[$($name),*];
+        }
+
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let bits = <$repr>::from_le_bytes($ty::into_bytes(*self));
+                let names = (0..<$repr>::BITS)
+                    .filter(|bit| bits & (1 << bit) != 0)
+                    .map(|bit| match $ty::BIT_NAMES.get(bit as usize) {
+                        Some(name) if !name.is_empty() => (*name).to_owned(),
+                        _ => format!("bit{}", bit),
+                    });
+                serializer.collect_seq(names)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let names: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+                let mut bits: $repr = 0;
+                for name in &names {
+                    let bit = $ty::BIT_NAMES
+                        .iter()
+                        .position(|known| !known.is_empty() && known == name)
+                        .map(|bit| bit as u32)
+                        .or_else(|| name.strip_prefix("bit")?.parse().ok())
+                        .filter(|bit| *bit < <$repr>::BITS)
+                        .ok_or_else(|| serde::de::Error::custom(format!("unknown flag: {}", name)))?;
+                    bits |= 1 << bit;
+                }
+                Ok($ty::from_bytes(bits.to_le_bytes()))
+            }
+        }
+    };
+}
+
 #[bitfield(bits = 16)]
 #[derive(Debug, Clone, Copy)]
 pub struct FieldFlags {
@@ -585,6 +702,21 @@ pub struct FieldFlags {
     pub remainder: B5,
 }
 
+#[cfg(feature = "serde")]
+serde_flags!(FieldFlags, u16, [
+    "is_native",
+    "is_editable",
+    "is_inline",
+    "is_const",
+    "is_replicated",
+    "has_hint",
+    "is_instance_editable",
+    "has_default",
+    "is_persistent",
+    "is_test_only",
+    "is_browsable",
+]);
+
 impl Decode for FieldFlags {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         Ok(FieldFlags::from_bytes(input.decode()?))
@@ -605,6 +737,9 @@ pub struct LocalFlags {
     pub remainder: B7,
 }
 
+#[cfg(feature = "serde")]
+serde_flags!(LocalFlags, u8, ["is_const"]);
+
 impl Decode for LocalFlags {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         Ok(LocalFlags::from_bytes(input.decode()?))
@@ -628,6 +763,14 @@ pub struct ParameterFlags {
     pub remainder: B4,
 }
 
+#[cfg(feature = "serde")]
+serde_flags!(ParameterFlags, u8, [
+    "is_optional",
+    "is_out",
+    "is_short_circuit",
+    "is_const"
+]);
+
 impl Decode for ParameterFlags {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         Ok(ParameterFlags::from_bytes(input.decode()?))
@@ -656,6 +799,19 @@ pub struct ClassFlags {
     pub remainder: B7,
 }
 
+#[cfg(feature = "serde")]
+serde_flags!(ClassFlags, u16, [
+    "is_native",
+    "is_abstract",
+    "is_final",
+    "is_struct",
+    "has_functions",
+    "has_fields",
+    "is_import_only",
+    "is_test_only",
+    "has_overrides",
+]);
+
 impl Decode for ClassFlags {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         Ok(ClassFlags::from_bytes(input.decode()?))
@@ -695,6 +851,32 @@ pub struct FunctionFlags {
     pub remainder: B10,
 }
 
+#[cfg(feature = "serde")]
+serde_flags!(FunctionFlags, u32, [
+    "is_static",
+    "is_exec",
+    "is_timer",
+    "is_final",
+    "is_native",
+    "is_callback",
+    "is_operator",
+    "has_return_value",
+    "has_base_method",
+    "has_parameters",
+    "has_locals",
+    "has_body",
+    "is_cast",
+    "is_implicit_cast",
+    "",
+    "",
+    "",
+    "",
+    "is_const",
+    "is_thread_safe",
+    "is_quest",
+    "is_test_only",
+]);
+
 impl Decode for FunctionFlags {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
         Ok(FunctionFlags::from_bytes(input.decode()?))
@@ -710,6 +892,7 @@ impl Encode for FunctionFlags {
 #[derive(BitfieldSpecifier)]
 #[bits = 8]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Visibility {
     Public = 0,
     Protected = 1,
@@ -729,7 +912,11 @@ impl Display for Visibility {
 
 impl Decode for Visibility {
     fn decode<I: io::Read>(input: &mut I) -> io::Result<Self> {
-        Ok(Visibility::from_bytes(input.decode()?).expect("Invalid Visibility enum value"))
+        let byte: u8 = input.decode()?;
+        Visibility::from_bytes(byte).map_err(|_| {
+            let msg = format!("Invalid visibility: {}", byte);
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        })
     }
 }
 
@@ -740,6 +927,7 @@ impl Encode for Visibility {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SourceReference {
     pub file: PoolIndex<Definition>,
     pub line: u32,
@@ -762,6 +950,7 @@ impl Encode for SourceReference {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Property {
     pub name: String,
     pub value: String,
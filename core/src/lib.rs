@@ -5,6 +5,7 @@ pub mod decode;
 pub mod definition;
 pub mod encode;
 pub mod mapper;
+pub mod verify;
 
 #[cfg(not(feature = "arc"))]
 pub type Ref<A> = std::rc::Rc<A>;
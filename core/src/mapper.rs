@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::marker::PhantomData;
 
-use crate::bundle::{ConstantPool, PoolIndex};
+use crate::bundle::{ConstantPool, PoolError, PoolIndex};
 use crate::bytecode::Instr;
 use crate::definition::{AnyDefinition, Class, Function};
 
@@ -48,8 +48,8 @@ where
     CM: Mapper<PoolIndex<Class>>,
     FM: Mapper<PoolIndex<Function>>,
 {
-    pub fn map(&self, pool: &mut ConstantPool) {
-        for def in &mut pool.definitions {
+    pub fn map(&self, pool: &mut ConstantPool) -> Result<(), PoolError> {
+        for def in pool.definitions.iter_mut()? {
             match &mut def.value {
                 AnyDefinition::Type(_) => {}
                 AnyDefinition::Class(class) => {
@@ -57,6 +57,7 @@ where
                 }
                 AnyDefinition::EnumValue(_) => {}
                 AnyDefinition::Enum(_) => {}
+                AnyDefinition::BitField(_) => {}
                 AnyDefinition::Function(fun) => {
                     def.parent = self.map_class.apply(def.parent.cast()).cast();
                     for instr in &mut fun.code.0 {
@@ -75,6 +76,7 @@ where
                 AnyDefinition::SourceFile(_) => {}
             }
         }
+        Ok(())
     }
 
     fn map_instr<L>(&self, instr: &mut Instr<L>) {
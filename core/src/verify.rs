@@ -0,0 +1,800 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::bundle::{crc, ConstantPool, DefinitionHeader, DefinitionType, Header, PoolError, PoolIndex, TableHeader};
+use crate::bytecode::{Instr, IntrinsicOp, Location, Offset};
+use crate::decode::DecodeExt;
+use crate::definition::{AnyDefinition, Definition, Function, Type};
+
+#[derive(Debug, Clone, Error)]
+pub enum Issue {
+    #[error("invalid file header: {0}")]
+    InvalidHeader(String),
+    #[error("header hash mismatch (stored {stored:08x}, computed {computed:08x})")]
+    HeaderHash { stored: u32, computed: u32 },
+    #[error("{0} table is out of bounds")]
+    TableOutOfBounds(&'static str),
+    #[error("{table} table hash mismatch (stored {stored:08x}, computed {computed:08x})")]
+    TableHash {
+        table: &'static str,
+        stored: u32,
+        computed: u32,
+    },
+    #[error("string {index} in the {table} table has an invalid offset {offset}")]
+    InvalidString {
+        table: &'static str,
+        index: u32,
+        offset: u32,
+    },
+    #[error("definition {index} has an invalid header: {message}")]
+    InvalidDefinitionHeader { index: u32, message: String },
+    #[error("definition {index} at offset {offset} could not be decoded: {message}")]
+    InvalidDefinition { index: u32, offset: u32, message: String },
+    #[error("definition {index} at offset {offset} has a size of {size}, but {decoded} bytes were decoded")]
+    DefinitionSize {
+        index: u32,
+        offset: u32,
+        size: u32,
+        decoded: u32,
+    },
+    #[error("definition {index} has an invalid name {name}")]
+    InvalidName { index: u32, name: u32 },
+    #[error("definition {index} refers to {target} as {expected:?}, but found {found}")]
+    InvalidReference {
+        index: u32,
+        target: u32,
+        expected: DefinitionType,
+        found: String,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub definitions: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl VerifyReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks the hashes, the table bounds and the definitions of an encoded bundle.
+pub fn verify(bytes: &[u8]) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    let header: Header = match io::Cursor::new(bytes).decode() {
+        Ok(header) => header,
+        Err(err) => {
+            report.issues.push(Issue::InvalidHeader(err.to_string()));
+            return report;
+        }
+    };
+
+    let mut verifier = Verifier { bytes, issues: vec![] };
+    verifier.check_header_hash(&header);
+    verifier.check_table("data", &header.data, 1);
+    for (name, table) in [
+        ("names", &header.names),
+        ("tweakdb", &header.tweakdb_indexes),
+        ("resources", &header.resources),
+        ("strings", &header.strings),
+    ] {
+        if verifier.check_table(name, table, 4) {
+            verifier.check_strings(name, table, &header.data);
+        }
+    }
+    if verifier.check_table("definitions", &header.definitions, DefinitionHeader::SIZE) {
+        report.definitions = header.definitions.count as usize;
+        verifier.check_definitions(&header);
+    }
+    report.issues = verifier.issues;
+    report
+}
+
+struct Verifier<'a> {
+    bytes: &'a [u8],
+    issues: Vec<Issue>,
+}
+
+impl<'a> Verifier<'a> {
+    fn check_header_hash(&mut self, header: &Header) {
+        match header.computed_hash() {
+            Ok(computed) if computed != header.hash => {
+                self.issues.push(Issue::HeaderHash {
+                    stored: header.hash,
+                    computed,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn table_bytes(&self, table: &TableHeader, item_size: usize) -> Option<&'a [u8]> {
+        let start = table.offset as usize;
+        let end = start.checked_add((table.count as usize).checked_mul(item_size)?)?;
+        self.bytes.get(start..end)
+    }
+
+    fn check_table(&mut self, name: &'static str, table: &TableHeader, item_size: usize) -> bool {
+        match self.table_bytes(table, item_size) {
+            Some(bytes) => {
+                let computed = crc(bytes);
+                if computed != table.hash {
+                    self.issues.push(Issue::TableHash {
+                        table: name,
+                        stored: table.hash,
+                        computed,
+                    });
+                }
+                true
+            }
+            None => {
+                self.issues.push(Issue::TableOutOfBounds(name));
+                false
+            }
+        }
+    }
+
+    fn check_strings(&mut self, name: &'static str, table: &TableHeader, data: &TableHeader) {
+        let (offsets, data) = match (self.table_bytes(table, 4), self.table_bytes(data, 1)) {
+            (Some(offsets), Some(data)) => (offsets, data),
+            _ => return,
+        };
+        for (index, chunk) in offsets.chunks_exact(4).enumerate() {
+            let offset = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let is_valid = data
+                .get(offset as usize..)
+                .and_then(|rest| rest.iter().position(|b| *b == 0).map(|end| &rest[..end]))
+                .and_then(|str| std::str::from_utf8(str).ok())
+                .is_some();
+            if !is_valid {
+                self.issues.push(Issue::InvalidString {
+                    table: name,
+                    index: index as u32,
+                    offset,
+                });
+            }
+        }
+    }
+
+    fn check_definitions(&mut self, header: &Header) {
+        let bytes = match self.table_bytes(&header.definitions, DefinitionHeader::SIZE) {
+            Some(bytes) => bytes,
+            None => return,
+        };
+        let mut types = Vec::with_capacity(header.definitions.count as usize);
+        let mut definitions = Vec::with_capacity(header.definitions.count as usize);
+
+        for (index, chunk) in bytes.chunks_exact(DefinitionHeader::SIZE).enumerate() {
+            let index = index as u32;
+            let def_header: DefinitionHeader = match io::Cursor::new(chunk).decode() {
+                Ok(def_header) => def_header,
+                Err(err) => {
+                    let message = err.to_string();
+                    self.issues.push(Issue::InvalidDefinitionHeader { index, message });
+                    types.push(None);
+                    continue;
+                }
+            };
+            types.push(Some(def_header.type_));
+            if index == 0 {
+                continue;
+            }
+            if let Some(definition) = self.check_definition(index, &def_header) {
+                definitions.push((index, definition));
+            }
+        }
+
+        for (index, definition) in &definitions {
+            if u32::from(definition.name) >= header.names.count {
+                self.issues.push(Issue::InvalidName {
+                    index: *index,
+                    name: definition.name.into(),
+                });
+            }
+            References {
+                index: *index,
+                types: &types,
+                issues: &mut self.issues,
+            }
+            .check(definition);
+        }
+    }
+
+    fn check_definition(&mut self, index: u32, header: &DefinitionHeader) -> Option<Definition> {
+        let offset = header.offset;
+        let mut cursor = io::Cursor::new(self.bytes);
+        match Definition::decode(&mut cursor, header) {
+            Ok(definition) => {
+                let decoded = (cursor.position() - u64::from(offset)) as u32;
+                // this tool writes the end offset of a definition as its size, a length is accepted as well
+                if header.size != decoded && u64::from(header.size) != cursor.position() {
+                    self.issues.push(Issue::DefinitionSize {
+                        index,
+                        offset,
+                        size: header.size,
+                        decoded,
+                    });
+                }
+                Some(definition)
+            }
+            Err(err) => {
+                let message = err.to_string();
+                self.issues.push(Issue::InvalidDefinition { index, offset, message });
+                None
+            }
+        }
+    }
+}
+
+struct References<'a> {
+    index: u32,
+    types: &'a [Option<DefinitionType>],
+    issues: &'a mut Vec<Issue>,
+}
+
+impl<'a> References<'a> {
+    fn check(&mut self, definition: &Definition) {
+        let parent = match &definition.value {
+            AnyDefinition::Function(_) | AnyDefinition::Field(_) => Some(DefinitionType::Class),
+            AnyDefinition::Parameter(_) | AnyDefinition::Local(_) => Some(DefinitionType::Function),
+            AnyDefinition::EnumValue(_) if self.type_of(definition.parent) == Some(DefinitionType::BitField) => None,
+            AnyDefinition::EnumValue(_) => Some(DefinitionType::Enum),
+            _ => None,
+        };
+        if let Some(expected) = parent {
+            if !definition.parent.is_undefined() {
+                self.expect(definition.parent, expected);
+            }
+        }
+
+        match &definition.value {
+            AnyDefinition::Type(type_) => match type_ {
+                Type::Prim | Type::Class => {}
+                Type::Ref(inner)
+                | Type::WeakRef(inner)
+                | Type::Array(inner)
+                | Type::StaticArray(inner, _)
+                | Type::ScriptRef(inner) => self.expect(*inner, DefinitionType::Type),
+            },
+            AnyDefinition::Class(class) => {
+                if !class.base.is_undefined() {
+                    self.expect(class.base, DefinitionType::Class);
+                }
+                self.expect_all(&class.functions, DefinitionType::Function);
+                self.expect_all(&class.fields, DefinitionType::Field);
+                self.expect_all(&class.overrides, DefinitionType::Field);
+            }
+            AnyDefinition::Enum(enum_) => self.expect_all(&enum_.members, DefinitionType::EnumValue),
+            AnyDefinition::BitField(bit_field) => self.expect_all(&bit_field.members, DefinitionType::EnumValue),
+            AnyDefinition::Function(fun) => {
+                if let Some(type_) = fun.return_type {
+                    self.expect(type_, DefinitionType::Type);
+                }
+                if let Some(base) = fun.base_method {
+                    self.expect(base, DefinitionType::Function);
+                }
+                if let Some(source) = &fun.source {
+                    self.expect(source.file, DefinitionType::SourceFile);
+                }
+                self.expect_all(&fun.parameters, DefinitionType::Parameter);
+                self.expect_all(&fun.locals, DefinitionType::Local);
+            }
+            AnyDefinition::Parameter(param) => self.expect(param.type_, DefinitionType::Type),
+            AnyDefinition::Local(local) => self.expect(local.type_, DefinitionType::Type),
+            AnyDefinition::Field(field) => self.expect(field.type_, DefinitionType::Type),
+            AnyDefinition::EnumValue(_) | AnyDefinition::SourceFile(_) => {}
+        }
+    }
+
+    fn type_of(&self, index: PoolIndex<Definition>) -> Option<DefinitionType> {
+        self.types.get(u32::from(index) as usize).copied().flatten()
+    }
+
+    fn expect_all<A>(&mut self, targets: &[PoolIndex<A>], expected: DefinitionType) {
+        for target in targets {
+            self.expect(*target, expected);
+        }
+    }
+
+    fn expect<A>(&mut self, target: PoolIndex<A>, expected: DefinitionType) {
+        let target = u32::from(target);
+        let found = match self.types.get(target as usize) {
+            _ if target == 0 => "undefined".to_owned(),
+            Some(Some(found)) if *found == expected => return,
+            Some(Some(found)) => format!("{:?}", found),
+            Some(None) => "invalid".to_owned(),
+            None => "out of bounds".to_owned(),
+        };
+        self.issues.push(Issue::InvalidReference {
+            index: self.index,
+            target,
+            expected,
+            found,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum CodeIssue {
+    #[error("jump at {location} targets {target}, which is not an instruction boundary")]
+    InvalidJump { location: u16, target: i32 },
+    #[error("call at {location} passes {found} arguments to {function}, which takes {expected}")]
+    ArgumentCount {
+        location: u16,
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("call at {location} refers to {index}, which is not a function")]
+    InvalidCallee { location: u16, index: u32 },
+    #[error("unexpected ParamEnd at {0}")]
+    UnexpectedParamEnd(u16),
+    #[error("call at {0} has no ParamEnd")]
+    MissingParamEnd(u16),
+    #[error("instruction at {0} is missing its operands")]
+    MissingOperands(u16),
+    #[error("local {index} used at {location} does not belong to the function")]
+    ForeignLocal { location: u16, index: u32 },
+    #[error("parameter {index} used at {location} does not belong to the function")]
+    ForeignParam { location: u16, index: u32 },
+    #[error("not all paths return a value")]
+    MissingReturn,
+}
+
+/// Checks that the bytecode of a function is well-formed and consistent with the definitions in the pool.
+pub fn verify_code(pool: &ConstantPool, index: PoolIndex<Function>) -> Result<Vec<CodeIssue>, PoolError> {
+    let function = pool.function(index)?;
+    let instrs: Vec<_> = function.code.cursor().collect();
+    let end = Location::new(instrs.iter().map(|(_, instr)| instr.size()).sum());
+    let mut verifier = CodeVerifier {
+        pool,
+        function,
+        instrs: &instrs,
+        end,
+        pos: 0,
+        issues: vec![],
+    };
+
+    verifier.check_jumps();
+    while verifier.pos < instrs.len() {
+        if let Err(issue) = verifier.check_expr() {
+            verifier.issues.push(issue);
+            break;
+        }
+    }
+    if function.return_type.is_some() && !instrs.is_empty() && verifier.is_end_reachable() {
+        verifier.issues.push(CodeIssue::MissingReturn);
+    }
+    Ok(verifier.issues)
+}
+
+struct CodeVerifier<'a> {
+    pool: &'a ConstantPool,
+    function: &'a Function,
+    instrs: &'a [(Location, Instr<Offset>)],
+    end: Location,
+    pos: usize,
+    issues: Vec<CodeIssue>,
+}
+
+impl<'a> CodeVerifier<'a> {
+    fn check_jumps(&mut self) {
+        for (location, instr) in self.instrs {
+            for offset in jump_offsets(instr) {
+                let target = location.value as i32 + offset.value as i32;
+                if !self.is_boundary(target) {
+                    self.issues.push(CodeIssue::InvalidJump {
+                        location: location.value,
+                        target,
+                    });
+                }
+            }
+        }
+    }
+
+    fn is_boundary(&self, target: i32) -> bool {
+        target == self.end.value as i32
+            || self
+                .instrs
+                .binary_search_by_key(&target, |(loc, _)| loc.value as i32)
+                .is_ok()
+    }
+
+    fn check_expr(&mut self) -> Result<(), CodeIssue> {
+        let (location, instr) = &self.instrs[self.pos];
+        let location = location.value;
+        self.pos += 1;
+
+        let operands = match instr {
+            Instr::Local(idx) => {
+                if !self.function.locals.contains(idx) {
+                    let index = (*idx).into();
+                    self.issues.push(CodeIssue::ForeignLocal { location, index });
+                }
+                0
+            }
+            Instr::Param(idx) => {
+                if !self.function.parameters.contains(idx) {
+                    let index = (*idx).into();
+                    self.issues.push(CodeIssue::ForeignParam { location, index });
+                }
+                0
+            }
+            Instr::InvokeStatic(_, _, idx, _) => {
+                let found = self.check_args(location)?;
+                match self.pool.function(*idx) {
+                    Ok(callee) if callee.parameters.len() != found => {
+                        self.issues.push(CodeIssue::ArgumentCount {
+                            location,
+                            function: self
+                                .pool
+                                .def_name(*idx)
+                                .map(|name| name.to_string())
+                                .unwrap_or_default(),
+                            expected: callee.parameters.len(),
+                            found,
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(_) => self.issues.push(CodeIssue::InvalidCallee {
+                        location,
+                        index: (*idx).into(),
+                    }),
+                }
+                0
+            }
+            Instr::InvokeVirtual(_, _, _, _) => {
+                self.check_args(location)?;
+                0
+            }
+            Instr::ParamEnd => return Err(CodeIssue::UnexpectedParamEnd(location)),
+            Instr::Construct(n, _) => *n as usize,
+            other => operand_count(other),
+        };
+        for _ in 0..operands {
+            if self.pos >= self.instrs.len() {
+                return Err(CodeIssue::MissingOperands(location));
+            }
+            self.check_expr()?;
+        }
+        Ok(())
+    }
+
+    fn check_args(&mut self, location: u16) -> Result<usize, CodeIssue> {
+        let mut count = 0;
+        loop {
+            match self.instrs.get(self.pos) {
+                Some((_, Instr::ParamEnd)) => {
+                    self.pos += 1;
+                    return Ok(count);
+                }
+                Some(_) => {
+                    self.check_expr()?;
+                    count += 1;
+                }
+                None => return Err(CodeIssue::MissingParamEnd(location)),
+            }
+        }
+    }
+
+    fn is_end_reachable(&self) -> bool {
+        let mut visited = vec![false; self.instrs.len()];
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let Some((location, instr)) = self.instrs.get(index) else {
+                return true;
+            };
+            if std::mem::replace(&mut visited[index], true) {
+                continue;
+            }
+            let falls_through = !matches!(instr, Instr::Jump(_) | Instr::Return);
+            if falls_through {
+                stack.push(index + 1);
+            }
+            // the head of a `while true` loop never exits, the loop can only be left with a break or a return
+            let unconditional = matches!(
+                (instr, self.instrs.get(index + 1)),
+                (Instr::JumpIfFalse(_), Some((_, Instr::TrueConst)))
+            );
+            if unconditional {
+                continue;
+            }
+            for offset in jump_offsets(instr) {
+                let target = offset.absolute(*location);
+                if target == self.end {
+                    return true;
+                }
+                if let Ok(target) = self.instrs.binary_search_by_key(&target, |(loc, _)| *loc) {
+                    stack.push(target);
+                }
+            }
+        }
+        false
+    }
+}
+
+fn jump_offsets(instr: &Instr<Offset>) -> Vec<Offset> {
+    match instr {
+        Instr::Target(offset)
+        | Instr::Switch(_, offset)
+        | Instr::Jump(offset)
+        | Instr::JumpIfFalse(offset)
+        | Instr::Skip(offset)
+        | Instr::Context(offset)
+        | Instr::InvokeStatic(offset, _, _, _)
+        | Instr::InvokeVirtual(offset, _, _, _) => vec![*offset],
+        Instr::SwitchLabel(first, second) | Instr::Conditional(first, second) => vec![*first, *second],
+        _ => vec![],
+    }
+}
+
+/// The number of expressions that follow an instruction as its operands.
+fn operand_count<L>(instr: &Instr<L>) -> usize {
+    match instr {
+        Instr::Assign
+        | Instr::Context(_)
+        | Instr::Equals(_)
+        | Instr::NotEquals(_)
+        | Instr::ArrayElement(_)
+        | Instr::StaticArrayElement(_) => 2,
+        Instr::Conditional(_, _) => 3,
+        Instr::Return
+        | Instr::Switch(_, _)
+        | Instr::SwitchLabel(_, _)
+        | Instr::JumpIfFalse(_)
+        | Instr::Skip(_)
+        | Instr::StructField(_)
+        | Instr::Delete
+        | Instr::RefToBool
+        | Instr::WeakRefToBool
+        | Instr::EnumToI32(_, _)
+        | Instr::I32ToEnum(_, _)
+        | Instr::DynamicCast(_, _)
+        | Instr::VariantIsDefined
+        | Instr::VariantToString => 1,
+        Instr::ArrayClear(_) => IntrinsicOp::ArrayClear.arg_count().into(),
+        Instr::ArraySize(_) | Instr::StaticArraySize(_) => IntrinsicOp::ArraySize.arg_count().into(),
+        Instr::ArrayResize(_) => IntrinsicOp::ArrayResize.arg_count().into(),
+        Instr::ArrayFindFirst(_)
+        | Instr::ArrayFindFirstFast(_)
+        | Instr::StaticArrayFindFirst(_)
+        | Instr::StaticArrayFindFirstFast(_) => IntrinsicOp::ArrayFindFirst.arg_count().into(),
+        Instr::ArrayFindLast(_)
+        | Instr::ArrayFindLastFast(_)
+        | Instr::StaticArrayFindLast(_)
+        | Instr::StaticArrayFindLastFast(_) => IntrinsicOp::ArrayFindLast.arg_count().into(),
+        Instr::ArrayContains(_)
+        | Instr::ArrayContainsFast(_)
+        | Instr::StaticArrayContains(_)
+        | Instr::StaticArrayContainsFast(_) => IntrinsicOp::ArrayContains.arg_count().into(),
+        Instr::ArrayCount(_)
+        | Instr::ArrayCountFast(_)
+        | Instr::StaticArrayCount(_)
+        | Instr::StaticArrayCountFast(_) => IntrinsicOp::ArrayCount.arg_count().into(),
+        Instr::ArrayPush(_) => IntrinsicOp::ArrayPush.arg_count().into(),
+        Instr::ArrayPop(_) => IntrinsicOp::ArrayPop.arg_count().into(),
+        Instr::ArrayInsert(_) => IntrinsicOp::ArrayInsert.arg_count().into(),
+        Instr::ArrayRemove(_) | Instr::ArrayRemoveFast(_) => IntrinsicOp::ArrayRemove.arg_count().into(),
+        Instr::ArrayGrow(_) => IntrinsicOp::ArrayGrow.arg_count().into(),
+        Instr::ArrayErase(_) | Instr::ArrayEraseFast(_) => IntrinsicOp::ArrayErase.arg_count().into(),
+        Instr::ArrayLast(_) | Instr::StaticArrayLast(_) => IntrinsicOp::ArrayLast.arg_count().into(),
+        Instr::ToString(_) => IntrinsicOp::ToString.arg_count().into(),
+        Instr::ToVariant(_) => IntrinsicOp::ToVariant.arg_count().into(),
+        Instr::FromVariant(_) => IntrinsicOp::FromVariant.arg_count().into(),
+        Instr::VariantIsRef => IntrinsicOp::VariantIsRef.arg_count().into(),
+        Instr::VariantIsArray => IntrinsicOp::VariantIsArray.arg_count().into(),
+        Instr::VariantTypeName => IntrinsicOp::VariantTypeName.arg_count().into(),
+        Instr::WeakRefToRef => IntrinsicOp::WeakRefToRef.arg_count().into(),
+        Instr::RefToWeakRef => IntrinsicOp::RefToWeakRef.arg_count().into(),
+        Instr::AsRef(_) => IntrinsicOp::AsRef.arg_count().into(),
+        Instr::Deref(_) => IntrinsicOp::Deref.arg_count().into(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::bundle::ScriptBundle;
+    use crate::bytecode::Code;
+    use crate::definition::{FunctionFlags, Parameter, ParameterFlags, Visibility};
+
+    const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+    #[test]
+    fn verify_valid_bundles() {
+        assert!(verify(PREDEF).is_valid());
+
+        let bundle = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+        let mut output = Cursor::new(Vec::new());
+        bundle.save(&mut output).unwrap();
+        assert!(verify(output.get_ref()).is_valid());
+    }
+
+    #[test]
+    fn verify_corrupted_bundles() {
+        let mut corrupted = PREDEF.to_vec();
+        corrupted[Header::SIZE] ^= 0x01;
+        let report = verify(&corrupted);
+        assert!(matches!(report.issues[..], [Issue::TableHash { table: "data", .. }]));
+
+        let report = verify(&PREDEF[..400]);
+        assert!(matches!(report.issues[..], [
+            Issue::TableOutOfBounds("strings"),
+            Issue::TableOutOfBounds("definitions")
+        ]));
+
+        let report = verify(&PREDEF[..PREDEF.len() - 1]);
+        assert!(matches!(report.issues[..], [Issue::InvalidDefinition {
+            index: 20,
+            ..
+        }]));
+
+        let report = verify(&PREDEF[..16]);
+        assert!(matches!(report.issues[..], [Issue::InvalidHeader(_)]));
+    }
+
+    #[test]
+    fn verify_definition_sizes() {
+        let header: Header = Cursor::new(PREDEF).decode().unwrap();
+        // the size of the first definition is stored as its end offset
+        let pos = header.definitions.offset as usize + DefinitionHeader::SIZE + 8;
+        let offset = u32::from_le_bytes(PREDEF[pos..pos + 4].try_into().unwrap());
+        let end = u32::from_le_bytes(PREDEF[pos + 4..pos + 8].try_into().unwrap());
+        let with_size = |size: u32| {
+            let mut bytes = PREDEF.to_vec();
+            bytes[pos + 4..pos + 8].copy_from_slice(&size.to_le_bytes());
+            verify(&bytes)
+                .issues
+                .iter()
+                .any(|issue| matches!(issue, Issue::DefinitionSize { index: 1, .. }))
+        };
+
+        assert!(!with_size(end));
+        assert!(!with_size(end - offset));
+        assert!(with_size(end + 4));
+    }
+
+    #[test]
+    fn verify_valid_code() {
+        let mut pool = empty_pool();
+        let (callee, params) = function(&mut pool, 1, true);
+        set_code(&mut pool, callee, vec![Instr::Return, Instr::Param(params[0])]);
+
+        let (caller, params) = function(&mut pool, 1, true);
+        let exit = call_size(1);
+        set_code(&mut pool, caller, vec![
+            Instr::Return,
+            Instr::InvokeStatic(Offset::new(exit), 0, callee, 0),
+            Instr::Param(params[0]),
+            Instr::ParamEnd,
+            Instr::Nop,
+        ]);
+
+        assert!(verify_code(&pool, callee).unwrap().is_empty());
+        assert!(verify_code(&pool, caller).unwrap().is_empty());
+    }
+
+    #[test]
+    fn verify_infinite_loops() {
+        let mut pool = empty_pool();
+        let (index, params) = function(&mut pool, 1, true);
+        let mut code = vec![
+            Instr::JumpIfFalse(Offset::new(0)),
+            Instr::TrueConst,
+            Instr::Return,
+            Instr::Param(params[0]),
+        ];
+        let back: u16 = code.iter().map(Instr::size).sum();
+        code.push(Instr::Jump(Offset::new(-(back as i16))));
+        code[0] = Instr::JumpIfFalse(Offset::new((back + Instr::Jump(Offset::new(0)).size()) as i16));
+        set_code(&mut pool, index, code.clone());
+        assert!(verify_code(&pool, index).unwrap().is_empty());
+
+        code[1] = Instr::FalseConst;
+        set_code(&mut pool, index, code);
+        assert!(matches!(verify_code(&pool, index).unwrap()[..], [
+            CodeIssue::MissingReturn
+        ]));
+    }
+
+    #[test]
+    fn verify_invalid_code() {
+        let mut pool = empty_pool();
+        let (callee, callee_params) = function(&mut pool, 2, false);
+        set_code(&mut pool, callee, vec![
+            Instr::Jump(Offset::new(1)),
+            Instr::Param(callee_params[0]),
+        ]);
+        assert!(matches!(verify_code(&pool, callee).unwrap()[..], [
+            CodeIssue::InvalidJump { location: 0, target: 1 }
+        ]));
+
+        let (caller, _) = function(&mut pool, 0, true);
+        set_code(&mut pool, caller, vec![
+            Instr::InvokeStatic(Offset::new(call_size(1)), 0, callee, 0),
+            Instr::Param(callee_params[1]),
+            Instr::ParamEnd,
+        ]);
+        assert!(matches!(verify_code(&pool, caller).unwrap()[..], [
+            CodeIssue::ForeignParam { location: 15, .. },
+            CodeIssue::ArgumentCount {
+                location: 0,
+                expected: 2,
+                found: 1,
+                ..
+            },
+            CodeIssue::MissingReturn
+        ]));
+
+        set_code(&mut pool, caller, vec![Instr::Return, Instr::ParamEnd]);
+        assert!(matches!(verify_code(&pool, caller).unwrap()[..], [
+            CodeIssue::UnexpectedParamEnd(1)
+        ]));
+
+        set_code(&mut pool, caller, vec![
+            Instr::Return,
+            Instr::InvokeStatic(Offset::new(call_size(0)), 0, callee, 0),
+        ]);
+        assert!(matches!(verify_code(&pool, caller).unwrap()[..], [
+            CodeIssue::InvalidJump { location: 1, .. },
+            CodeIssue::MissingParamEnd(1)
+        ]));
+    }
+
+    fn empty_pool() -> ConstantPool {
+        let mut pool = ConstantPool::default();
+        pool.reserve::<Definition>();
+        pool
+    }
+
+    fn function(
+        pool: &mut ConstantPool,
+        param_count: usize,
+        has_return: bool,
+    ) -> (PoolIndex<Function>, Vec<PoolIndex<Parameter>>) {
+        let index = pool.reserve();
+        let parameters: Vec<_> = (0..param_count)
+            .map(|_| {
+                let param = Parameter {
+                    type_: PoolIndex::UNDEFINED,
+                    flags: ParameterFlags::new(),
+                };
+                pool.add_definition(Definition::param(PoolIndex::UNDEFINED, index, param))
+            })
+            .collect();
+        let fun = Function {
+            visibility: Visibility::Public,
+            flags: FunctionFlags::new(),
+            source: None,
+            return_type: has_return.then_some(PoolIndex::UNDEFINED),
+            unk1: false,
+            base_method: None,
+            parameters: parameters.clone(),
+            locals: vec![],
+            operator: None,
+            cast: 0,
+            code: Code::EMPTY,
+        };
+        pool.put_definition(
+            index,
+            Definition::function(PoolIndex::UNDEFINED, PoolIndex::UNDEFINED, fun),
+        );
+        (index, parameters)
+    }
+
+    fn set_code(pool: &mut ConstantPool, index: PoolIndex<Function>, code: Vec<Instr<Offset>>) {
+        pool.function_mut(index).unwrap().code = Code(code);
+    }
+
+    fn call_size(args: usize) -> i16 {
+        let invoke = Instr::<Offset>::InvokeStatic(Offset::new(0), 0, PoolIndex::UNDEFINED, 0).size();
+        let param = Instr::<Offset>::Param(PoolIndex::UNDEFINED).size();
+        (invoke + param * args as u16 + Instr::<Offset>::ParamEnd.size()) as i16
+    }
+}
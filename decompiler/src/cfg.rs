@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use redscript::ast::{Expr, SourceAst};
+use redscript::bytecode::Location;
+
+use crate::error::Error;
+
+/// A top-level statement of a function body, control flow instructions are kept apart from the rest.
+#[derive(Clone)]
+pub enum Stmt {
+    Expr(Expr<SourceAst>),
+    Jump(Location),
+    JumpIfFalse(Expr<SourceAst>, Location),
+    Switch(Expr<SourceAst>, Location),
+    SwitchLabel(Expr<SourceAst>, Location, Location),
+    SwitchDefault,
+    Return(Expr<SourceAst>),
+}
+
+pub enum Exit {
+    Next,
+    Jump(usize),
+    /// Jumps to the block when the condition is false and falls through otherwise.
+    Branch(Expr<SourceAst>, usize),
+    /// The `end` block starts the default case if there's one, otherwise it's where unmatched values go.
+    Switch {
+        subject: Expr<SourceAst>,
+        cases: Vec<(Expr<SourceAst>, usize)>,
+        end: usize,
+        default: bool,
+    },
+    Return(Expr<SourceAst>),
+    End,
+}
+
+pub struct Block {
+    pub stmts: Vec<Expr<SourceAst>>,
+    pub exit: Exit,
+}
+
+/// A basic block graph of a function body. The blocks are in the order of the code
+/// and the last one is an empty block that marks the end of the function.
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+    pub successors: Vec<Vec<usize>>,
+    idom: Vec<Option<usize>>,
+    ipdom: Vec<Option<usize>>,
+}
+
+impl Cfg {
+    pub fn new(stmts: Vec<(Location, Stmt)>, end: Location) -> Result<Self, Error> {
+        let mut positions: BTreeMap<Location, usize> = BTreeMap::new();
+        for (i, (location, _)) in stmts.iter().enumerate() {
+            positions.insert(*location, i);
+        }
+        positions.insert(end, stmts.len());
+        let stmt_at = |location: Location| {
+            positions
+                .get(&location)
+                .copied()
+                .ok_or_else(|| Error::DecompileError(format!("Jump to an invalid location {}", location.value)))
+        };
+
+        let mut leaders = BTreeSet::from([0, stmts.len()]);
+        let mut labels = BTreeMap::new();
+        let mut defaults = BTreeSet::new();
+        for (i, (_, stmt)) in stmts.iter().enumerate() {
+            match stmt {
+                Stmt::Expr(_) => continue,
+                Stmt::Jump(target) | Stmt::JumpIfFalse(_, target) | Stmt::Switch(_, target) => {
+                    leaders.insert(stmt_at(*target)?);
+                }
+                Stmt::SwitchLabel(_, next, body) => {
+                    leaders.extend([i, stmt_at(*next)?, stmt_at(*body)?]);
+                    labels.insert(i, (stmt_at(*next)?, stmt_at(*body)?));
+                }
+                Stmt::SwitchDefault => {
+                    leaders.insert(i);
+                    defaults.insert(i);
+                }
+                Stmt::Return(_) => {}
+            }
+            leaders.insert(i + 1);
+        }
+
+        let block_of: BTreeMap<usize, usize> = leaders.iter().enumerate().map(|(i, stmt)| (*stmt, i)).collect();
+        let mut blocks: Vec<Block> = Vec::with_capacity(block_of.len());
+
+        // a label is only ever reached by falling through from the previous case, so it's a jump to its body
+        let mut matchers = BTreeMap::new();
+        let stmts: Vec<Stmt> = stmts
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, stmt))| match stmt {
+                Stmt::SwitchLabel(matcher, _, body) => {
+                    matchers.insert(i, matcher);
+                    Stmt::Jump(body)
+                }
+                stmt => stmt,
+            })
+            .collect();
+
+        for (i, stmt) in stmts.into_iter().enumerate() {
+            if leaders.contains(&i) {
+                blocks.push(Block {
+                    stmts: vec![],
+                    exit: Exit::Next,
+                });
+            }
+            let block = blocks.last_mut().unwrap();
+            block.exit = match stmt {
+                Stmt::Expr(expr) => {
+                    block.stmts.push(expr);
+                    Exit::Next
+                }
+                Stmt::Jump(target) => Exit::Jump(block_of[&stmt_at(target)?]),
+                Stmt::JumpIfFalse(condition, target) => Exit::Branch(condition, block_of[&stmt_at(target)?]),
+                Stmt::Switch(subject, first) => {
+                    let mut cases = vec![];
+                    let mut label = stmt_at(first)?;
+                    while let Some((next, body)) = labels.get(&label) {
+                        let matcher = matchers
+                            .remove(&label)
+                            .ok_or_else(|| Error::DecompileError("Switch label used more than once".to_owned()))?;
+                        cases.push((matcher, block_of[body]));
+                        label = *next;
+                    }
+                    Exit::Switch {
+                        subject,
+                        cases,
+                        end: block_of[&label],
+                        default: defaults.contains(&label),
+                    }
+                }
+                Stmt::SwitchLabel(_, _, _) | Stmt::SwitchDefault => Exit::Next,
+                Stmt::Return(expr) => Exit::Return(expr),
+            };
+        }
+        blocks.push(Block {
+            stmts: vec![],
+            exit: Exit::End,
+        });
+
+        let successors: Vec<Vec<usize>> = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| match &block.exit {
+                Exit::Next => vec![i + 1],
+                Exit::Jump(target) => vec![*target],
+                Exit::Branch(_, target) => vec![i + 1, *target],
+                Exit::Switch { cases, end, .. } => cases.iter().map(|(_, body)| *body).chain([*end]).collect(),
+                Exit::Return(_) | Exit::End => vec![],
+            })
+            .collect();
+
+        // post-dominators are computed on the reversed graph with a virtual node that all exits lead to
+        let sink = blocks.len();
+        let mut reversed = vec![vec![]; sink + 1];
+        for (i, succs) in successors.iter().enumerate() {
+            for succ in succs {
+                reversed[*succ].push(i);
+            }
+            if succs.is_empty() {
+                reversed[sink].push(i);
+            }
+        }
+        let mut ipdom = dominators(sink, &reversed);
+        ipdom.pop();
+        for dom in &mut ipdom {
+            if *dom == Some(sink) {
+                *dom = None;
+            }
+        }
+
+        Ok(Cfg {
+            idom: dominators(0, &successors),
+            blocks,
+            successors,
+            ipdom,
+        })
+    }
+
+    pub fn end(&self) -> usize {
+        self.successors.len() - 1
+    }
+
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.idom[block].is_some()
+    }
+
+    pub fn dominates(&self, dominator: usize, mut block: usize) -> bool {
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match self.idom[block] {
+                Some(idom) if idom != block => block = idom,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Returns the closest block that every path from the block goes through, unless all of them return.
+    pub fn post_dominator(&self, block: usize) -> Option<usize> {
+        self.ipdom[block]
+    }
+
+    /// Finds the loops of the graph, each one is identified by its header and includes every block
+    /// that can reach one of the jumps back to the header without going through it.
+    pub fn loops(&self) -> BTreeMap<usize, BTreeSet<usize>> {
+        let mut predecessors = vec![vec![]; self.successors.len()];
+        for (i, succs) in self.successors.iter().enumerate() {
+            for succ in succs {
+                predecessors[*succ].push(i);
+            }
+        }
+
+        let mut loops: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for (latch, succs) in self.successors.iter().enumerate() {
+            for header in succs {
+                if !self.is_reachable(latch) || !self.dominates(*header, latch) {
+                    continue;
+                }
+                let body = loops.entry(*header).or_insert_with(|| BTreeSet::from([*header]));
+                let mut stack = vec![latch];
+                while let Some(block) = stack.pop() {
+                    if body.insert(block) {
+                        stack.extend(predecessors[block].iter().filter(|pred| self.is_reachable(**pred)));
+                    }
+                }
+            }
+        }
+        loops
+    }
+}
+
+/// Computes the immediate dominators of a graph with the algorithm by Cooper, Harvey and Kennedy,
+/// nodes that can't be reached from the entry have none.
+fn dominators(entry: usize, successors: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let mut order = vec![];
+    let mut visited = vec![false; successors.len()];
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some((node, i)) = stack.pop() {
+        if let Some(succ) = successors[node].get(i) {
+            stack.push((node, i + 1));
+            if !visited[*succ] {
+                visited[*succ] = true;
+                stack.push((*succ, 0));
+            }
+        } else {
+            order.push(node);
+        }
+    }
+    order.reverse();
+
+    let mut rank = vec![usize::MAX; successors.len()];
+    for (i, node) in order.iter().enumerate() {
+        rank[*node] = i;
+    }
+    let mut predecessors = vec![vec![]; successors.len()];
+    for (node, succs) in successors.iter().enumerate() {
+        for succ in succs {
+            predecessors[*succ].push(node);
+        }
+    }
+
+    let mut idom = vec![None; successors.len()];
+    idom[entry] = Some(entry);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in order.iter().skip(1) {
+            let mut new_idom = None;
+            for pred in &predecessors[*node] {
+                if idom[*pred].is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => *pred,
+                    Some(mut other) => {
+                        let mut pred = *pred;
+                        while pred != other {
+                            while rank[pred] > rank[other] {
+                                pred = idom[pred].unwrap();
+                            }
+                            while rank[other] > rank[pred] {
+                                other = idom[other].unwrap();
+                            }
+                        }
+                        pred
+                    }
+                });
+            }
+            if new_idom != idom[*node] {
+                idom[*node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
@@ -3,19 +3,22 @@ use std::iter;
 use std::path::Path;
 
 use itertools::Itertools;
-use redscript::bundle::{ConstantPool, PoolIndex};
+use redscript::bundle::{ConstantPool, PoolError, PoolIndex};
 use redscript::definition::{AnyDefinition, Definition};
 
 pub struct FileIndex<'a> {
     file_map: HashMap<PoolIndex<Definition>, HashSet<PoolIndex<Definition>>>,
+    orphans: Vec<&'a Definition>,
     pool: &'a ConstantPool,
 }
 
 impl<'a> FileIndex<'a> {
-    pub fn from_pool(pool: &'a ConstantPool) -> FileIndex {
+    pub fn from_pool(pool: &'a ConstantPool) -> Result<FileIndex, PoolError> {
         let mut file_map: HashMap<PoolIndex<Definition>, HashSet<PoolIndex<Definition>>> = HashMap::new();
+        let mut orphans = vec![];
 
-        for (idx, def) in pool.definitions() {
+        for entry in pool.definitions() {
+            let (idx, def) = entry?;
             if let Some(source) = def.source() {
                 let root_idx = if def.parent.is_undefined() { idx } else { def.parent };
                 file_map
@@ -25,9 +28,16 @@ impl<'a> FileIndex<'a> {
                     })
                     .or_insert_with(|| iter::once(root_idx).collect());
             }
+            if Self::is_orphan(def, pool) {
+                orphans.push(def);
+            }
         }
 
-        FileIndex { file_map, pool }
+        Ok(FileIndex {
+            file_map,
+            orphans,
+            pool,
+        })
     }
 
     pub fn iter(&'a self) -> impl Iterator<Item = FileEntry<'a>> {
@@ -53,24 +63,22 @@ impl<'a> FileIndex<'a> {
     }
 
     fn orphans(&'a self) -> FileEntry<'a> {
-        let definitions = self
-            .pool
-            .definitions()
-            .filter(|(_, def)| match &def.value {
-                AnyDefinition::Class(class) => class
-                    .functions
-                    .iter()
-                    .filter_map(|idx| self.pool.function(*idx).ok())
-                    .all(|fun| fun.flags.is_native()),
-                AnyDefinition::Enum(_) => true,
-                AnyDefinition::Function(fun) if def.parent == PoolIndex::UNDEFINED && fun.flags.is_native() => true,
-                _ => false,
-            })
-            .map(|(_, def)| def)
-            .collect();
         FileEntry {
             path: Path::new("orphans.script"),
-            definitions,
+            definitions: self.orphans.clone(),
+        }
+    }
+
+    fn is_orphan(def: &Definition, pool: &ConstantPool) -> bool {
+        match &def.value {
+            AnyDefinition::Class(class) => class
+                .functions
+                .iter()
+                .filter_map(|idx| pool.function(*idx).ok())
+                .all(|fun| fun.flags.is_native()),
+            AnyDefinition::Enum(_) | AnyDefinition::BitField(_) => true,
+            AnyDefinition::Function(fun) if def.parent == PoolIndex::UNDEFINED && fun.flags.is_native() => true,
+            _ => false,
         }
     }
 }
@@ -1,16 +1,20 @@
-use std::collections::BTreeMap;
-use std::io;
+use std::collections::{BTreeMap, HashSet};
 
+use cfg::{Cfg, Stmt};
 use error::Error;
-use redscript::ast::{Constant, Expr, Ident, Literal, Seq, SourceAst, Span, SwitchCase, Target, TypeName};
+use redscript::ast::{Constant, Expr, Ident, Literal, Seq, SourceAst, Span, TypeName};
 use redscript::bundle::{ConstantPool, PoolIndex};
 use redscript::bytecode::{CodeCursor, Instr, IntrinsicOp, Location, Offset};
 use redscript::definition::Function;
 use redscript::Ref;
 
+mod cfg;
 pub mod error;
 pub mod files;
 pub mod print;
+mod recompilable;
+mod structure;
+mod sugar;
 
 pub struct Decompiler<'a> {
     code: CodeCursor<'a, Offset>,
@@ -40,13 +44,43 @@ impl<'a> Decompiler<'a> {
             locals.insert(name, type_);
         }
 
+        let mut names: HashSet<_> = locals.keys().cloned().collect();
+        for param in &function.parameters {
+            names.insert(Ident::Owned(pool.def_name(*param)?));
+        }
+
         let mut decompiler = Decompiler::new(function.code.cursor(), function.base_method, pool);
-        let body = decompiler.decompile()?;
+        let body = sugar::resugar(decompiler.decompile(&names)?, &mut locals);
         merge_declarations(locals, body)
     }
 
-    pub fn decompile(&mut self) -> Result<Seq<SourceAst>, Error> {
-        self.consume_path(Location::MAX)
+    /// Decompiles a function into code that can be compiled again.
+    pub fn recompilable(function: &Function, pool: &'a ConstantPool) -> Result<Seq<SourceAst>, Error> {
+        let params = function
+            .parameters
+            .iter()
+            .map(|param| Ok(Ident::Owned(pool.def_name(*param)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(recompilable::recompilable(Self::decompiled(function, pool)?, &params))
+    }
+
+    /// Decompiles the code, `names` are the locals and parameters in scope that new variables must not reuse.
+    pub fn decompile(&mut self, names: &HashSet<Ident>) -> Result<Seq<SourceAst>, Error> {
+        let stmts = self.consume_stmts()?;
+        let end = self.code.pos();
+        if let Ok(body) = structure::structure(Cfg::new(stmts.clone(), end)?) {
+            return Ok(body);
+        }
+        Ok(structure::dispatch(Cfg::new(stmts, end)?, names))
+    }
+
+    fn consume_stmts(&mut self) -> Result<Vec<(Location, Stmt)>, Error> {
+        let mut stmts = vec![];
+        while self.code.peek().is_some() {
+            let position = self.code.pos();
+            stmts.push((position, self.consume_stmt()?));
+        }
+        Ok(stmts)
     }
 
     fn definition_ident<A>(&self, index: PoolIndex<A>) -> Result<Ident, Error> {
@@ -61,24 +95,6 @@ impl<'a> Decompiler<'a> {
         Ok(body)
     }
 
-    fn consume_path(&mut self, target: Location) -> Result<Seq<SourceAst>, Error> {
-        let mut body = Vec::new();
-        loop {
-            if self.code.pos() >= target
-                || matches!(body.last(), Some(Expr::Goto(_, _)))
-                || matches!(body.last(), Some(Expr::Return(_, _)))
-            {
-                break;
-            }
-            match self.consume() {
-                Ok(expr) => body.push(expr),
-                Err(Error::IoError(err)) if err.kind() == io::ErrorKind::UnexpectedEof => break,
-                Err(err) => return Err(err),
-            }
-        }
-        Ok(Seq::new(body))
-    }
-
     fn consume_intrisnic_typed(&mut self, op: IntrinsicOp, type_args: Vec<TypeName>) -> Result<Expr<SourceAst>, Error> {
         let params = self.consume_n(op.arg_count() as usize)?;
         Ok(Expr::Call(Ident::Static(op.into()), type_args, params, Span::ZERO))
@@ -108,62 +124,35 @@ impl<'a> Decompiler<'a> {
         Ok(params)
     }
 
-    fn consume_conditional_jump(&mut self, position: Location, offset: Offset) -> Result<Expr<SourceAst>, Error> {
-        let condition = self.consume()?;
-        let target = offset.absolute(position);
-        let mut body = self.consume_path(target)?;
-        self.code.set_pos(target)?;
-
-        let result = if resolve_jump(&mut body, Some(position)).is_some() {
-            Expr::While(Box::new(condition), body, Span::ZERO)
-        } else if let Some(jump) = resolve_jump(&mut body, None) {
-            let else_case = self.consume_path(Location::new(jump.position))?;
-            Expr::If(Box::new(condition), body, Some(else_case), Span::ZERO)
-        } else {
-            Expr::If(Box::new(condition), body, None, Span::ZERO)
-        };
-        Ok(result)
-    }
-
-    fn consume_switch(&mut self, start: Location) -> Result<Expr<SourceAst>, Error> {
-        let subject = self.consume()?;
-
-        self.code.set_pos(start)?;
-        let mut labels = Vec::new();
-        while let Some(Instr::SwitchLabel(exit_offset, start_offset)) = self.code.peek() {
-            let position = self.code.pos();
-            labels.push((position, start_offset.absolute(position)));
-            self.code.seek(exit_offset)?;
-        }
-        if let Some(Instr::SwitchDefault) = self.code.peek() {
-            labels.push((self.code.pos(), self.code.pos()));
-        };
-        labels.sort_by_key(|(_, start)| *start);
-
-        let mut default = None;
-        let mut cases = Vec::new();
-        for (label, start_position) in labels {
-            self.code.set_pos(label)?;
-
-            match self.code.pop()? {
-                Instr::SwitchLabel(exit_offset, _) => {
-                    let exit = exit_offset.absolute(label);
-                    let matcher = self.consume()?;
-
-                    self.code.set_pos(start_position)?;
-                    let mut body = self.consume_path(exit)?;
-                    if let Some(Expr::Goto(_, _)) = body.exprs.last() {
-                        body.exprs.pop();
-                        body.exprs.push(Expr::Break(Span::ZERO));
-                    }
-                    cases.push(SwitchCase { matcher, body });
-                }
-                Instr::SwitchDefault => default = Some(Seq::new(vec![self.consume()?])),
-                _ => return Err(Error::DecompileError("Unexpected switch label instruction".to_owned())),
+    fn consume_stmt(&mut self) -> Result<Stmt, Error> {
+        let position = self.code.pos();
+        let stmt = match self.code.peek() {
+            Some(Instr::Jump(offset) | Instr::Skip(offset)) => {
+                self.code.pop()?;
+                Stmt::Jump(offset.absolute(position))
             }
-        }
-
-        Ok(Expr::Switch(Box::new(subject), cases, default, Span::ZERO))
+            Some(Instr::JumpIfFalse(offset)) => {
+                self.code.pop()?;
+                Stmt::JumpIfFalse(self.consume()?, offset.absolute(position))
+            }
+            Some(Instr::Switch(_, offset)) => {
+                self.code.pop()?;
+                Stmt::Switch(self.consume()?, offset.absolute(position))
+            }
+            Some(Instr::SwitchLabel(next, body)) => {
+                self.code.pop()?;
+                Stmt::SwitchLabel(self.consume()?, next.absolute(position), body.absolute(position))
+            }
+            Some(Instr::SwitchDefault) => {
+                self.code.pop()?;
+                Stmt::SwitchDefault
+            }
+            _ => match self.consume()? {
+                expr @ Expr::Return(_, _) => Stmt::Return(expr),
+                expr => Stmt::Expr(expr),
+            },
+        };
+        Ok(stmt)
     }
 
     fn consume(&mut self) -> Result<Expr<SourceAst>, Error> {
@@ -171,7 +160,6 @@ impl<'a> Decompiler<'a> {
     }
 
     fn consume_with(&mut self, context: Option<Expr<SourceAst>>) -> Result<Expr<SourceAst>, Error> {
-        let position = self.code.pos();
         let res = match self.code.pop()? {
             Instr::Nop => Expr::EMPTY,
             Instr::Null => Expr::Null(Span::ZERO),
@@ -240,16 +228,12 @@ impl<'a> Decompiler<'a> {
                 }
             }
             Instr::ExternalVar => return Err(Error::DecompileError("Unexpected ExternalVar".to_owned())),
-            Instr::Switch(_, start) => self.consume_switch(start.absolute(position))?,
+            Instr::Switch(_, _) => return Err(Error::DecompileError("Unexpected Switch".to_owned())),
             Instr::SwitchLabel(_, _) => return Err(Error::DecompileError("Unexpected SwitchLabel".to_owned())),
             Instr::SwitchDefault => return Err(Error::DecompileError("Unexpected SwitchDefault".to_owned())),
-            Instr::Jump(Offset { value: 3 }) => Expr::EMPTY,
-            Instr::Jump(offset) => Expr::Goto(Target::new(offset.absolute(position).value), Span::ZERO),
-            Instr::JumpIfFalse(offset) => {
-                assert!(offset.value >= 0, "negative offset is not supported for JumpIfFalse");
-                self.consume_conditional_jump(position, offset)?
+            Instr::Jump(_) | Instr::JumpIfFalse(_) | Instr::Skip(_) => {
+                return Err(Error::DecompileError("Unexpected jump".to_owned()))
             }
-            Instr::Skip(offset) => Expr::Goto(Target::new(offset.absolute(position).value), Span::ZERO),
             Instr::Conditional(_, _) => {
                 let expr = self.consume()?;
                 let true_case = self.consume()?;
@@ -432,15 +416,3 @@ fn merge_declarations(mut locals: BTreeMap<Ident, TypeName>, seq: Seq<SourceAst>
     body.extend(it);
     Ok(Seq::new(body))
 }
-
-fn resolve_jump(seq: &mut Seq<SourceAst>, target: Option<Location>) -> Option<&mut Target> {
-    seq.exprs.iter_mut().rev().find_map(|expr| match expr {
-        Expr::Goto(goto, _) if !goto.resolved && target.map(|target| goto.position == target.value).unwrap_or(true) => {
-            goto.resolved = true;
-            Some(goto)
-        }
-        Expr::If(_, if_, None, _) => resolve_jump(if_, target),
-        Expr::If(_, if_, Some(else_), _) => resolve_jump(if_, target).or_else(move || resolve_jump(else_, target)),
-        _ => None,
-    })
-}
@@ -15,10 +15,17 @@ const INDENT: &str = "  ";
 #[derive(Debug, Clone, Copy)]
 pub enum OutputMode {
     Code { verbose: bool },
+    Recompilable,
     SyntaxTree,
     Bytecode,
 }
 
+impl OutputMode {
+    fn is_verbose(self) -> bool {
+        matches!(self, OutputMode::Code { verbose: true })
+    }
+}
+
 pub fn write_definition<W: Write>(
     out: &mut W,
     definition: &Definition,
@@ -39,7 +46,7 @@ pub fn write_definition<W: Write>(
             if class.flags.is_final() {
                 write!(out, "final ")?;
             }
-            if class.flags.is_import_only() {
+            if class.flags.is_import_only() || class.flags.is_native() && matches!(mode, OutputMode::Recompilable) {
                 write!(out, "importonly ")?;
             } else if class.flags.is_native() {
                 write!(out, "native ")?;
@@ -86,6 +93,18 @@ pub fn write_definition<W: Write>(
 
             writeln!(out, "}}")?
         }
+        AnyDefinition::BitField(bit_field) => {
+            // there's no syntax for bit fields, so they're written as enums of bit positions
+            writeln!(out)?;
+            writeln!(out, "// bitfield")?;
+            writeln!(out, "enum {} {{", pool.names.get(definition.name)?)?;
+
+            for member in &bit_field.members {
+                write_definition(out, pool.definition(*member)?, pool, depth + 1, mode)?;
+            }
+
+            writeln!(out, "}}")?
+        }
         AnyDefinition::Function(fun) => {
             let return_type = fun
                 .return_type
@@ -101,6 +120,14 @@ pub fn write_definition<W: Write>(
                 .map(|param| format_param(pool.definition(*param).unwrap(), pool).unwrap())
                 .format(", ");
 
+            // the body is written to a buffer first, so that a failure doesn't leave a dangling header
+            let mut body = Vec::new();
+            if fun.flags.has_body() {
+                write_function_body(&mut body, fun, pool, depth, mode)?;
+            } else {
+                write!(body, ";")?;
+            }
+
             writeln!(out)?;
             write!(out, "{}{} ", padding, fun.visibility)?;
             if fun.flags.is_final() {
@@ -126,11 +153,7 @@ pub fn write_definition<W: Write>(
             }
             write!(out, "func {}({}) -> {}", pretty_name, params, return_type)?;
 
-            if fun.flags.has_body() {
-                write_function_body(out, fun, pool, depth, mode)?;
-            } else {
-                write!(out, ";")?;
-            }
+            out.write_all(&body)?;
             writeln!(out)?;
         }
         AnyDefinition::Parameter(_) => write!(out, "{}", format_param(definition, pool)?)?,
@@ -149,24 +172,38 @@ pub fn write_definition<W: Write>(
             let type_name = format_type(pool.definition(field.type_)?, pool)?;
             let field_name = pool.names.get(definition.name)?;
 
+            // there's no syntax for attributes and some of the qualifiers, so they're kept as comments
+            let (comment, open, close) = match mode {
+                OutputMode::Recompilable => ("// ", "/* ", " */"),
+                _ => ("", "", ""),
+            };
+
             writeln!(out)?;
             for property in &field.attributes {
-                writeln!(out, "{}@attrib({}, \"{}\")", padding, property.name, property.value)?;
+                writeln!(
+                    out,
+                    "{}{}@attrib({}, \"{}\")",
+                    padding, comment, property.name, property.value
+                )?;
             }
 
             for property in &field.defaults {
-                writeln!(out, "{}@default({}, {})", padding, property.name, property.value)?;
+                writeln!(
+                    out,
+                    "{}{}@default({}, {})",
+                    padding, comment, property.name, property.value
+                )?;
             }
 
             write!(out, "{}{} ", padding, field.visibility)?;
             if field.flags.is_inline() {
-                write!(out, "inline ")?;
+                write!(out, "{}inline{} ", open, close)?;
             }
             if field.flags.is_replicated() {
-                write!(out, "replicated ")?;
+                write!(out, "{}replicated{} ", open, close)?;
             }
             if field.flags.is_editable() {
-                write!(out, "edit ")?;
+                write!(out, "{}edit{} ", open, close)?;
             }
             if field.flags.is_native() {
                 write!(out, "native ")?;
@@ -193,9 +230,13 @@ fn write_function_body<W: Write>(
 ) -> Result<(), Error> {
     writeln!(out, " {{")?;
     match mode {
-        OutputMode::Code { verbose } => {
+        OutputMode::Code { .. } => {
             let code = Decompiler::decompiled(fun, pool)?;
-            write_seq(out, &code, verbose, depth + 1)?;
+            write_seq(out, &code, mode, depth + 1)?;
+        }
+        OutputMode::Recompilable => {
+            let code = Decompiler::recompilable(fun, pool)?;
+            write_seq(out, &code, mode, depth + 1)?;
         }
         OutputMode::SyntaxTree => {
             let code = Decompiler::decompiled(fun, pool)?;
@@ -219,24 +260,24 @@ fn write_function_body<W: Write>(
     Ok(())
 }
 
-fn write_seq<W: Write>(out: &mut W, code: &Seq<SourceAst>, verbose: bool, depth: usize) -> Result<(), Error> {
+fn write_seq<W: Write>(out: &mut W, code: &Seq<SourceAst>, mode: OutputMode, depth: usize) -> Result<(), Error> {
     for expr in code.exprs.iter().filter(|expr| !expr.is_empty()) {
         write!(out, "{}", INDENT.repeat(depth))?;
-        write_expr(out, expr, verbose, depth)?;
+        write_expr(out, expr, mode, depth)?;
         writeln!(out, ";")?;
     }
     Ok(())
 }
 
-fn write_expr<W: Write>(out: &mut W, expr: &Expr<SourceAst>, verbose: bool, depth: usize) -> Result<(), Error> {
-    write_expr_nested(out, expr, None, verbose, depth)
+fn write_expr<W: Write>(out: &mut W, expr: &Expr<SourceAst>, mode: OutputMode, depth: usize) -> Result<(), Error> {
+    write_expr_nested(out, expr, None, mode, depth)
 }
 
 fn write_expr_nested<W: Write>(
     out: &mut W,
     expr: &Expr<SourceAst>,
     parent_op: Option<ParentOp>,
-    verbose: bool,
+    mode: OutputMode,
     depth: usize,
 ) -> Result<(), Error> {
     let padding = INDENT.repeat(depth);
@@ -251,7 +292,9 @@ fn write_expr_nested<W: Write>(
             Constant::I32(lit) => write!(out, "{}", lit)?,
             Constant::I64(lit) => write!(out, "{}l", lit)?,
             Constant::U32(lit) => write!(out, "{}u", lit)?,
-            Constant::U64(lit) => write!(out, "{}u", lit)?,
+            Constant::U64(lit) => write!(out, "{}ul", lit)?,
+            Constant::F32(lit) if matches!(mode, OutputMode::Recompilable) => write!(out, "{}", exact_float(*lit))?,
+            Constant::F64(lit) if matches!(mode, OutputMode::Recompilable) => write!(out, "{}d", exact_float(*lit))?,
             Constant::F32(lit) => write!(out, "{:.2}", lit)?,
             Constant::F64(lit) => write!(out, "{:.2}d", lit)?,
             Constant::Bool(true) => write!(out, "true")?,
@@ -260,11 +303,11 @@ fn write_expr_nested<W: Write>(
         Expr::Cast(type_, expr, _) => {
             if parent_op.is_some() {
                 write!(out, "(")?;
-                write_expr(out, expr, verbose, 0)?;
+                write_expr(out, expr, mode, 0)?;
                 write!(out, " as {}", type_.pretty())?;
                 write!(out, ")")?;
             } else {
-                write_expr(out, expr, verbose, 0)?;
+                write_expr(out, expr, mode, 0)?;
                 write!(out, " as {}", type_.pretty())?;
             }
         }
@@ -275,56 +318,56 @@ fn write_expr_nested<W: Write>(
             }
             if let Some(val) = val {
                 write!(out, " = ")?;
-                write_expr(out, val, verbose, 0)?;
+                write_expr(out, val, mode, 0)?;
             }
         }
         Expr::Assign(lhs, rhs, _) => {
-            write_expr(out, lhs, verbose, 0)?;
+            write_expr(out, lhs, mode, 0)?;
             write!(out, " = ")?;
-            write_expr(out, rhs, verbose, 0)?
+            write_expr(out, rhs, mode, 0)?
         }
-        Expr::Call(fun, type_args, params, _) => write_call(out, fun, type_args, params, parent_op, verbose)?,
+        Expr::Call(fun, type_args, params, _) => write_call(out, fun, type_args, params, parent_op, mode)?,
         Expr::MethodCall(obj, fun, params, _) => {
-            write_expr_nested(out, obj, Some(ParentOp::Dot), verbose, 0)?;
+            write_expr_nested(out, obj, Some(ParentOp::Dot), mode, 0)?;
             write!(out, ".")?;
-            write_call(out, fun, &[], params, None, verbose)?
+            write_call(out, fun, &[], params, None, mode)?
         }
         Expr::ArrayElem(arr, idx, _) => {
-            write_expr(out, arr, verbose, 0)?;
+            write_expr(out, arr, mode, 0)?;
             write!(out, "[")?;
-            write_expr(out, idx, verbose, 0)?;
+            write_expr(out, idx, mode, 0)?;
             write!(out, "]")?;
         }
         Expr::New(ident, params, _) => {
             write!(out, "new {}(", ident)?;
             if !params.is_empty() {
                 for param in params.iter().take(params.len() - 1) {
-                    write_expr(out, param, verbose, depth)?;
+                    write_expr(out, param, mode, depth)?;
                     write!(out, ", ")?;
                 }
-                write_expr(out, params.last().unwrap(), verbose, depth)?;
+                write_expr(out, params.last().unwrap(), mode, depth)?;
             }
             write!(out, ")")?
         }
         Expr::Return(Some(expr), _) => {
             write!(out, "return ")?;
-            write_expr(out, expr, verbose, depth)?
+            write_expr(out, expr, mode, depth)?
         }
         Expr::Return(None, _) => write!(out, "return")?,
-        Expr::Seq(exprs) => write_seq(out, exprs, verbose, depth)?,
+        Expr::Seq(exprs) => write_seq(out, exprs, mode, depth)?,
         Expr::Switch(expr, cases, default, _) => {
             write!(out, "switch ")?;
-            write_expr(out, expr, verbose, 0)?;
+            write_expr(out, expr, mode, 0)?;
             writeln!(out, " {{")?;
             for SwitchCase { matcher, body } in cases {
                 write!(out, "{}  case ", padding)?;
-                write_expr(out, matcher, verbose, 0)?;
+                write_expr(out, matcher, mode, 0)?;
                 writeln!(out, ":")?;
-                write_seq(out, body, verbose, depth + 2)?;
+                write_seq(out, body, mode, depth + 2)?;
             }
             if let Some(default_body) = default {
                 writeln!(out, "{}  default:", padding)?;
-                write_seq(out, default_body, verbose, depth + 2)?;
+                write_seq(out, default_body, mode, depth + 2)?;
             }
             write!(out, "{}}}", padding)?
         }
@@ -332,47 +375,81 @@ fn write_expr_nested<W: Write>(
         Expr::Goto(_, _) => (),
         Expr::If(condition, true_, false_, _) => {
             write!(out, "if ")?;
-            write_expr(out, condition, verbose, 0)?;
+            write_expr(out, condition, mode, 0)?;
             writeln!(out, " {{")?;
-            write_seq(out, true_, verbose, depth + 1)?;
+            write_seq(out, true_, mode, depth + 1)?;
             write!(out, "{}}}", padding)?;
             if let Some(branch) = false_ {
-                writeln!(out, " else {{")?;
-                write_seq(out, branch, verbose, depth + 1)?;
-                write!(out, "{}}}", padding)?
+                let mut exprs = branch.exprs.iter().filter(|expr| !expr.is_empty());
+                match (exprs.next(), exprs.next()) {
+                    (Some(nested @ Expr::If(_, _, _, _)), None) => {
+                        write!(out, " else ")?;
+                        write_expr(out, nested, mode, depth)?
+                    }
+                    _ => {
+                        writeln!(out, " else {{")?;
+                        write_seq(out, branch, mode, depth + 1)?;
+                        write!(out, "{}}}", padding)?
+                    }
+                }
             }
         }
         Expr::Conditional(condition, true_, false_, _) => {
-            write_expr(out, condition, verbose, 0)?;
+            write_expr(out, condition, mode, 0)?;
             write!(out, " ? ")?;
-            write_expr(out, true_, verbose, 0)?;
+            write_expr(out, true_, mode, 0)?;
             write!(out, " : ")?;
-            write_expr(out, false_, verbose, 0)?;
+            write_expr(out, false_, mode, 0)?;
         }
         Expr::While(condition, body, _) => {
             write!(out, "while ")?;
-            write_expr(out, condition, verbose, 0)?;
+            write_expr(out, condition, mode, 0)?;
+            writeln!(out, " {{")?;
+            write_seq(out, body, mode, depth + 1)?;
+            write!(out, "{}}}", padding)?;
+        }
+        Expr::ForIn(name, array, body, _) => {
+            write!(out, "for {} in ", name)?;
+            write_expr(out, array, mode, 0)?;
             writeln!(out, " {{")?;
-            write_seq(out, body, verbose, depth + 1)?;
+            write_seq(out, body, mode, depth + 1)?;
             write!(out, "{}}}", padding)?;
         }
         Expr::Member(expr, accessor, _) => {
-            write_expr(out, expr, verbose, 0)?;
+            write_expr(out, expr, mode, 0)?;
             write!(out, ".{}", accessor)?;
         }
         Expr::BinOp(lhs, rhs, op, _) => {
-            write_binop(out, lhs, rhs, *op, verbose)?;
+            write_binop(out, lhs, rhs, *op, mode)?;
         }
         Expr::UnOp(val, op, _) => {
-            write_unop(out, val, *op, verbose)?;
+            write_unop(out, val, *op, mode)?;
         }
         Expr::Break(_) => write!(out, "break")?,
+        Expr::Continue(_) => write!(out, "continue")?,
         Expr::Null(_) => write!(out, "null")?,
         Expr::This(_) => write!(out, "this")?,
         Expr::Super(_) => write!(out, "super")?,
-        Expr::ArrayLit(_, _, _) => panic!("Shouldn't get here"),
-        Expr::InterpolatedString(_, _, _) => panic!("Shouldn't get here"),
-        Expr::ForIn(_, _, _, _) => panic!("Shouldn't get here"),
+        Expr::ArrayLit(exprs, _, _) => {
+            write!(out, "[")?;
+            if !exprs.is_empty() {
+                for expr in exprs.iter().take(exprs.len() - 1) {
+                    write_expr(out, expr, mode, 0)?;
+                    write!(out, ", ")?;
+                }
+                write_expr(out, exprs.last().unwrap(), mode, 0)?;
+            }
+            write!(out, "]")?
+        }
+        Expr::InterpolatedString(prefix, parts, _) => {
+            write!(out, "s\"{}", str::escape_default(prefix))?;
+            for (part, str) in parts {
+                write!(out, "\\(")?;
+                write_expr(out, part, mode, 0)?;
+                write!(out, "){}", str::escape_default(str))?;
+            }
+            write!(out, "\"")?
+        }
     };
     Ok(())
 }
@@ -383,7 +460,7 @@ fn write_call<W: Write>(
     type_params: &[TypeName],
     params: &[Expr<SourceAst>],
     parent_op: Option<ParentOp>,
-    verbose: bool,
+    mode: OutputMode,
 ) -> Result<(), Error> {
     let extracted = name.as_ref().split(';').next().expect("Empty function name");
     let fun_name = if extracted.is_empty() { "undefined" } else { extracted };
@@ -398,16 +475,16 @@ fn write_call<W: Write>(
             .is_some()
         {
             write!(out, "(")?;
-            write_binop(out, &params[0], &params[1], binop, verbose)?;
+            write_binop(out, &params[0], &params[1], binop, mode)?;
             write!(out, ")")?;
             Ok(())
         } else {
-            write_binop(out, &params[0], &params[1], binop, verbose)
+            write_binop(out, &params[0], &params[1], binop, mode)
         }
     } else if let Ok(unop) = UnOp::from_str(fun_name) {
-        write_unop(out, &params[0], unop, verbose)
-    } else if (fun_name == "WeakRefToRef" || fun_name == "RefToWeakRef" || fun_name == "AsRef") && !verbose {
-        write_expr(out, &params[0], verbose, 0)
+        write_unop(out, &params[0], unop, mode)
+    } else if (fun_name == "WeakRefToRef" || fun_name == "RefToWeakRef" || fun_name == "AsRef") && !mode.is_verbose() {
+        write_expr(out, &params[0], mode, 0)
     } else {
         write!(out, "{}", fun_name)?;
         if !type_params.is_empty() {
@@ -416,10 +493,10 @@ fn write_call<W: Write>(
         write!(out, "(")?;
         if !params.is_empty() {
             for param in params.iter().take(params.len() - 1) {
-                write_expr(out, param, verbose, 0)?;
+                write_expr(out, param, mode, 0)?;
                 write!(out, ", ")?;
             }
-            write_expr(out, params.last().unwrap(), verbose, 0)?;
+            write_expr(out, params.last().unwrap(), mode, 0)?;
         }
         write!(out, ")")?;
         Ok(())
@@ -431,19 +508,29 @@ fn write_binop<W: Write>(
     lhs: &Expr<SourceAst>,
     rhs: &Expr<SourceAst>,
     op: BinOp,
-    verbose: bool,
+    mode: OutputMode,
 ) -> Result<(), Error> {
-    write_expr_nested(out, lhs, Some(ParentOp::BinOp(op)), verbose, 0)?;
-    write!(out, " {} ", format_binop(op))?;
-    write_expr_nested(out, rhs, Some(ParentOp::BinOp(op)), verbose, 0)
+    write_expr_nested(out, lhs, Some(ParentOp::BinOp(op)), mode, 0)?;
+    write!(out, " {} ", op.symbol())?;
+    write_expr_nested(out, rhs, Some(ParentOp::BinOp(op)), mode, 0)
+}
+
+fn write_unop<W: Write>(out: &mut W, param: &Expr<SourceAst>, op: UnOp, mode: OutputMode) -> Result<(), Error> {
+    write!(out, "{}", op.symbol())?;
+    write_expr_nested(out, param, Some(ParentOp::UnOp(op)), mode, 0)
 }
 
-fn write_unop<W: Write>(out: &mut W, param: &Expr<SourceAst>, op: UnOp, verbose: bool) -> Result<(), Error> {
-    write!(out, "{}", format_unop(op))?;
-    write_expr_nested(out, param, Some(ParentOp::UnOp(op)), verbose, 0)
+/// Formats a float without rounding it, in a form that's always parsed as a float.
+fn exact_float<F: ToString>(value: F) -> String {
+    let str = value.to_string();
+    if str.contains('.') {
+        str
+    } else {
+        format!("{}.0", str)
+    }
 }
 
-fn format_param(def: &Definition, pool: &ConstantPool) -> Result<String, Error> {
+pub fn format_param(def: &Definition, pool: &ConstantPool) -> Result<String, Error> {
     let param = def.value.as_parameter().expect("Expected a param definition");
     let type_name = format_type(pool.definition(param.type_)?, pool)?;
     let name = pool.names.get(def.name)?;
@@ -453,7 +540,7 @@ fn format_param(def: &Definition, pool: &ConstantPool) -> Result<String, Error>
     Ok(format!("{}{}{}{}: {}", const_, out, optional, name, type_name))
 }
 
-fn format_type(def: &Definition, pool: &ConstantPool) -> Result<String, Error> {
+pub fn format_type(def: &Definition, pool: &ConstantPool) -> Result<String, Error> {
     let type_ = def.value.as_type().expect("Expected a type definition");
     let result = match type_ {
         Type::Prim => pool.names.get(def.name)?.to_string(),
@@ -469,41 +556,6 @@ fn format_type(def: &Definition, pool: &ConstantPool) -> Result<String, Error> {
     Ok(result)
 }
 
-fn format_binop(op: BinOp) -> &'static str {
-    match op {
-        BinOp::AssignAdd => "+=",
-        BinOp::AssignSubtract => "-=",
-        BinOp::AssignMultiply => "*=",
-        BinOp::AssignDivide => "/=",
-        BinOp::AssignOr => "|=",
-        BinOp::AssignAnd => "&=",
-        BinOp::LogicOr => "||",
-        BinOp::LogicAnd => "&&",
-        BinOp::Or => "|",
-        BinOp::Xor => "^",
-        BinOp::Equal => "==",
-        BinOp::NotEqual => "!=",
-        BinOp::And => "&",
-        BinOp::Less => "<",
-        BinOp::LessEqual => "<=",
-        BinOp::Greater => ">",
-        BinOp::GreaterEqual => ">=",
-        BinOp::Add => "+",
-        BinOp::Subtract => "-",
-        BinOp::Multiply => "*",
-        BinOp::Divide => "/",
-        BinOp::Modulo => "%",
-    }
-}
-
-fn format_unop(op: UnOp) -> &'static str {
-    match op {
-        UnOp::BitNot => "~",
-        UnOp::LogicNot => "!",
-        UnOp::Neg => "-",
-    }
-}
-
 enum ParentOp {
     UnOp(UnOp),
     BinOp(BinOp),
@@ -0,0 +1,98 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use redscript::ast::{Constant, Expr, Ident, Seq, SourceAst};
+use redscript::bytecode::IntrinsicOp;
+
+use crate::sugar::map_children;
+
+/// Rewrites a decompiled function body into code that the compiler accepts.
+pub fn recompilable(body: Seq<SourceAst>, params: &[Ident]) -> Seq<SourceAst> {
+    let mut names = Names::default();
+    let body = names.on_seq(body);
+    let renames = local_names(names.names, params);
+
+    let exprs = body.exprs.into_iter().map(|expr| rewrite(expr, &renames)).collect();
+    Seq::new(exprs)
+}
+
+#[derive(Default)]
+struct Names {
+    names: BTreeSet<Ident>,
+}
+
+impl Names {
+    fn on_seq(&mut self, seq: Seq<SourceAst>) -> Seq<SourceAst> {
+        Seq::new(seq.exprs.into_iter().map(|expr| self.on_expr(expr)).collect())
+    }
+
+    fn on_expr(&mut self, expr: Expr<SourceAst>) -> Expr<SourceAst> {
+        if let Expr::Ident(name, _) | Expr::Declare(name, _, _, _) | Expr::ForIn(name, _, _, _) = &expr {
+            self.names.insert(name.clone());
+        }
+        map_children(expr, &mut |expr| self.on_expr(expr))
+    }
+}
+
+/// Locals are mangled as `name$local$index` by the compiler, which isn't a valid identifier.
+/// They're given their original names back, unless that would make them clash with something else.
+fn local_names(names: BTreeSet<Ident>, params: &[Ident]) -> BTreeMap<Ident, Ident> {
+    let (mangled, plain): (Vec<_>, Vec<_>) = names.into_iter().partition(|name| name.as_ref().contains('$'));
+    let mut taken: BTreeSet<String> = plain
+        .iter()
+        .chain(params)
+        .map(|name| name.as_ref().to_owned())
+        .collect();
+
+    let mut groups: BTreeMap<&str, Vec<&Ident>> = BTreeMap::new();
+    for name in &mangled {
+        let base = name.as_ref().split('$').next().unwrap_or_default();
+        groups.entry(base).or_default().push(name);
+    }
+
+    let mut renames = BTreeMap::new();
+    for (base, group) in groups {
+        for name in &group {
+            let mut candidate = if group.len() == 1 && !base.is_empty() {
+                base.to_owned()
+            } else {
+                let index = name.as_ref().rsplit('$').next().unwrap_or_default();
+                format!("{}{}", if base.is_empty() { "local" } else { base }, index)
+            };
+            while taken.contains(&candidate) {
+                candidate.push('_');
+            }
+            taken.insert(candidate.clone());
+            renames.insert((*name).clone(), Ident::new(candidate));
+        }
+    }
+    renames
+}
+
+fn rewrite(expr: Expr<SourceAst>, renames: &BTreeMap<Ident, Ident>) -> Expr<SourceAst> {
+    let rename = |name: Ident| renames.get(&name).cloned().unwrap_or(name);
+    match expr {
+        Expr::Ident(name, span) => Expr::Ident(rename(name), span),
+        Expr::Declare(name, type_, init, span) => {
+            let init = init.map(|expr| Box::new(rewrite(*expr, renames)));
+            Expr::Declare(rename(name), type_, init, span)
+        }
+        Expr::ForIn(name, array, body, span) => {
+            let array = Box::new(rewrite(*array, renames));
+            let body = Seq::new(body.exprs.into_iter().map(|expr| rewrite(expr, renames)).collect());
+            Expr::ForIn(rename(name), array, body, span)
+        }
+        // the compiler only accepts 32-bit integers here
+        Expr::Call(name, type_args, mut args, span) if name.as_ref() == <&str>::from(IntrinsicOp::IntEnum) => {
+            if let [Expr::Constant(constant, _)] = &mut args[..] {
+                if let Constant::I64(value) = constant {
+                    if let Ok(value) = i32::try_from(*value) {
+                        *constant = Constant::I32(value);
+                    }
+                }
+            }
+            let args = args.into_iter().map(|expr| rewrite(expr, renames)).collect();
+            Expr::Call(name, type_args, args, span)
+        }
+        other => map_children(other, &mut |expr| rewrite(expr, renames)),
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::{iter, mem};
+
+use redscript::ast::{Constant, Expr, Ident, Seq, SourceAst, Span, SwitchCase, TypeName, UnOp};
+
+use crate::cfg::{Cfg, Exit};
+use crate::error::Error;
+
+/// Turns the graph of a function body back into structured code.
+///
+/// Loops are found through the dominator tree, conditionals and switches end at the post-dominator
+/// of their first block and jumps to the exit or the header of the enclosing loop become breaks and continues.
+pub fn structure(mut cfg: Cfg) -> Result<Seq<SourceAst>, Error> {
+    let blocks = mem::take(&mut cfg.blocks);
+    let jumps = blocks
+        .iter()
+        .map(|block| match block.exit {
+            Exit::Jump(target) => Some(target),
+            _ => None,
+        })
+        .collect();
+    let mut structurer = Structurer {
+        loops: cfg.loops(),
+        visited: vec![false; blocks.len()],
+        blocks: blocks.into_iter().map(|block| (block.stmts, block.exit)).collect(),
+        jumps,
+        cfg,
+    };
+
+    let end = structurer.cfg.end();
+    let ctx = Context {
+        follow: Some(end),
+        break_to: None,
+        continue_to: None,
+    };
+    let body = structurer.region(0, &ctx)?;
+
+    for (block, visited) in structurer.visited.iter().enumerate() {
+        if !visited && block != end && structurer.cfg.is_reachable(block) {
+            return Err(Error::DecompileError(format!("Unstructured jump into block {}", block)));
+        }
+    }
+    Ok(body)
+}
+
+/// Turns the graph of a function body into a loop over a switch on the block to run next.
+///
+/// This is the fallback for control flow that has no structured equivalent, like a loop that can be entered
+/// in more than one place. Every block becomes a case that runs its statements and picks its successor.
+/// The variable holding the next block gets a name that isn't in `names`.
+pub fn dispatch(mut cfg: Cfg, names: &HashSet<Ident>) -> Seq<SourceAst> {
+    let var = iter::once(Ident::Static("block"))
+        .chain((1..).map(|i| Ident::new(format!("block{}", i))))
+        .find(|name| !names.contains(name))
+        .unwrap();
+    let goto = |block: usize| {
+        let target = Expr::Constant(Constant::I32(block as i32), Span::ZERO);
+        Expr::Assign(
+            Box::new(Expr::Ident(var.clone(), Span::ZERO)),
+            Box::new(target),
+            Span::ZERO,
+        )
+    };
+
+    let mut cases = vec![];
+    for (index, block) in mem::take(&mut cfg.blocks).into_iter().enumerate() {
+        if !cfg.is_reachable(index) {
+            continue;
+        }
+        let mut body = block.stmts;
+        match block.exit {
+            Exit::Next => body.push(goto(index + 1)),
+            Exit::Jump(target) => body.push(goto(target)),
+            Exit::Branch(condition, target) => {
+                let if_ = Seq::new(vec![goto(index + 1)]);
+                let else_ = Seq::new(vec![goto(target)]);
+                body.push(Expr::If(Box::new(condition), if_, Some(else_), Span::ZERO));
+            }
+            Exit::Switch {
+                subject, cases, end, ..
+            } => {
+                let cases = cases
+                    .into_iter()
+                    .map(|(matcher, target)| SwitchCase {
+                        matcher,
+                        body: Seq::new(vec![goto(target), Expr::Break(Span::ZERO)]),
+                    })
+                    .collect();
+                let default = Seq::new(vec![goto(end)]);
+                body.push(Expr::Switch(Box::new(subject), cases, Some(default), Span::ZERO));
+            }
+            Exit::Return(expr) => body.push(expr),
+            Exit::End => body.push(Expr::Return(None, Span::ZERO)),
+        }
+        if !matches!(body.last(), Some(Expr::Return(_, _))) {
+            body.push(Expr::Break(Span::ZERO));
+        }
+        let matcher = Expr::Constant(Constant::I32(index as i32), Span::ZERO);
+        cases.push(SwitchCase {
+            matcher,
+            body: Seq::new(body),
+        });
+    }
+
+    let subject = Expr::Ident(var.clone(), Span::ZERO);
+    let switch = Expr::Switch(Box::new(subject), cases, None, Span::ZERO);
+    let condition = Expr::Constant(Constant::Bool(true), Span::ZERO);
+    let entry = Expr::Constant(Constant::I32(0), Span::ZERO);
+    Seq::new(vec![
+        Expr::Declare(var, Some(TypeName::INT32), Some(Box::new(entry)), Span::ZERO),
+        Expr::While(Box::new(condition), Seq::new(vec![switch]), Span::ZERO),
+    ])
+}
+
+#[derive(Clone, Copy)]
+struct Context {
+    /// The block that control reaches when the current region completes normally.
+    follow: Option<usize>,
+    break_to: Option<usize>,
+    continue_to: Option<usize>,
+}
+
+impl Context {
+    fn with_follow(self, follow: Option<usize>) -> Self {
+        Self { follow, ..self }
+    }
+
+    fn is_jump_target(&self, block: usize) -> bool {
+        self.break_to == Some(block) || self.continue_to == Some(block)
+    }
+}
+
+struct Structurer {
+    cfg: Cfg,
+    blocks: Vec<(Vec<Expr<SourceAst>>, Exit)>,
+    jumps: Vec<Option<usize>>,
+    loops: BTreeMap<usize, BTreeSet<usize>>,
+    visited: Vec<bool>,
+}
+
+impl Structurer {
+    fn region(&mut self, entry: usize, ctx: &Context) -> Result<Seq<SourceAst>, Error> {
+        let mut body = vec![];
+        self.walk(entry, ctx, &mut body, false)?;
+        Ok(Seq::new(body))
+    }
+
+    /// Emits the blocks starting from `current` until the region ends. The loop header flag marks
+    /// the entry as the header of the loop being emitted, so it isn't treated as a jump back to it.
+    fn walk(
+        &mut self,
+        mut current: usize,
+        ctx: &Context,
+        body: &mut Vec<Expr<SourceAst>>,
+        mut loop_header: bool,
+    ) -> Result<(), Error> {
+        loop {
+            if !loop_header {
+                if ctx.follow == Some(current) {
+                    return Ok(());
+                } else if ctx.break_to == Some(current) {
+                    body.push(Expr::Break(Span::ZERO));
+                    return Ok(());
+                } else if ctx.continue_to == Some(current) {
+                    body.push(Expr::Continue(Span::ZERO));
+                    return Ok(());
+                } else if current == self.cfg.end() {
+                    body.push(Expr::Return(None, Span::ZERO));
+                    return Ok(());
+                } else if self.loops.contains_key(&current) {
+                    match self.structure_loop(current, body)? {
+                        Some(exit) => {
+                            current = exit;
+                            continue;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+            }
+            loop_header = false;
+
+            let (stmts, exit) = self.take(current)?;
+            body.extend(stmts);
+
+            match exit {
+                Exit::Next => current += 1,
+                Exit::Jump(target) => {
+                    if ctx.break_to == Some(target) {
+                        body.push(Expr::Break(Span::ZERO));
+                        return Ok(());
+                    } else if ctx.continue_to == Some(target) && ctx.follow != Some(target) {
+                        body.push(Expr::Continue(Span::ZERO));
+                        return Ok(());
+                    }
+                    current = target;
+                }
+                Exit::Branch(condition, target) => {
+                    // when the branches don't meet, both of them end with the current region
+                    let follow = self.if_follow(current, target, ctx);
+                    let inner = ctx.with_follow(follow.or(ctx.follow));
+                    let if_ = self.region(current + 1, &inner)?;
+                    let else_ = if follow != Some(target) {
+                        Some(self.region(target, &inner)?).filter(|seq| !seq.exprs.is_empty())
+                    } else {
+                        None
+                    };
+                    body.push(Expr::If(Box::new(condition), if_, else_, Span::ZERO));
+                    match follow {
+                        Some(follow) => current = follow,
+                        None => return Ok(()),
+                    }
+                }
+                Exit::Switch {
+                    subject,
+                    cases,
+                    end,
+                    default,
+                } => {
+                    let follow = if default {
+                        self.switch_follow(current, end, ctx).or(ctx.follow)
+                    } else {
+                        Some(end)
+                    };
+                    let inner = Context {
+                        follow,
+                        break_to: follow,
+                        continue_to: ctx.continue_to,
+                    };
+
+                    let mut switch_cases = vec![];
+                    let mut cases = cases.into_iter().peekable();
+                    while let Some((matcher, entry)) = cases.next() {
+                        let body = match cases.peek() {
+                            Some((_, next)) if *next == entry => Seq::new(vec![]),
+                            Some((_, next)) => self.region(entry, &inner.with_follow(Some(*next)))?,
+                            None => self.region(entry, &inner.with_follow(Some(end)))?,
+                        };
+                        switch_cases.push(SwitchCase { matcher, body });
+                    }
+                    let default = if default { Some(self.region(end, &inner)?) } else { None };
+                    body.push(Expr::Switch(Box::new(subject), switch_cases, default, Span::ZERO));
+                    match follow {
+                        Some(follow) => current = follow,
+                        None => return Ok(()),
+                    }
+                }
+                Exit::Return(expr) => {
+                    body.push(expr);
+                    return Ok(());
+                }
+                Exit::End => return Ok(()),
+            }
+        }
+    }
+
+    /// Emits a loop and returns the block that follows it.
+    fn structure_loop(&mut self, header: usize, body: &mut Vec<Expr<SourceAst>>) -> Result<Option<usize>, Error> {
+        let blocks = &self.loops[&header];
+        let condition_exit = match &self.blocks[header].1 {
+            Exit::Branch(_, target) if !blocks.contains(target) && blocks.contains(&(header + 1)) => Some(*target),
+            _ => None,
+        };
+        let exit = condition_exit.or_else(|| {
+            blocks
+                .iter()
+                .flat_map(|block| &self.cfg.successors[*block])
+                .filter(|succ| !blocks.contains(succ))
+                .min()
+                .copied()
+        });
+        let inner = Context {
+            follow: Some(header),
+            break_to: exit,
+            continue_to: Some(header),
+        };
+
+        let mut loop_body = vec![];
+        let condition = match condition_exit {
+            Some(_) => match self.take(header)? {
+                (stmts, Exit::Branch(condition, _)) if stmts.is_empty() => condition,
+                (stmts, Exit::Branch(condition, _)) => {
+                    // the condition can't be evaluated on its own, so the loop is exited with a break
+                    loop_body.extend(stmts);
+                    let negated = Expr::UnOp(Box::new(condition), UnOp::LogicNot, Span::ZERO);
+                    let break_ = Seq::new(vec![Expr::Break(Span::ZERO)]);
+                    loop_body.push(Expr::If(Box::new(negated), break_, None, Span::ZERO));
+                    Expr::Constant(Constant::Bool(true), Span::ZERO)
+                }
+                _ => return Err(Error::DecompileError("Unexpected loop header".to_owned())),
+            },
+            None => Expr::Constant(Constant::Bool(true), Span::ZERO),
+        };
+        let entry = if condition_exit.is_some() { header + 1 } else { header };
+        self.walk(entry, &inner, &mut loop_body, condition_exit.is_none())?;
+
+        body.push(Expr::While(Box::new(condition), Seq::new(loop_body), Span::ZERO));
+        Ok(exit)
+    }
+
+    fn take(&mut self, block: usize) -> Result<(Vec<Expr<SourceAst>>, Exit), Error> {
+        if mem::replace(&mut self.visited[block], true) {
+            return Err(Error::DecompileError(format!("Unstructured jump into block {}", block)));
+        }
+        Ok(mem::replace(&mut self.blocks[block], (vec![], Exit::End)))
+    }
+
+    /// Finds where the branches of a conditional meet. When one of them never gets there,
+    /// the jump over the else branch that the compiler places at the end of the if branch is used instead.
+    fn if_follow(&self, block: usize, target: usize, ctx: &Context) -> Option<usize> {
+        if let Some(follow) = self.cfg.post_dominator(block) {
+            if follow > block && self.cfg.dominates(block, follow) && !ctx.is_jump_target(follow) {
+                return Some(follow);
+            }
+        }
+        if target <= block || ctx.is_jump_target(target) {
+            return None;
+        }
+        match self.jumps[target - 1] {
+            Some(exit) if target - 1 > block && exit > target && !ctx.is_jump_target(exit) => Some(exit),
+            _ => Some(target),
+        }
+    }
+
+    /// Finds the end of a switch with a default case. Breaks out of the other cases point at it,
+    /// since their bodies end at the next label.
+    fn switch_follow(&self, block: usize, end: usize, ctx: &Context) -> Option<usize> {
+        let break_target = (block + 1..end)
+            .filter_map(|case| self.jumps[case])
+            .find(|target| *target > end && !ctx.is_jump_target(*target));
+        if break_target.is_some() {
+            return break_target;
+        }
+        self.cfg
+            .post_dominator(block)
+            .filter(|follow| *follow > end && self.cfg.dominates(block, *follow) && !ctx.is_jump_target(*follow))
+    }
+}
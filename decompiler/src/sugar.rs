@@ -0,0 +1,584 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use redscript::ast::{Constant, Expr, Ident, Literal, Seq, SourceAst, Span, SwitchCase, TypeName};
+use redscript::bytecode::IntrinsicOp;
+use redscript::Ref;
+
+const STRING_CONCAT: &str = "OperatorAdd;Script_RefStringScript_RefString;String";
+
+/// Folds the code generated by the desugaring pass of the compiler back into the syntax it came from.
+pub fn resugar(body: Seq<SourceAst>, locals: &mut BTreeMap<Ident, TypeName>) -> Seq<SourceAst> {
+    let mut references = BTreeMap::new();
+    for expr in &body.exprs {
+        count_references(expr, &mut references);
+    }
+
+    let mut resugar = Resugar {
+        locals,
+        references,
+        consumed: BTreeMap::new(),
+    };
+    let body = resugar.on_seq(body);
+
+    // loop variables are declared by the loops, unless they're used elsewhere
+    for (name, consumed) in resugar.consumed {
+        if resugar.references.get(&name) == Some(&consumed) {
+            resugar.locals.remove(&name);
+        }
+    }
+    body
+}
+
+struct ForInLoop {
+    name: Ident,
+    arr: Ident,
+    counter: Ident,
+}
+
+struct Resugar<'a> {
+    locals: &'a mut BTreeMap<Ident, TypeName>,
+    references: BTreeMap<Ident, usize>,
+    consumed: BTreeMap<Ident, usize>,
+}
+
+impl<'a> Resugar<'a> {
+    fn on_seq(&mut self, seq: Seq<SourceAst>) -> Seq<SourceAst> {
+        let mut exprs: Vec<_> = seq
+            .exprs
+            .into_iter()
+            .filter(|expr| !expr.is_empty())
+            .map(|expr| self.on_expr(expr))
+            .collect();
+
+        let mut i = 0;
+        while i < exprs.len() {
+            if let Some((local, count)) = self.match_array_lit(&exprs[i..]) {
+                let pushes: Vec<_> = exprs.drain(i..i + count).collect();
+                let use_site = exprs.remove(i);
+                exprs.insert(i, self.array_lit(local, pushes, use_site));
+                // the literal might have been the last element of an enclosing one
+                while i > 0 && array_push_target(&exprs[i - 1]).is_some() {
+                    i -= 1;
+                }
+            } else {
+                i += 1;
+            }
+        }
+
+        let mut i = 0;
+        while i + 2 < exprs.len() {
+            if let Some(for_in) = self.match_for_in(&exprs[i..i + 3]) {
+                let matched: Vec<_> = exprs.drain(i..i + 3).collect();
+                exprs.insert(i, self.for_in(for_in, matched));
+            }
+            i += 1;
+        }
+        Seq::new(exprs)
+    }
+
+    fn on_expr(&mut self, expr: Expr<SourceAst>) -> Expr<SourceAst> {
+        match expr {
+            Expr::Seq(seq) => Expr::Seq(self.on_seq(seq)),
+            Expr::If(cond, if_, else_, span) => {
+                let cond = self.on_expr(*cond);
+                Expr::If(
+                    Box::new(cond),
+                    self.on_seq(if_),
+                    else_.map(|seq| self.on_seq(seq)),
+                    span,
+                )
+            }
+            Expr::Switch(subject, cases, default, span) => {
+                let subject = self.on_expr(*subject);
+                let cases = cases
+                    .into_iter()
+                    .map(|case| SwitchCase {
+                        matcher: self.on_expr(case.matcher),
+                        body: self.on_seq(case.body),
+                    })
+                    .collect();
+                Expr::Switch(Box::new(subject), cases, default.map(|seq| self.on_seq(seq)), span)
+            }
+            Expr::While(cond, body, span) => {
+                let cond = self.on_expr(*cond);
+                Expr::While(Box::new(cond), self.on_seq(body), span)
+            }
+            Expr::ForIn(name, array, body, span) => {
+                let array = self.on_expr(*array);
+                Expr::ForIn(name, Box::new(array), self.on_seq(body), span)
+            }
+            Expr::Ident(name, span) if self.is_empty_array_lit(&name) => {
+                self.locals.remove(&name);
+                Expr::ArrayLit(vec![], None, span)
+            }
+            other => match map_children(other, &mut |expr| self.on_expr(expr)) {
+                call @ Expr::Call(_, _, _, _) if is_string_concat(&call) => interpolated_string(call),
+                other => other,
+            },
+        }
+    }
+
+    /// Matches the pushes that an array literal is lowered to:
+    /// ```text
+    /// ArrayPush(tmp, a);
+    /// ArrayPush(tmp, b);
+    /// Use(tmp);
+    /// ```
+    /// where `tmp` is not used anywhere else in the function.
+    fn match_array_lit(&self, exprs: &[Expr<SourceAst>]) -> Option<(Ident, usize)> {
+        let local = self.local_ident(array_push_target(exprs.first()?)?)?;
+        let count = exprs
+            .iter()
+            .take_while(|expr| array_push_target(expr).is_some_and(|target| is_ident(target, local)))
+            .count();
+
+        let mut use_references = BTreeMap::new();
+        count_references(exprs.get(count)?, &mut use_references);
+        if use_references.get(local) != Some(&1) || self.references.get(local) != Some(&(count + 1)) {
+            return None;
+        }
+        Some((local.clone(), count))
+    }
+
+    fn array_lit(&mut self, local: Ident, pushes: Vec<Expr<SourceAst>>, use_site: Expr<SourceAst>) -> Expr<SourceAst> {
+        let elements = pushes
+            .into_iter()
+            .map(|push| match push {
+                Expr::Call(_, _, args, _) => args.into_iter().nth(1).expect("ArrayPush has two arguments"),
+                _ => unreachable!("array literal has been matched"),
+            })
+            .collect();
+        self.locals.remove(&local);
+        let mut literal = Some(Expr::ArrayLit(elements, None, Span::ZERO));
+        replace_ident(use_site, &local, &mut literal)
+    }
+
+    /// Empty array literals are lowered to a temporary that's never pushed to.
+    fn is_empty_array_lit(&self, name: &Ident) -> bool {
+        name.as_ref().starts_with("synthetic$")
+            && self.references.get(name) == Some(&1)
+            && matches!(self.locals.get(name), Some(type_) if type_.name.as_ref() == "array")
+    }
+
+    /// Matches the while loop that `for x in array` is lowered to:
+    /// ```text
+    /// arr = array;
+    /// i = 0;
+    /// while i < ArraySize(arr) {
+    ///     x = arr[i];
+    ///     ...
+    ///     i += 1;
+    /// }
+    /// ```
+    /// where every `continue` in the body is preceded by `i += 1`.
+    fn match_for_in(&self, exprs: &[Expr<SourceAst>]) -> Option<ForInLoop> {
+        let [Expr::Assign(arr, _, _), Expr::Assign(counter, zero, _), Expr::While(cond, body, _)] = exprs else {
+            return None;
+        };
+        let arr = self.local_ident(arr)?;
+        let counter = self.local_ident(counter)?;
+        if arr == counter || !matches!(**zero, Expr::Constant(Constant::I32(0), _)) {
+            return None;
+        }
+
+        let Expr::Call(less, _, args, _) = cond.as_ref() else {
+            return None;
+        };
+        match (function_name(less), &args[..]) {
+            ("OperatorLess", [lhs, Expr::Call(size, _, size_args, _)])
+                if is_ident(lhs, counter) && function_name(size) == <&str>::from(IntrinsicOp::ArraySize) =>
+            {
+                match &size_args[..] {
+                    [size_arg] if is_ident(size_arg, arr) => {}
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+
+        let (first, rest) = body.exprs.split_first()?;
+        let (last, middle) = rest.split_last()?;
+        let Expr::Assign(name, elem, _) = first else {
+            return None;
+        };
+        let name = self.local_ident(name)?;
+        match elem.as_ref() {
+            Expr::ArrayElem(elem_arr, index, _) if is_ident(elem_arr, arr) && is_ident(index, counter) => {}
+            _ => return None,
+        }
+        if !is_increment(last, counter) || name == arr || name == counter {
+            return None;
+        }
+
+        // the temporaries must not be used anywhere else in the function
+        let continues = continue_increments(middle, counter);
+        if self.references.get(arr) != Some(&3) || self.references.get(counter) != Some(&(4 + continues)) {
+            return None;
+        }
+
+        Some(ForInLoop {
+            name: name.clone(),
+            arr: arr.clone(),
+            counter: counter.clone(),
+        })
+    }
+
+    fn for_in(&mut self, matched: ForInLoop, exprs: Vec<Expr<SourceAst>>) -> Expr<SourceAst> {
+        let mut exprs = exprs.into_iter();
+        let (Some(Expr::Assign(_, array, _)), _, Some(Expr::While(_, body, span))) =
+            (exprs.next(), exprs.next(), exprs.next())
+        else {
+            unreachable!("for-in loop has been matched")
+        };
+        let mut exprs = body.exprs;
+        exprs.remove(0);
+        exprs.pop();
+        let mut body = Seq::new(exprs);
+        remove_continue_increments(&mut body, &matched.counter);
+
+        let mut name_references = BTreeMap::new();
+        for expr in &body.exprs {
+            count_references(expr, &mut name_references);
+        }
+        let consumed = 1 + name_references.get(&matched.name).unwrap_or(&0);
+        *self.consumed.entry(matched.name.clone()).or_default() += consumed;
+
+        self.locals.remove(&matched.arr);
+        self.locals.remove(&matched.counter);
+        Expr::ForIn(matched.name, array, body, span)
+    }
+
+    fn local_ident<'b>(&self, expr: &'b Expr<SourceAst>) -> Option<&'b Ident> {
+        match expr {
+            Expr::Ident(name, _) if self.locals.contains_key(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+fn function_name(name: &Ident) -> &str {
+    name.as_ref().split(';').next().unwrap_or_default()
+}
+
+fn array_push_target(expr: &Expr<SourceAst>) -> Option<&Expr<SourceAst>> {
+    match expr {
+        Expr::Call(name, _, args, _) if function_name(name) == <&str>::from(IntrinsicOp::ArrayPush) => {
+            match &args[..] {
+                [target, _] => Some(target),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_intrinsic_call(expr: &Expr<SourceAst>, op: IntrinsicOp) -> bool {
+    matches!(expr, Expr::Call(name, _, args, _) if function_name(name) == <&str>::from(op) && args.len() == 1)
+}
+
+fn is_string_literal(expr: &Expr<SourceAst>) -> bool {
+    matches!(expr, Expr::Constant(Constant::String(Literal::String, _), _))
+}
+
+/// Matches the `OperatorAdd(AsRef(acc), part)` calls that interpolated strings are lowered to,
+/// where `acc` is either the prefix or an interpolated string that has already been folded.
+fn is_string_concat(expr: &Expr<SourceAst>) -> bool {
+    match expr {
+        Expr::Call(name, _, args, _) if name.as_ref() == STRING_CONCAT => match &args[..] {
+            [Expr::Call(_, _, acc, _), _] if is_intrinsic_call(&args[0], IntrinsicOp::AsRef) => {
+                matches!(&acc[..], [acc] if is_string_literal(acc) || matches!(acc, Expr::InterpolatedString(_, _, _)))
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn interpolated_string(expr: Expr<SourceAst>) -> Expr<SourceAst> {
+    let Expr::Call(_, _, args, span) = expr else {
+        unreachable!("string concatenation has been matched")
+    };
+    let mut args = args.into_iter();
+    let (Some(acc), Some(part)) = (args.next(), args.next()) else {
+        unreachable!("string concatenation has been matched")
+    };
+    let (prefix, mut parts) = match unwrap_intrinsic(acc, IntrinsicOp::AsRef) {
+        Ok(Expr::Constant(Constant::String(_, prefix), _)) => (prefix, vec![]),
+        Ok(Expr::InterpolatedString(prefix, parts, _)) => (prefix, parts),
+        _ => unreachable!("string concatenation has been matched"),
+    };
+
+    // a part followed by a string is concatenated with it before being appended
+    let part = match unwrap_intrinsic(part, IntrinsicOp::AsRef) {
+        Ok(Expr::Call(name, _, args, _)) if name.as_ref() == STRING_CONCAT && is_suffixed_part(&args) => {
+            let mut args = args.into_iter();
+            let (Some(part), Some(suffix)) = (args.next(), args.next()) else {
+                unreachable!("suffixed part has been matched")
+            };
+            let Ok(Expr::Constant(Constant::String(_, suffix), _)) = unwrap_intrinsic(suffix, IntrinsicOp::AsRef)
+            else {
+                unreachable!("suffixed part has been matched")
+            };
+            (interpolated_part(part), suffix)
+        }
+        Ok(other) => (
+            interpolated_part(wrap_intrinsic(other, IntrinsicOp::AsRef)),
+            Ref::default(),
+        ),
+        Err(other) => (interpolated_part(other), Ref::default()),
+    };
+    parts.push(part);
+    Expr::InterpolatedString(prefix, parts, span)
+}
+
+fn is_suffixed_part(args: &[Expr<SourceAst>]) -> bool {
+    match args {
+        [_, suffix @ Expr::Call(_, _, suffix_args, _)] if is_intrinsic_call(suffix, IntrinsicOp::AsRef) => {
+            is_string_literal(&suffix_args[0])
+        }
+        _ => false,
+    }
+}
+
+/// Strips the conversions inserted for string parts, they're added back by the compiler.
+fn interpolated_part(expr: Expr<SourceAst>) -> Expr<SourceAst> {
+    match unwrap_intrinsic(expr, IntrinsicOp::AsRef) {
+        Ok(expr) => unwrap_intrinsic(expr, IntrinsicOp::ToString).unwrap_or_else(|expr| expr),
+        Err(expr) => expr,
+    }
+}
+
+fn unwrap_intrinsic(expr: Expr<SourceAst>, op: IntrinsicOp) -> Result<Expr<SourceAst>, Expr<SourceAst>> {
+    if !is_intrinsic_call(&expr, op) {
+        return Err(expr);
+    }
+    match expr {
+        Expr::Call(_, _, args, _) => Ok(args.into_iter().next().unwrap()),
+        _ => unreachable!("intrinsic call has been matched"),
+    }
+}
+
+fn wrap_intrinsic(expr: Expr<SourceAst>, op: IntrinsicOp) -> Expr<SourceAst> {
+    Expr::Call(Ident::Static(op.into()), vec![], vec![expr], Span::ZERO)
+}
+
+fn replace_ident(expr: Expr<SourceAst>, name: &Ident, replacement: &mut Option<Expr<SourceAst>>) -> Expr<SourceAst> {
+    match expr {
+        Expr::Ident(ident, span) if &ident == name => replacement.take().unwrap_or(Expr::Ident(ident, span)),
+        other => map_children(other, &mut |expr| replace_ident(expr, name, replacement)),
+    }
+}
+
+/// Applies `f` to every direct child of an expression, including the statements of nested blocks.
+pub(crate) fn map_children<F>(expr: Expr<SourceAst>, f: &mut F) -> Expr<SourceAst>
+where
+    F: FnMut(Expr<SourceAst>) -> Expr<SourceAst>,
+{
+    let map_seq = |seq: Seq<SourceAst>, f: &mut F| Seq::new(seq.exprs.into_iter().map(&mut *f).collect());
+    let map_box = |expr: Box<Expr<SourceAst>>, f: &mut F| Box::new(f(*expr));
+
+    match expr {
+        Expr::ArrayLit(exprs, type_, span) => Expr::ArrayLit(exprs.into_iter().map(&mut *f).collect(), type_, span),
+        Expr::InterpolatedString(prefix, parts, span) => {
+            let parts = parts.into_iter().map(|(expr, str)| (f(expr), str)).collect();
+            Expr::InterpolatedString(prefix, parts, span)
+        }
+        Expr::Declare(name, type_, init, span) => Expr::Declare(name, type_, init.map(|expr| map_box(expr, f)), span),
+        Expr::Cast(type_, expr, span) => Expr::Cast(type_, map_box(expr, f), span),
+        Expr::Assign(lhs, rhs, span) => Expr::Assign(map_box(lhs, f), map_box(rhs, f), span),
+        Expr::Call(name, type_args, args, span) => {
+            Expr::Call(name, type_args, args.into_iter().map(&mut *f).collect(), span)
+        }
+        Expr::MethodCall(expr, name, args, span) => {
+            let expr = map_box(expr, f);
+            Expr::MethodCall(expr, name, args.into_iter().map(&mut *f).collect(), span)
+        }
+        Expr::Member(expr, name, span) => Expr::Member(map_box(expr, f), name, span),
+        Expr::ArrayElem(arr, index, span) => Expr::ArrayElem(map_box(arr, f), map_box(index, f), span),
+        Expr::New(type_, args, span) => Expr::New(type_, args.into_iter().map(&mut *f).collect(), span),
+        Expr::Return(expr, span) => Expr::Return(expr.map(|expr| map_box(expr, f)), span),
+        Expr::Seq(seq) => Expr::Seq(map_seq(seq, f)),
+        Expr::Switch(subject, cases, default, span) => {
+            let subject = map_box(subject, f);
+            let cases = cases
+                .into_iter()
+                .map(|case| SwitchCase {
+                    matcher: f(case.matcher),
+                    body: map_seq(case.body, f),
+                })
+                .collect();
+            Expr::Switch(subject, cases, default.map(|seq| map_seq(seq, f)), span)
+        }
+        Expr::If(cond, if_, else_, span) => {
+            let cond = map_box(cond, f);
+            let if_ = map_seq(if_, f);
+            Expr::If(cond, if_, else_.map(|seq| map_seq(seq, f)), span)
+        }
+        Expr::Conditional(cond, true_, false_, span) => {
+            Expr::Conditional(map_box(cond, f), map_box(true_, f), map_box(false_, f), span)
+        }
+        Expr::While(cond, body, span) => Expr::While(map_box(cond, f), map_seq(body, f), span),
+        Expr::ForIn(name, array, body, span) => Expr::ForIn(name, map_box(array, f), map_seq(body, f), span),
+        Expr::BinOp(lhs, rhs, op, span) => Expr::BinOp(map_box(lhs, f), map_box(rhs, f), op, span),
+        Expr::UnOp(expr, op, span) => Expr::UnOp(map_box(expr, f), op, span),
+        other @ (Expr::Ident(_, _)
+        | Expr::Constant(_, _)
+        | Expr::Goto(_, _)
+        | Expr::This(_)
+        | Expr::Super(_)
+        | Expr::Break(_)
+        | Expr::Continue(_)
+        | Expr::Null(_)) => other,
+    }
+}
+
+fn is_ident(expr: &Expr<SourceAst>, expected: &Ident) -> bool {
+    matches!(expr, Expr::Ident(name, _) if name == expected)
+}
+
+fn is_increment(expr: &Expr<SourceAst>, counter: &Ident) -> bool {
+    match expr {
+        Expr::Call(name, _, args, _) if function_name(name) == "OperatorAssignAdd" => {
+            matches!(&args[..], [lhs, Expr::Constant(Constant::I32(1), _)] if is_ident(lhs, counter))
+        }
+        _ => false,
+    }
+}
+
+/// Counts the counter increments that precede `continue` statements of the loop.
+fn continue_increments(exprs: &[Expr<SourceAst>], counter: &Ident) -> usize {
+    let preceding = exprs
+        .iter()
+        .tuple_windows()
+        .filter(|(expr, next)| is_increment(expr, counter) && matches!(next, Expr::Continue(_)))
+        .count();
+    let nested: usize = exprs
+        .iter()
+        .map(|expr| match expr {
+            Expr::Seq(seq) => continue_increments(&seq.exprs, counter),
+            Expr::If(_, if_, else_, _) => {
+                continue_increments(&if_.exprs, counter)
+                    + else_.as_ref().map_or(0, |seq| continue_increments(&seq.exprs, counter))
+            }
+            Expr::Switch(_, cases, default, _) => {
+                cases
+                    .iter()
+                    .map(|case| continue_increments(&case.body.exprs, counter))
+                    .sum::<usize>()
+                    + default
+                        .as_ref()
+                        .map_or(0, |seq| continue_increments(&seq.exprs, counter))
+            }
+            // continue statements in nested loops refer to the inner loop
+            _ => 0,
+        })
+        .sum();
+    preceding + nested
+}
+
+/// Removes the counter increments that precede `continue` statements of the loop, returns how many were removed.
+fn remove_continue_increments(seq: &mut Seq<SourceAst>, counter: &Ident) -> usize {
+    let mut removed = 0;
+    let mut i = 0;
+    while i < seq.exprs.len() {
+        if is_increment(&seq.exprs[i], counter) && matches!(seq.exprs.get(i + 1), Some(Expr::Continue(_))) {
+            seq.exprs.remove(i);
+            removed += 1;
+            continue;
+        }
+        match &mut seq.exprs[i] {
+            Expr::Seq(seq) => removed += remove_continue_increments(seq, counter),
+            Expr::If(_, if_, else_, _) => {
+                removed += remove_continue_increments(if_, counter);
+                if let Some(else_) = else_ {
+                    removed += remove_continue_increments(else_, counter);
+                }
+            }
+            Expr::Switch(_, cases, default, _) => {
+                for case in cases {
+                    removed += remove_continue_increments(&mut case.body, counter);
+                }
+                if let Some(default) = default {
+                    removed += remove_continue_increments(default, counter);
+                }
+            }
+            // continue statements in nested loops refer to the inner loop
+            _ => {}
+        }
+        i += 1;
+    }
+    removed
+}
+
+fn count_references(expr: &Expr<SourceAst>, counts: &mut BTreeMap<Ident, usize>) {
+    let count_seq = |seq: &Seq<SourceAst>, counts: &mut BTreeMap<Ident, usize>| {
+        for expr in &seq.exprs {
+            count_references(expr, counts);
+        }
+    };
+    match expr {
+        Expr::Ident(name, _) => *counts.entry(name.clone()).or_default() += 1,
+        Expr::ArrayLit(exprs, _, _) | Expr::Call(_, _, exprs, _) | Expr::New(_, exprs, _) => {
+            for expr in exprs {
+                count_references(expr, counts);
+            }
+        }
+        Expr::InterpolatedString(_, parts, _) => {
+            for (expr, _) in parts {
+                count_references(expr, counts);
+            }
+        }
+        Expr::Declare(_, _, Some(expr), _)
+        | Expr::Cast(_, expr, _)
+        | Expr::Member(expr, _, _)
+        | Expr::Return(Some(expr), _)
+        | Expr::UnOp(expr, _, _) => count_references(expr, counts),
+        Expr::Assign(lhs, rhs, _) | Expr::ArrayElem(lhs, rhs, _) | Expr::BinOp(lhs, rhs, _, _) => {
+            count_references(lhs, counts);
+            count_references(rhs, counts);
+        }
+        Expr::MethodCall(expr, _, args, _) => {
+            count_references(expr, counts);
+            for arg in args {
+                count_references(arg, counts);
+            }
+        }
+        Expr::Seq(seq) => count_seq(seq, counts),
+        Expr::Switch(subject, cases, default, _) => {
+            count_references(subject, counts);
+            for case in cases {
+                count_references(&case.matcher, counts);
+                count_seq(&case.body, counts);
+            }
+            if let Some(default) = default {
+                count_seq(default, counts);
+            }
+        }
+        Expr::If(cond, if_, else_, _) => {
+            count_references(cond, counts);
+            count_seq(if_, counts);
+            if let Some(else_) = else_ {
+                count_seq(else_, counts);
+            }
+        }
+        Expr::Conditional(cond, true_, false_, _) => {
+            count_references(cond, counts);
+            count_references(true_, counts);
+            count_references(false_, counts);
+        }
+        Expr::While(cond, body, _) | Expr::ForIn(_, cond, body, _) => {
+            count_references(cond, counts);
+            count_seq(body, counts);
+        }
+        Expr::Constant(_, _)
+        | Expr::Declare(_, _, None, _)
+        | Expr::Return(None, _)
+        | Expr::Goto(_, _)
+        | Expr::This(_)
+        | Expr::Super(_)
+        | Expr::Break(_)
+        | Expr::Continue(_)
+        | Expr::Null(_) => {}
+    }
+}
@@ -0,0 +1,276 @@
+use redscript::bundle::ConstantPool;
+use redscript::definition::AnyDefinition;
+use redscript_compiler::asm::assemble;
+use redscript_decompiler::print::{write_definition, OutputMode};
+use utils::{compiled, OPERATORS};
+
+#[allow(unused)]
+mod utils;
+
+#[test]
+fn decompile_for_in_loops() {
+    let sources = "
+        func Testing(arr: array<array<Int32>>) {
+            for inner in arr {
+                for x in inner {
+                    Log(ToString(x));
+                }
+            }
+        }
+    ";
+    let expected = "
+private static func Testing(arr: array<array<Int32>>) -> Void {
+  for inner$local$0 in arr {
+    for x$local$1 in inner$local$0 {
+      Log(ToString(x$local$1));
+    };
+  };
+}
+";
+    assert_eq!(decompiled(sources, "Testing"), expected);
+}
+
+#[test]
+fn keep_loops_with_counters_used_elsewhere() {
+    let sources = "
+        func Testing(arr: array<Int32>) -> Int32 {
+            let i = 0;
+            while i < ArraySize(arr) {
+                let x = arr[i];
+                Log(ToString(x));
+                i += 1;
+            }
+            return i;
+        }
+    ";
+    let code = decompiled(sources, "Testing");
+    assert!(code.contains("while i$local$0 < ArraySize(arr)"), "{}", code);
+}
+
+#[test]
+fn decompile_interpolated_strings() {
+    let sources = r#"
+        func Testing(name: String, year: Int32) -> String {
+            Log(s"\(year)");
+            return s"My name is \"\(name)\" and I am \(year - 1990) years old\n";
+        }
+    "#;
+    let expected = r#"
+private static func Testing(name: String, year: Int32) -> String {
+  Log(s"\(year)");
+  return s"My name is \"\(name)\" and I am \(year - 1990) years old\n";
+}
+"#;
+    assert_eq!(decompiled(sources, "Testing"), expected);
+}
+
+#[test]
+fn decompile_array_literals() {
+    let sources = "
+        func Testing() -> array<array<Int32>> {
+            let xs = [[1, 2], [3]];
+            Consume([]);
+            for x in [4, 5] {
+                Consume([x, x]);
+            }
+            return xs;
+        }
+    ";
+    let expected = "
+private static func Testing() -> array<array<Int32>> {
+  let xs$local$0: array<array<Int32>> = [[1, 2], [3]];
+  Consume([]);
+  for x$local$1 in [4, 5] {
+    Consume([x$local$1, x$local$1]);
+  };
+  return xs$local$0;
+}
+";
+    assert_eq!(decompiled(sources, "Testing"), expected);
+}
+
+#[test]
+fn decompile_loops_with_backward_conditional_jumps() {
+    let sources = "
+        func Testing(n: Int32) -> Int32 {
+            return n;
+        }
+    ";
+    let listing = "
+        func Testing;Int32 {
+            let i: Int32
+            assign
+            local i
+            i32zero
+        start:
+            invokestatic add 0 OperatorAssignAdd;OutInt32Int32;Int32 0
+            local i
+            i32one
+            paramend
+        add:
+            jumpiffalse start
+            invokestatic less 0 OperatorLess;Int32Int32;Bool 0
+            param n
+            local i
+            paramend
+        less:
+            return
+            local i
+        }
+    ";
+    let expected = "
+private static func Testing(n: Int32) -> Int32 {
+  let i: Int32 = 0;
+  while true {
+    i += 1;
+    if n < i {
+      break;
+    };
+  };
+  return i;
+}
+";
+    let mut pool = compiled(&[sources, OPERATORS]);
+    assemble(listing, &mut pool).unwrap();
+    assert_eq!(decompiled_from(&pool, "Testing"), expected);
+}
+
+#[test]
+fn decompile_irreducible_loops() {
+    let sources = "
+        func Testing(n: Int32) -> Int32 {
+            return n;
+        }
+    ";
+    // the loop can be entered both at its start and in the middle
+    let listing = "
+        func Testing;Int32 {
+            let i: Int32
+            assign
+            local i
+            i32zero
+            jumpiffalse second
+            invokestatic first 0 OperatorLess;Int32Int32;Bool 0
+            i32zero
+            param n
+            paramend
+        first:
+            invokestatic second 0 OperatorAssignAdd;OutInt32Int32;Int32 0
+            local i
+            i32one
+            paramend
+        second:
+            invokestatic add 0 OperatorAssignAdd;OutInt32Int32;Int32 0
+            local i
+            i32one
+            paramend
+        add:
+            jumpiffalse end
+            invokestatic less 0 OperatorLess;Int32Int32;Bool 0
+            local i
+            param n
+            paramend
+        less:
+            jump first
+        end:
+            return
+            local i
+        }
+    ";
+    let expected = "
+private static func Testing(n: Int32) -> Int32 {
+  let i: Int32;
+  let block: Int32 = 0;
+  while true {
+    switch block {
+      case 0:
+        i = 0;
+        if 0 < n {
+          block = 1;
+        } else {
+          block = 2;
+        };
+        break;
+      case 1:
+        i += 1;
+        block = 2;
+        break;
+      case 2:
+        i += 1;
+        if i < n {
+          block = 3;
+        } else {
+          block = 4;
+        };
+        break;
+      case 3:
+        block = 1;
+        break;
+      case 4:
+        return i;
+    };
+  };
+}
+";
+    let mut pool = compiled(&[sources, OPERATORS]);
+    assemble(listing, &mut pool).unwrap();
+    assert_eq!(decompiled_from(&pool, "Testing"), expected);
+}
+
+#[test]
+fn decompile_irreducible_loops_without_name_clashes() {
+    let sources = "
+        func Testing(block: Int32) -> Int32 {
+            return block;
+        }
+    ";
+    let listing = "
+        func Testing;Int32 {
+            let block1: Int32
+            jumpiffalse second
+            invokestatic first 0 OperatorLess;Int32Int32;Bool 0
+            i32zero
+            param block
+            paramend
+        first:
+            assign
+            local block1
+            i32one
+        second:
+            jumpiffalse end
+            invokestatic less 0 OperatorLess;Int32Int32;Bool 0
+            local block1
+            param block
+            paramend
+        less:
+            jump first
+        end:
+            return
+            local block1
+        }
+    ";
+    let mut pool = compiled(&[sources, OPERATORS]);
+    assemble(listing, &mut pool).unwrap();
+    let output = decompiled_from(&pool, "Testing");
+    assert!(output.contains("let block2: Int32 = 0;"), "{}", output);
+    assert!(output.contains("switch block2 {"), "{}", output);
+}
+
+fn decompiled(source: &str, function: &str) -> String {
+    decompiled_from(&compiled(&[source, OPERATORS]), function)
+}
+
+fn decompiled_from(pool: &ConstantPool, function: &str) -> String {
+    let (_, def) = pool
+        .definitions()
+        .map(Result::unwrap)
+        .find(|(idx, def)| {
+            matches!(def.value, AnyDefinition::Function(_))
+                && pool.def_name(*idx).unwrap().split(';').next() == Some(function)
+        })
+        .expect("function not found");
+
+    let mut out = Vec::new();
+    write_definition(&mut out, def, pool, 0, OutputMode::Code { verbose: false }).unwrap();
+    String::from_utf8(out).unwrap()
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::{env, fs, mem};
+
+use itertools::Itertools;
+use redscript::bundle::{ConstantPool, PoolIndex, ScriptBundle};
+use redscript::bytecode::{Instr, Offset};
+use redscript::definition::{AnyDefinition, Function, Parameter, Type};
+use redscript_compiler::source_map::{File, Files};
+use redscript_compiler::unit::CompilationUnit;
+use redscript_decompiler::print::{write_definition, OutputMode};
+use utils::{compiled, OPERATORS, PREDEF};
+
+#[allow(unused)]
+mod utils;
+
+const SOURCES: &str = r#"
+    enum Direction {
+        Left = 0,
+        Right = 1,
+        Unknown = -1
+    }
+
+    class Counter {
+        let count: Int32;
+        let values: array<Int32>;
+
+        public func Add(value: Int32) -> Int32 {
+            let previous = this.count;
+            this.count += value;
+            ArrayPush(this.values, value);
+            return previous;
+        }
+
+        public func Sum() -> Int32 {
+            let total = 0;
+            for value in this.values {
+                total += value;
+            }
+            return total;
+        }
+
+        public static func Create(start: Int32) -> ref<Counter> {
+            let counter = new Counter();
+            counter.count = start;
+            return counter;
+        }
+    }
+
+    func Describe(counter: ref<Counter>, direction: Direction) -> String {
+        let name: String;
+        switch direction {
+            case Direction.Left:
+                name = "left";
+                break;
+            case Direction.Right:
+                name = "right";
+                break;
+            default:
+                name = "unknown";
+        }
+        return s"\(name): \(counter.Sum())";
+    }
+
+    func Clamp(x: Float, low: Float, high: Float) -> Float {
+        if x < low {
+            return low;
+        } else {
+            if x > high {
+                return high;
+            }
+        }
+        return x;
+    }
+
+    func Scale(x: Float) -> Float = x * 0.125 + -1.5;
+
+    func Power(n: Int32) -> Int32 {
+        let i = 0;
+        let acc = 1;
+        while i < n {
+            acc = acc * 2;
+            i += 1;
+        }
+        return n > 0 ? acc : -1;
+    }
+
+    func Shadowed(xs: array<Int32>) {
+        for x in xs {
+            Log(ToString(x));
+        }
+        for x in [1, 2] {
+            Log(ToString(x));
+        }
+    }
+
+    func FindAbove(xs: array<Int32>, limit: Int32) -> Int32 {
+        let i = 0;
+        while i < ArraySize(xs) {
+            if xs[i] > limit {
+                return xs[i];
+            }
+            i += 1;
+        }
+        return -1;
+    }
+
+    func Nested(n: Int32) -> Int32 {
+        let total = 0;
+        let i = 0;
+        while i < n {
+            i += 1;
+            if i == 3 {
+                continue;
+            }
+            let j = 0;
+            while true {
+                j += 1;
+                if j > i {
+                    break;
+                }
+                if j > 10 {
+                    return total;
+                }
+                total += j;
+            }
+            if total > 100 {
+                break;
+            } else {
+                total += 1;
+            }
+        }
+        return total;
+    }
+
+    func Classify(values: array<Int32>) -> Int32 {
+        let result = 0;
+        for value in values {
+            switch value {
+                case 0:
+                case 1:
+                    result += 1;
+                    break;
+                case 2:
+                    if result > 5 {
+                        continue;
+                    }
+                    result += 2;
+                case 3:
+                    result += 3;
+                    break;
+                default:
+                    if result > 10 {
+                        return result;
+                    }
+                    result -= 1;
+            }
+        }
+        return result;
+    }
+
+    func Sign(x: Int32) -> Int32 {
+        if x > 0 {
+            return 1;
+        } else {
+            if x == 0 {
+                return 0;
+            } else {
+                return -1;
+            }
+        }
+    }
+
+    native func OperatorEqual(l: Int32, r: Int32) -> Bool
+    native func OperatorAssignSubtract(out l: Int32, r: Int32) -> Int32
+    native func OperatorLess(l: Float, r: Float) -> Bool
+    native func OperatorGreater(l: Float, r: Float) -> Bool
+    native func OperatorGreater(l: Int32, r: Int32) -> Bool
+    native func OperatorMultiply(l: Int32, r: Int32) -> Int32
+    native func OperatorMultiply(l: Float, r: Float) -> Float
+    native func OperatorAdd(l: Float, r: Float) -> Float
+"#;
+
+#[test]
+fn recompile_predef() {
+    // predef.redscripts only defines the primitive types, it has no function bodies to recompile
+    let pool = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap().pool;
+    assert_recompiles(&pool);
+}
+
+#[test]
+fn recompile_compiled_functions() {
+    let pool = compiled(&[SOURCES, OPERATORS]);
+    assert!(assert_recompiles(&pool) > 0);
+}
+
+/// Runs the harness over a bundle from the game, the path is taken from the `REDSCRIPT_BUNDLE` variable.
+#[test]
+#[ignore]
+fn recompile_game_bundle() {
+    let path = env::var("REDSCRIPT_BUNDLE").expect("REDSCRIPT_BUNDLE should point to a bundle");
+    let bytes = fs::read(path).unwrap();
+    let pool = ScriptBundle::load(&mut Cursor::new(bytes)).unwrap().pool;
+    assert!(assert_recompiles(&pool) > 0);
+}
+
+/// Decompiles every function of the pool, compiles it back as a replacement of itself
+/// and checks that the bytecode is equivalent to the original. Returns the number of functions checked.
+fn assert_recompiles(pool: &ConstantPool) -> usize {
+    let mut files = Files::new();
+    let mut functions = vec![];
+
+    for entry in pool.definitions() {
+        let (idx, def) = entry.unwrap();
+        match &def.value {
+            AnyDefinition::Function(fun) if fun.flags.has_body() => {}
+            _ => continue,
+        }
+        let name = pool.def_name(idx).unwrap();
+        let mut source = Vec::new();
+        if def.parent.is_undefined() {
+            writeln!(source, "@replaceGlobal()").unwrap();
+        } else {
+            writeln!(source, "@replaceMethod({})", pool.def_name(def.parent).unwrap()).unwrap();
+        }
+        write_definition(&mut source, def, pool, 0, OutputMode::Recompilable)
+            .unwrap_or_else(|err| panic!("failed to decompile {}: {}", name, err));
+
+        files.add(PathBuf::from(name.as_str()), String::from_utf8(source).unwrap());
+        functions.push(idx.cast());
+    }
+
+    let mut recompiled = pool.clone();
+    let diagnostics = CompilationUnit::new(&mut recompiled)
+        .unwrap()
+        .compile_files(&files)
+        .unwrap();
+    let errors: Vec<_> = diagnostics.iter().filter(|diagnostic| diagnostic.is_fatal()).collect();
+    assert!(
+        errors.is_empty(),
+        "{:?}\n{}",
+        errors,
+        files.files().map(File::source).join("\n")
+    );
+
+    for idx in &functions {
+        let name = pool.def_name(*idx).unwrap();
+        let original = (pool, pool.function(*idx).unwrap());
+        let recompiled = (&recompiled, recompiled.function(*idx).unwrap());
+        assert_equivalent(&name, original, recompiled);
+    }
+    functions.len()
+}
+
+/// Compares the code of two functions, locals are matched by the order of their use
+/// and types by name, since the compiler can end up creating a type more than once.
+fn assert_equivalent(name: &str, original: (&ConstantPool, &Function), recompiled: (&ConstantPool, &Function)) {
+    let (original_pool, original) = original;
+    let (recompiled_pool, recompiled) = recompiled;
+    let original_code = &original.code.0;
+    let recompiled_code = &recompiled.code.0;
+    let mut locals = HashMap::new();
+    let mut reverse_locals = HashMap::new();
+
+    let matches = original_code.len() == recompiled_code.len()
+        && original_code
+            .iter()
+            .zip(recompiled_code)
+            .all(|(lhs, rhs)| match (lhs, rhs) {
+                (Instr::Local(lhs), Instr::Local(rhs)) => {
+                    *locals.entry(*lhs).or_insert(*rhs) == *rhs && *reverse_locals.entry(*rhs).or_insert(*lhs) == *lhs
+                }
+                (Instr::Param(lhs), Instr::Param(rhs)) => {
+                    param_position(original, *lhs) == param_position(recompiled, *rhs)
+                }
+                (lhs, rhs) if mem::discriminant(lhs) == mem::discriminant(rhs) => {
+                    match (type_operand(lhs), type_operand(rhs)) {
+                        (Some(lhs), Some(rhs)) => {
+                            original_pool.def_name(lhs).unwrap() == recompiled_pool.def_name(rhs).unwrap()
+                        }
+                        _ => lhs == rhs,
+                    }
+                }
+                _ => false,
+            });
+    assert!(
+        matches,
+        "bytecode of {} differs:\n{:?}\n{:?}",
+        name, original_code, recompiled_code
+    );
+}
+
+fn type_operand(instr: &Instr<Offset>) -> Option<PoolIndex<Type>> {
+    match instr {
+        Instr::Switch(type_, _) | Instr::EnumToI32(type_, _) | Instr::I32ToEnum(type_, _) => Some(*type_),
+        Instr::Equals(type_)
+        | Instr::NotEquals(type_)
+        | Instr::ArrayClear(type_)
+        | Instr::ArraySize(type_)
+        | Instr::ArrayResize(type_)
+        | Instr::ArrayFindFirst(type_)
+        | Instr::ArrayFindFirstFast(type_)
+        | Instr::ArrayFindLast(type_)
+        | Instr::ArrayFindLastFast(type_)
+        | Instr::ArrayContains(type_)
+        | Instr::ArrayContainsFast(type_)
+        | Instr::ArrayCount(type_)
+        | Instr::ArrayCountFast(type_)
+        | Instr::ArrayPush(type_)
+        | Instr::ArrayPop(type_)
+        | Instr::ArrayInsert(type_)
+        | Instr::ArrayRemove(type_)
+        | Instr::ArrayRemoveFast(type_)
+        | Instr::ArrayGrow(type_)
+        | Instr::ArrayErase(type_)
+        | Instr::ArrayEraseFast(type_)
+        | Instr::ArrayLast(type_)
+        | Instr::ArrayElement(type_)
+        | Instr::StaticArraySize(type_)
+        | Instr::StaticArrayFindFirst(type_)
+        | Instr::StaticArrayFindFirstFast(type_)
+        | Instr::StaticArrayFindLast(type_)
+        | Instr::StaticArrayFindLastFast(type_)
+        | Instr::StaticArrayContains(type_)
+        | Instr::StaticArrayContainsFast(type_)
+        | Instr::StaticArrayCount(type_)
+        | Instr::StaticArrayCountFast(type_)
+        | Instr::StaticArrayLast(type_)
+        | Instr::StaticArrayElement(type_)
+        | Instr::ToString(type_)
+        | Instr::ToVariant(type_)
+        | Instr::FromVariant(type_)
+        | Instr::AsRef(type_)
+        | Instr::Deref(type_) => Some(*type_),
+        _ => None,
+    }
+}
+
+fn param_position(fun: &Function, idx: PoolIndex<Parameter>) -> Option<usize> {
+    fun.parameters.iter().position(|param| *param == idx)
+}
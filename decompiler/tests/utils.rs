@@ -0,0 +1,38 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use redscript::bundle::{ConstantPool, ScriptBundle};
+use redscript_compiler::source_map::Files;
+use redscript_compiler::unit::CompilationUnit;
+
+pub const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+pub const OPERATORS: &str = "
+    native func OperatorAssignAdd(out l: Int32, r: Int32) -> Int32
+    native func OperatorLess(l: Int32, r: Int32) -> Bool
+    native func OperatorAdd(a: ref<Script_RefString>, b: ref<Script_RefString>) -> String
+    native func OperatorSubtract(l: Int32, r: Int32) -> Int32
+    native func Log(str: String)
+    native func Consume(arr: array<Int32>)
+
+    class Script_RefString {}
+";
+
+pub fn compiled(sources: &[&str]) -> ConstantPool {
+    let mut files = Files::new();
+    for (i, source) in sources.iter().enumerate() {
+        files.add(PathBuf::from(format!("test{}.reds", i)), source.to_string());
+    }
+    let mut bundle = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+    let diagnostics = CompilationUnit::new(&mut bundle.pool)
+        .unwrap()
+        .compile_files(&files)
+        .unwrap();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    // function flags are only updated when the bundle is saved
+    let mut saved = Cursor::new(Vec::new());
+    bundle.save(&mut saved).unwrap();
+    saved.set_position(0);
+    ScriptBundle::load(&mut saved).unwrap().pool
+}
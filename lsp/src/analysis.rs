@@ -0,0 +1,663 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use lsp_types::{CompletionItem, CompletionItemKind};
+use redscript::ast::{Expr, Ident, Pos, Span};
+use redscript::bundle::{ConstantPool, PoolIndex};
+use redscript::definition::{
+    AnyDefinition, BitField, Class, Definition, Enum, Field, Function, Local, Parameter, Type
+};
+use redscript_compiler::parser::{parse_file_with_recovery, MemberSource, SourceEntry, SourceModule};
+use redscript_compiler::scope::{Reference, Scope, TypeId, Value};
+use redscript_compiler::source_map::{File, FilePos, Files, SourceLoc};
+use redscript_compiler::symbol::{ModulePath, Symbol};
+use redscript_compiler::typechecker::{collect_supertypes, type_of, Callable, Member, TypedAst};
+use redscript_compiler::unit::{CompilationUnit, CompiledFunction, Diagnostic};
+
+pub struct Analysis {
+    files: Files,
+    pool: ConstantPool,
+    functions: Vec<CompiledFunction>,
+    declarations: HashMap<PoolIndex<Definition>, Span>,
+    diagnostics: Vec<Diagnostic>,
+    has_syntax_errors: bool,
+}
+
+impl Analysis {
+    pub fn run(base: &ConstantPool, sources: Vec<(PathBuf, String)>) -> Analysis {
+        let mut files = Files::new();
+        for (path, source) in sources {
+            files.add(path, source);
+        }
+
+        let mut diagnostics = vec![];
+        let mut modules = vec![];
+        for file in files.files() {
+            let (module, errors) = parse_file_with_recovery(file);
+            for err in errors {
+                match Diagnostic::from_error(err) {
+                    Ok(diagnostic) => diagnostics.push(diagnostic),
+                    Err(other) => log::error!("Unexpected error during parsing: {}", other),
+                }
+            }
+            modules.push(module);
+        }
+        let has_syntax_errors = !diagnostics.is_empty();
+        let declarations = DeclarationSources::collect(&modules);
+
+        // the clone shares the definitions of the base pool until the compiler modifies them
+        let mut pool = base.clone();
+        let res = CompilationUnit::new(&mut pool).and_then(|unit| unit.typecheck(modules, false, true));
+        let functions = match res {
+            Ok((functions, diags)) => {
+                diagnostics.extend(diags);
+                functions
+            }
+            Err(err) => {
+                match Diagnostic::from_error(err) {
+                    Ok(diagnostic) => diagnostics.push(diagnostic),
+                    Err(other) => log::error!("Unexpected error during analysis: {}", other),
+                }
+                vec![]
+            }
+        };
+        let declarations = declarations.resolve(&pool);
+
+        Analysis {
+            files,
+            pool,
+            functions,
+            declarations,
+            diagnostics,
+            has_syntax_errors,
+        }
+    }
+
+    pub fn files(&self) -> &Files {
+        &self.files
+    }
+
+    pub fn has_syntax_errors(&self) -> bool {
+        self.has_syntax_errors
+    }
+
+    pub fn diagnostics(&self) -> impl Iterator<Item = (SourceLoc<'_>, &Diagnostic)> {
+        self.diagnostics
+            .iter()
+            .filter_map(move |diagnostic| Some((self.files.lookup(diagnostic.span())?, diagnostic)))
+    }
+
+    pub fn hover(&self, path: &Path, pos: FilePos) -> Option<String> {
+        let pos = self.position(path, pos)?;
+        let (fun, expr) = self.expr_at(pos)?;
+        let pool = &self.pool;
+        let scope = &fun.scope;
+
+        match expr {
+            Expr::Ident(Reference::Value(Value::Local(idx)), _) | Expr::Declare(idx, _, _, _) => {
+                self.describe_local(*idx, scope)
+            }
+            Expr::Ident(Reference::Value(Value::Parameter(idx)), _) => self.describe_param(*idx, scope),
+            Expr::Ident(Reference::Symbol(symbol), _) => self.describe_symbol(symbol, scope),
+            Expr::Call(Callable::Function(idx), _, _, _) | Expr::MethodCall(_, idx, _, _) => {
+                self.describe_function(*idx, scope)
+            }
+            Expr::Member(_, Member::ClassField(idx) | Member::StructField(idx), _) => self.describe_field(*idx, scope),
+            Expr::Member(_, Member::EnumMember(enum_idx, member_idx), _) => {
+                let value = pool.enum_value(*member_idx).ok()?;
+                Some(format!(
+                    "{}.{} = {}",
+                    pool.def_name(*enum_idx).ok()?,
+                    pool.def_name(*member_idx).ok()?,
+                    value
+                ))
+            }
+            Expr::New(TypeId::Class(idx) | TypeId::Struct(idx), _, _) => self.describe_class(*idx),
+            other => match type_of(other, scope, pool).ok()? {
+                TypeId::Void => None,
+                type_ => Some(type_.pretty(pool).ok()?.to_string()),
+            },
+        }
+    }
+
+    pub fn definition(&self, path: &Path, pos: FilePos) -> Option<SourceLoc<'_>> {
+        let pos = self.position(path, pos)?;
+        let (fun, expr) = self.expr_at(pos)?;
+
+        let span = match expr {
+            Expr::Ident(Reference::Value(Value::Local(idx)), _) => {
+                let mut res = None;
+                for expr in &fun.code.exprs {
+                    visit(expr, &mut |expr| match expr {
+                        Expr::Declare(local, _, _, span) if local == idx => res = Some(*span),
+                        Expr::ForIn(local, _, _, span) if local == idx => res = Some(*span),
+                        _ => {}
+                    });
+                }
+                res?
+            }
+            Expr::Ident(Reference::Value(Value::Parameter(_)), _) => fun.span,
+            Expr::Ident(Reference::Symbol(symbol), _) => match symbol {
+                Symbol::Class(idx, _) | Symbol::Struct(idx, _) => *self.declarations.get(&idx.cast())?,
+                Symbol::Enum(idx) => *self.declarations.get(&idx.cast())?,
+                Symbol::BitField(idx) => *self.declarations.get(&idx.cast())?,
+                Symbol::Functions(funs) => funs.iter().find_map(|(idx, _)| self.function_span(*idx))?,
+            },
+            Expr::Call(Callable::Function(idx), _, _, _) | Expr::MethodCall(_, idx, _, _) => {
+                self.function_span(*idx)?
+            }
+            Expr::Member(_, Member::ClassField(idx) | Member::StructField(idx), _) => {
+                *self.declarations.get(&idx.cast())?
+            }
+            Expr::Member(_, Member::EnumMember(enum_idx, _), _) => *self.declarations.get(&enum_idx.cast())?,
+            Expr::New(TypeId::Class(idx) | TypeId::Struct(idx), _, _) => *self.declarations.get(&idx.cast())?,
+            _ => None?,
+        };
+        self.files.lookup(span)
+    }
+
+    pub fn completions(&self, path: &Path, pos: FilePos, line_prefix: &str) -> Vec<CompletionItem> {
+        let (pos, fun) = match self
+            .position(path, pos)
+            .and_then(|pos| Some((pos, self.function_at(pos)?)))
+        {
+            Some(res) => res,
+            None => return vec![],
+        };
+        let scope = &fun.scope;
+
+        let partial = line_prefix.trim_end_matches(is_ident_char);
+        if let Some(receiver) = partial.strip_suffix('.') {
+            let receiver = &receiver[receiver.trim_end_matches(is_ident_char).len()..];
+            return self.member_completions(receiver, fun, pos).unwrap_or_default();
+        }
+
+        let mut items = vec![];
+        for (name, local, _) in self.locals_before(fun, pos) {
+            items.push(completion(
+                &name,
+                CompletionItemKind::VARIABLE,
+                self.describe_local(local, scope),
+            ));
+        }
+        if let Ok(function) = self.pool.function(fun.index) {
+            for param in &function.parameters {
+                if let Ok(name) = self.pool.def_name(*param) {
+                    let detail = self.describe_param(*param, scope);
+                    items.push(completion(name.as_str(), CompletionItemKind::VARIABLE, detail));
+                }
+            }
+        }
+        if let Some(this) = scope.this {
+            items.extend(self.class_members(this, scope));
+        }
+        for (name, symbol) in scope.symbols() {
+            let kind = match symbol {
+                Symbol::Class(_, _) => CompletionItemKind::CLASS,
+                Symbol::Struct(_, _) => CompletionItemKind::STRUCT,
+                Symbol::Enum(_) | Symbol::BitField(_) => CompletionItemKind::ENUM,
+                Symbol::Functions(_) => CompletionItemKind::FUNCTION,
+            };
+            items.push(completion(name.as_ref(), kind, self.describe_symbol(symbol, scope)));
+        }
+        items
+    }
+
+    fn member_completions(&self, receiver: &str, fun: &CompiledFunction, pos: Pos) -> Option<Vec<CompletionItem>> {
+        let pool = &self.pool;
+        let scope = &fun.scope;
+
+        let type_ = match receiver {
+            "this" => TypeId::Class(scope.this?),
+            "super" => TypeId::Class(pool.class(scope.this?).ok()?.base),
+            name => {
+                let local = self
+                    .locals_before(fun, pos)
+                    .into_iter()
+                    .rev()
+                    .find(|(local_name, _, _)| local_name == name);
+                let param = pool
+                    .function(fun.index)
+                    .ok()?
+                    .parameters
+                    .iter()
+                    .find(|param| pool.def_name(**param).map(|n| n.as_str() == name).unwrap_or(false));
+
+                if let Some((_, idx, _)) = local {
+                    scope.resolve_type_from_pool(pool.local(idx).ok()?.type_, pool).ok()?
+                } else if let Some(idx) = param {
+                    scope
+                        .resolve_type_from_pool(pool.parameter(*idx).ok()?.type_, pool)
+                        .ok()?
+                } else {
+                    match scope.resolve_symbol(Ident::new(name.to_owned())).ok()? {
+                        Symbol::Class(idx, _) => TypeId::Class(idx),
+                        Symbol::Struct(idx, _) => TypeId::Struct(idx),
+                        Symbol::Enum(idx) => TypeId::Enum(idx),
+                        Symbol::BitField(idx) => TypeId::BitField(idx),
+                        Symbol::Functions(_) => None?,
+                    }
+                }
+            }
+        };
+
+        let items = match type_.unwrapped() {
+            TypeId::Class(idx) | TypeId::Struct(idx) => self.class_members(*idx, scope),
+            TypeId::Enum(idx) => pool
+                .enum_(*idx)
+                .ok()?
+                .members
+                .iter()
+                .filter_map(|member| {
+                    let name = pool.def_name(*member).ok()?;
+                    Some(completion(name.as_str(), CompletionItemKind::ENUM_MEMBER, None))
+                })
+                .collect(),
+            _ => vec![],
+        };
+        Some(items)
+    }
+
+    fn class_members(&self, class_idx: PoolIndex<Class>, scope: &Scope) -> Vec<CompletionItem> {
+        let pool = &self.pool;
+        let mut seen = HashSet::new();
+        let mut items = vec![];
+
+        for class_idx in collect_supertypes(class_idx, pool)
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+        {
+            let class = match pool.class(class_idx) {
+                Ok(class) => class,
+                Err(_) => continue,
+            };
+            for field in &class.fields {
+                if let Ok(name) = pool.def_name(*field) {
+                    if seen.insert(name.clone()) {
+                        let detail = self.describe_field(*field, scope);
+                        items.push(completion(name.as_str(), CompletionItemKind::FIELD, detail));
+                    }
+                }
+            }
+            for fun in &class.functions {
+                if let Ok(name) = pool.def_name(*fun) {
+                    if seen.insert(name.clone()) {
+                        let short_name = name.split(';').next().unwrap_or(name.as_str());
+                        let detail = self.describe_function(*fun, scope);
+                        items.push(completion(short_name, CompletionItemKind::METHOD, detail));
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    fn locals_before(&self, fun: &CompiledFunction, pos: Pos) -> Vec<(String, PoolIndex<Local>, Span)> {
+        let mut locals = vec![];
+        for expr in &fun.code.exprs {
+            visit(expr, &mut |expr| match expr {
+                Expr::Declare(local, _, _, span) | Expr::ForIn(local, _, _, span) if span.low < pos => {
+                    locals.push((*local, *span));
+                }
+                _ => {}
+            });
+        }
+        locals
+            .into_iter()
+            .filter_map(|(local, span)| Some((self.local_name(local)?, local, span)))
+            .collect()
+    }
+
+    fn local_name(&self, idx: PoolIndex<Local>) -> Option<String> {
+        let name = self.pool.def_name(idx).ok()?;
+        Some(name.split('$').next()?.to_owned())
+    }
+
+    fn describe_local(&self, idx: PoolIndex<Local>, scope: &Scope) -> Option<String> {
+        let type_ = self.pretty_type(self.pool.local(idx).ok()?.type_, scope)?;
+        Some(format!("let {}: {}", self.local_name(idx)?, type_))
+    }
+
+    fn describe_param(&self, idx: PoolIndex<Parameter>, scope: &Scope) -> Option<String> {
+        let type_ = self.pretty_type(self.pool.parameter(idx).ok()?.type_, scope)?;
+        Some(format!("{}: {}", self.pool.def_name(idx).ok()?, type_))
+    }
+
+    fn describe_field(&self, idx: PoolIndex<Field>, scope: &Scope) -> Option<String> {
+        let def = self.pool.definition(idx).ok()?;
+        let type_ = self.pretty_type(self.pool.field(idx).ok()?.type_, scope)?;
+        Some(format!(
+            "let {}.{}: {}",
+            self.pool.def_name(def.parent).ok()?,
+            self.pool.names.get(def.name).ok()?,
+            type_
+        ))
+    }
+
+    fn describe_function(&self, idx: PoolIndex<Function>, scope: &Scope) -> Option<String> {
+        let pool = &self.pool;
+        let def = pool.definition(idx).ok()?;
+        let fun = pool.function(idx).ok()?;
+        let name = pool.names.get(def.name).ok()?;
+        let name = name.split(';').next()?;
+
+        let mut params = vec![];
+        for param in &fun.parameters {
+            params.push(self.describe_param(*param, scope)?);
+        }
+        let qualified = if def.parent.is_undefined() {
+            name.to_owned()
+        } else {
+            format!("{}.{}", pool.def_name(def.parent).ok()?, name)
+        };
+        let return_type = match fun.return_type {
+            Some(type_) => format!(" -> {}", self.pretty_type(type_, scope)?),
+            None => String::new(),
+        };
+        Some(format!("func {}({}){}", qualified, params.join(", "), return_type))
+    }
+
+    fn describe_class(&self, idx: PoolIndex<Class>) -> Option<String> {
+        let class = self.pool.class(idx).ok()?;
+        let keyword = if class.flags.is_struct() { "struct" } else { "class" };
+        let name = self.pool.def_name(idx).ok()?;
+        if class.base.is_undefined() {
+            Some(format!("{} {}", keyword, name))
+        } else {
+            Some(format!(
+                "{} {} extends {}",
+                keyword,
+                name,
+                self.pool.def_name(class.base).ok()?
+            ))
+        }
+    }
+
+    fn describe_enum(&self, idx: PoolIndex<Enum>) -> Option<String> {
+        Some(format!("enum {}", self.pool.def_name(idx).ok()?))
+    }
+
+    fn describe_bit_field(&self, idx: PoolIndex<BitField>) -> Option<String> {
+        Some(format!("bitfield {}", self.pool.def_name(idx).ok()?))
+    }
+
+    fn describe_symbol(&self, symbol: &Symbol, scope: &Scope) -> Option<String> {
+        match symbol {
+            Symbol::Class(idx, _) | Symbol::Struct(idx, _) => self.describe_class(*idx),
+            Symbol::Enum(idx) => self.describe_enum(*idx),
+            Symbol::BitField(idx) => self.describe_bit_field(*idx),
+            Symbol::Functions(funs) => {
+                let overloads: Vec<String> = funs
+                    .iter()
+                    .filter_map(|(idx, _)| self.describe_function(*idx, scope))
+                    .collect();
+                Some(overloads.join("\n"))
+            }
+        }
+    }
+
+    fn pretty_type(&self, idx: PoolIndex<Type>, scope: &Scope) -> Option<String> {
+        let type_ = scope.resolve_type_from_pool(idx, &self.pool).ok()?;
+        Some(type_.pretty(&self.pool).ok()?.to_string())
+    }
+
+    fn function_span(&self, idx: PoolIndex<Function>) -> Option<Span> {
+        self.functions
+            .iter()
+            .find(|fun| fun.index == idx)
+            .map(|fun| fun.span)
+            .or_else(|| self.declarations.get(&idx.cast()).cloned())
+    }
+
+    fn file(&self, path: &Path) -> Option<&File> {
+        self.files.files().find(|file| file.path() == path)
+    }
+
+    fn position(&self, path: &Path, pos: FilePos) -> Option<Pos> {
+        self.file(path)?.lookup_pos(pos)
+    }
+
+    fn function_at(&self, pos: Pos) -> Option<&CompiledFunction> {
+        self.functions.iter().find(|fun| fun.span.contains(pos))
+    }
+
+    fn expr_at(&self, pos: Pos) -> Option<(&CompiledFunction, &Expr<TypedAst>)> {
+        let fun = self.function_at(pos)?;
+        let expr = fun.code.exprs.iter().find_map(|expr| innermost(expr, pos))?;
+        Some((fun, expr))
+    }
+}
+
+struct DeclarationSources {
+    types: Vec<TypeSource>,
+}
+
+struct TypeSource {
+    name: String,
+    span: Span,
+    members: Vec<(Ident, Span)>,
+}
+
+impl DeclarationSources {
+    fn collect(modules: &[SourceModule]) -> Self {
+        let mut types = vec![];
+        for module in modules {
+            let path = module.path.clone().unwrap_or(ModulePath::EMPTY);
+            for entry in &module.entries {
+                match entry {
+                    SourceEntry::Class(class) => {
+                        let members = class
+                            .members
+                            .iter()
+                            .map(|member| match member {
+                                MemberSource::Function(fun) => (fun.declaration.name.clone(), fun.span),
+                                MemberSource::Field(field) => (field.declaration.name.clone(), field.declaration.span),
+                            })
+                            .collect();
+                        let name = path.with_child(class.name.clone()).render().to_string();
+                        types.push(TypeSource {
+                            name,
+                            span: class.span,
+                            members,
+                        });
+                    }
+                    SourceEntry::Enum(enum_) => {
+                        let name = path.with_child(enum_.name.clone()).render().to_string();
+                        types.push(TypeSource {
+                            name,
+                            span: enum_.span,
+                            members: vec![],
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        DeclarationSources { types }
+    }
+
+    fn resolve(self, pool: &ConstantPool) -> HashMap<PoolIndex<Definition>, Span> {
+        let mut declarations = HashMap::new();
+        let roots: Vec<_> = pool
+            .roots()
+            .filter_map(|entry| {
+                entry
+                    .map_err(|err| log::error!("Failed to load a definition: {}", err))
+                    .ok()
+            })
+            .collect();
+        for TypeSource { name, span, members } in self.types {
+            let name_idx = match pool.names.get_index(&name) {
+                Ok(idx) => idx,
+                Err(_) => continue,
+            };
+            let found = roots.iter().find(|(_, def)| {
+                def.name == name_idx && matches!(def.value, AnyDefinition::Class(_) | AnyDefinition::Enum(_))
+            });
+            if let Some(&(idx, def)) = found {
+                declarations.insert(idx, span);
+
+                if let AnyDefinition::Class(class) = &def.value {
+                    let children = class
+                        .fields
+                        .iter()
+                        .map(|idx| idx.cast())
+                        .chain(class.functions.iter().map(|idx| idx.cast()));
+                    for child in children {
+                        let child_name = match pool.def_name(child) {
+                            Ok(name) => name,
+                            Err(_) => continue,
+                        };
+                        let short_name = child_name.split(';').next().unwrap_or(child_name.as_str());
+                        if let Some((_, span)) = members.iter().find(|(name, _)| name.as_ref() == short_name) {
+                            declarations.insert(child, *span);
+                        }
+                    }
+                }
+            }
+        }
+        declarations
+    }
+}
+
+fn completion(label: &str, kind: CompletionItemKind, detail: Option<String>) -> CompletionItem {
+    CompletionItem {
+        label: label.to_owned(),
+        kind: Some(kind),
+        detail,
+        ..CompletionItem::default()
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn innermost(expr: &Expr<TypedAst>, pos: Pos) -> Option<&Expr<TypedAst>> {
+    if !expr.span().contains(pos) {
+        return None;
+    }
+    children(expr)
+        .into_iter()
+        .find_map(|child| innermost(child, pos))
+        .or(Some(expr))
+}
+
+fn visit<'a, F: FnMut(&'a Expr<TypedAst>)>(expr: &'a Expr<TypedAst>, f: &mut F) {
+    f(expr);
+    for child in children(expr) {
+        visit(child, f);
+    }
+}
+
+fn children(expr: &Expr<TypedAst>) -> Vec<&Expr<TypedAst>> {
+    match expr {
+        Expr::ArrayLit(exprs, _, _) => exprs.iter().collect(),
+        Expr::InterpolatedString(_, parts, _) => parts.iter().map(|(expr, _)| expr).collect(),
+        Expr::Declare(_, _, init, _) => init.iter().map(AsRef::as_ref).collect(),
+        Expr::Cast(_, expr, _) => vec![expr],
+        Expr::Assign(lhs, rhs, _) => vec![lhs, rhs],
+        Expr::Call(_, _, args, _) => args.iter().collect(),
+        Expr::MethodCall(context, _, args, _) => std::iter::once(context.as_ref()).chain(args).collect(),
+        Expr::Member(context, _, _) => vec![context],
+        Expr::ArrayElem(array, index, _) => vec![array, index],
+        Expr::New(_, args, _) => args.iter().collect(),
+        Expr::Return(expr, _) => expr.iter().map(AsRef::as_ref).collect(),
+        Expr::Seq(seq) => seq.exprs.iter().collect(),
+        Expr::Switch(matched, cases, default, _) => std::iter::once(matched.as_ref())
+            .chain(
+                cases
+                    .iter()
+                    .flat_map(|case| std::iter::once(&case.matcher).chain(&case.body.exprs)),
+            )
+            .chain(default.iter().flat_map(|seq| &seq.exprs))
+            .collect(),
+        Expr::If(cond, if_, else_, _) => std::iter::once(cond.as_ref())
+            .chain(&if_.exprs)
+            .chain(else_.iter().flat_map(|seq| &seq.exprs))
+            .collect(),
+        Expr::Conditional(cond, true_, false_, _) => vec![cond, true_, false_],
+        Expr::While(cond, body, _) => std::iter::once(cond.as_ref()).chain(&body.exprs).collect(),
+        Expr::ForIn(_, array, body, _) => std::iter::once(array.as_ref()).chain(&body.exprs).collect(),
+        Expr::BinOp(lhs, rhs, _, _) => vec![lhs, rhs],
+        Expr::UnOp(expr, _, _) => vec![expr],
+        Expr::Ident(_, _)
+        | Expr::Constant(_, _)
+        | Expr::Goto(_, _)
+        | Expr::This(_)
+        | Expr::Super(_)
+        | Expr::Break(_)
+        | Expr::Continue(_)
+        | Expr::Null(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use redscript::bundle::ScriptBundle;
+
+    use super::*;
+
+    const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+    const SOURCE: &str = "
+class Counter {
+  let count: Int32;
+
+  func Increment(by: Int32) -> Int32 {
+    let next = by;
+    this.count = next;
+    return next;
+  }
+}
+
+func Run(counter: ref<Counter>) {
+  counter.Increment(1);
+}
+";
+
+    fn analyze(source: &str) -> Analysis {
+        let bundle = ScriptBundle::load(&mut Cursor::new(PREDEF)).unwrap();
+        Analysis::run(&bundle.pool, vec![(PathBuf::from("test.reds"), source.to_owned())])
+    }
+
+    #[test]
+    fn hover_and_definition() {
+        let analysis = analyze(SOURCE);
+        let path = Path::new("test.reds");
+        assert_eq!(analysis.diagnostics().count(), 0);
+
+        let hover = analysis.hover(path, FilePos { line: 7, col: 12 });
+        assert_eq!(hover.as_deref(), Some("let next: Int32"));
+
+        let hover = analysis.hover(path, FilePos { line: 12, col: 12 });
+        assert_eq!(hover.as_deref(), Some("func Counter.Increment(by: Int32) -> Int32"));
+
+        let loc = analysis.definition(path, FilePos { line: 12, col: 12 }).unwrap();
+        assert_eq!(loc.start, FilePos { line: 4, col: 2 });
+
+        let loc = analysis.definition(path, FilePos { line: 7, col: 12 }).unwrap();
+        assert_eq!(loc.start, FilePos { line: 5, col: 4 });
+    }
+
+    #[test]
+    fn member_completions() {
+        let analysis = analyze(SOURCE);
+        let path = Path::new("test.reds");
+
+        let items = analysis.completions(path, FilePos { line: 12, col: 10 }, "  counter.");
+        let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+        assert!(labels.contains(&"count"));
+        assert!(labels.contains(&"Increment"));
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let analysis = analyze("func Broken( {}");
+        assert!(analysis.has_syntax_errors());
+        assert_eq!(analysis.diagnostics().count(), 1);
+    }
+}
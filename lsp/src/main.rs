@@ -0,0 +1,77 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use gumdrop::Options;
+use lsp_server::Connection;
+use lsp_types::{
+    CompletionOptions, HoverProviderCapability, InitializeParams, OneOf, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind,
+};
+use redscript::bundle::ScriptBundle;
+
+use crate::server::Server;
+
+mod analysis;
+mod server;
+
+#[derive(Debug, Options)]
+struct LspOpts {
+    #[options(help = "print help message")]
+    help: bool,
+    #[options(required, short = "b", help = "redscript bundle file to use")]
+    bundle: PathBuf,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    setup_logger();
+
+    let opts = LspOpts::parse_args_default_or_exit();
+    let bundle = load_bundle(&opts.bundle)?;
+
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_owned()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    #[allow(deprecated)]
+    let root = params
+        .workspace_folders
+        .and_then(|folders| folders.into_iter().next())
+        .map(|folder| folder.uri)
+        .or(params.root_uri)
+        .and_then(|uri| uri.to_file_path().ok());
+
+    log::info!("Starting the language server in {:?}", root);
+    Server::new(bundle.pool, root).run(&connection)?;
+
+    drop(connection);
+    io_threads.join()?;
+    log::info!("Language server stopped");
+    Ok(())
+}
+
+fn setup_logger() {
+    // stdout is reserved for the protocol
+    fern::Dispatch::new()
+        .format(|out, message, rec| {
+            out.finish(format_args!("[{}] {}", rec.level(), message));
+        })
+        .level(log::LevelFilter::Info)
+        .chain(io::stderr())
+        .apply()
+        .expect("Failed to initialize the logger");
+}
+
+fn load_bundle(path: &Path) -> Result<ScriptBundle, io::Error> {
+    let bytes = std::fs::read(path)?;
+    ScriptBundle::load(&mut io::Cursor::new(bytes))
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Notification as LspNotification, PublishDiagnostics, ShowMessage,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as LspRequest};
+use lsp_types::{
+    CompletionParams, CompletionResponse, Diagnostic, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, LanguageString, Location, MarkedString, MessageType, NumberOrString, Position,
+    PublishDiagnosticsParams, Range, ShowMessageParams, Url,
+};
+use redscript::bundle::ConstantPool;
+use redscript_compiler::source_map::{FilePos, Files, SourceFilter, SourceLoc};
+use redscript_compiler::unit::Severity;
+
+use crate::analysis::Analysis;
+
+pub struct Server {
+    pool: ConstantPool,
+    root: Option<PathBuf>,
+    documents: HashMap<PathBuf, String>,
+    analysis: Option<Analysis>,
+    last_parsed: Option<Analysis>,
+}
+
+impl Server {
+    pub fn new(pool: ConstantPool, root: Option<PathBuf>) -> Self {
+        Server {
+            pool,
+            root,
+            documents: HashMap::new(),
+            analysis: None,
+            last_parsed: None,
+        }
+    }
+
+    pub fn run(mut self, connection: &Connection) -> Result<(), Box<dyn Error>> {
+        self.analyze(connection)?;
+
+        for msg in &connection.receiver {
+            match msg {
+                Message::Request(req) => {
+                    if connection.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(req);
+                    connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    // a single bad notification shouldn't take the whole server down
+                    if let Err(err) = self.handle_notification(notification, connection) {
+                        report_error(&format!("Failed to handle a notification: {}", err), connection)?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, req: Request) -> Response {
+        match req.method.as_str() {
+            HoverRequest::METHOD => Self::respond::<HoverRequest, _>(req, |params| self.hover(params)),
+            GotoDefinition::METHOD => Self::respond::<GotoDefinition, _>(req, |params| self.definition(params)),
+            Completion::METHOD => Self::respond::<Completion, _>(req, |params| self.completion(params)),
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {}", req.method),
+            ),
+        }
+    }
+
+    fn respond<R, F>(req: Request, handler: F) -> Response
+    where
+        R: LspRequest,
+        F: FnOnce(R::Params) -> R::Result,
+    {
+        match serde_json::from_value(req.params) {
+            Ok(params) => Response::new_ok(req.id, handler(params)),
+            Err(err) => Response::new_err(req.id, ErrorCode::InvalidParams as i32, err.to_string()),
+        }
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: Notification,
+        connection: &Connection,
+    ) -> Result<(), Box<dyn Error>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: <DidOpenTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params)?;
+                if let Ok(path) = params.text_document.uri.to_file_path() {
+                    self.documents.insert(path, params.text_document.text);
+                    self.analyze(connection)?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: <DidChangeTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params)?;
+                let path = params.text_document.uri.to_file_path();
+                if let (Ok(path), Some(change)) = (path, params.content_changes.into_iter().last()) {
+                    self.documents.insert(path, change.text);
+                    self.analyze(connection)?;
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                self.analyze(connection)?;
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: <DidCloseTextDocument as LspNotification>::Params =
+                    serde_json::from_value(notification.params)?;
+                if let Ok(path) = params.text_document.uri.to_file_path() {
+                    self.documents.remove(&path);
+                    self.analyze(connection)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn analyze(&mut self, connection: &Connection) -> Result<(), Box<dyn Error>> {
+        let files = self.root.as_ref().map(|root| Files::from_dir(root, SourceFilter::None));
+        let mut sources: HashMap<PathBuf, String> = match files {
+            Some(Ok(files)) => files
+                .files()
+                .map(|file| (file.path().to_path_buf(), file.source().to_owned()))
+                .collect(),
+            Some(Err(err)) => {
+                report_error(&format!("Failed to read the workspace sources: {}", err), connection)?;
+                HashMap::new()
+            }
+            None => HashMap::new(),
+        };
+        for (path, source) in &self.documents {
+            sources.insert(path.clone(), source.clone());
+        }
+        let mut sources: Vec<_> = sources.into_iter().collect();
+        sources.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let analysis = Analysis::run(&self.pool, sources);
+        self.publish_diagnostics(&analysis, connection)?;
+
+        if let Some(previous) = self.analysis.replace(analysis) {
+            if !previous.has_syntax_errors() {
+                self.last_parsed = Some(previous);
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_diagnostics(&self, analysis: &Analysis, connection: &Connection) -> Result<(), Box<dyn Error>> {
+        let mut diagnostics: HashMap<PathBuf, Vec<Diagnostic>> = analysis
+            .files()
+            .files()
+            .map(|file| (file.path().to_path_buf(), vec![]))
+            .collect();
+
+        for (loc, diagnostic) in analysis.diagnostics() {
+            let severity = match diagnostic.severity() {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            };
+            let diagnostic = Diagnostic {
+                range: to_range(&loc),
+                severity: Some(severity),
+                code: Some(NumberOrString::String(diagnostic.code().to_owned())),
+                source: Some("redscript".to_owned()),
+                message: diagnostic.message().to_owned(),
+                ..Diagnostic::default()
+            };
+            diagnostics
+                .entry(loc.file.path().to_path_buf())
+                .or_default()
+                .push(diagnostic);
+        }
+
+        for (path, diagnostics) in diagnostics {
+            if let Ok(uri) = Url::from_file_path(&path) {
+                let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+                let notification = Notification::new(PublishDiagnostics::METHOD.to_owned(), params);
+                connection.sender.send(Message::Notification(notification))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let params = params.text_document_position_params;
+        let path = params.text_document.uri.to_file_path().ok()?;
+        let pos = self.file_pos(&path, params.position);
+        let text = self.analysis.as_ref()?.hover(&path, pos)?;
+
+        let contents = HoverContents::Scalar(MarkedString::LanguageString(LanguageString {
+            language: "redscript".to_owned(),
+            value: text,
+        }));
+        Some(Hover { contents, range: None })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let params = params.text_document_position_params;
+        let path = params.text_document.uri.to_file_path().ok()?;
+        let pos = self.file_pos(&path, params.position);
+        let loc = self.analysis.as_ref()?.definition(&path, pos)?;
+
+        let location = Location::new(Url::from_file_path(loc.file.path()).ok()?, to_range(&loc));
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let params = params.text_document_position;
+        let path = params.text_document.uri.to_file_path().ok()?;
+        let pos = self.file_pos(&path, params.position);
+
+        let line = self.documents.get(&path)?.lines().nth(pos.line).unwrap_or_default();
+        let line_prefix: String = line.chars().take(pos.col).collect();
+
+        // the current document is unlikely to parse while typing, fall back to the last parsed state
+        let items = [&self.analysis, &self.last_parsed]
+            .into_iter()
+            .flatten()
+            .map(|analysis| analysis.completions(&path, pos, &line_prefix))
+            .find(|items| !items.is_empty())
+            .unwrap_or_default();
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn file_pos(&self, path: &Path, pos: Position) -> FilePos {
+        let source = match self.documents.get(path) {
+            Some(source) => Some(source.as_str()),
+            None => self
+                .analysis
+                .as_ref()
+                .and_then(|analysis| analysis.files().files().find(|file| file.path() == path))
+                .map(|file| file.source()),
+        };
+        let line = source.and_then(|source| source.lines().nth(pos.line as usize));
+        to_file_pos(line.unwrap_or_default(), pos)
+    }
+}
+
+fn report_error(message: &str, connection: &Connection) -> Result<(), Box<dyn Error>> {
+    log::error!("{}", message);
+    let params = ShowMessageParams {
+        typ: MessageType::ERROR,
+        message: message.to_owned(),
+    };
+    let notification = Notification::new(ShowMessage::METHOD.to_owned(), params);
+    connection.sender.send(Message::Notification(notification))?;
+    Ok(())
+}
+
+// LSP positions count UTF-16 code units, while the columns of the source map count chars
+fn to_file_pos(line: &str, pos: Position) -> FilePos {
+    let mut units = 0;
+    let col = line
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= pos.character as usize
+        })
+        .count();
+    FilePos {
+        line: pos.line as usize,
+        col,
+    }
+}
+
+fn to_position(source: &str, pos: FilePos) -> Position {
+    let line = source.lines().nth(pos.line).unwrap_or_default();
+    let character: usize = line.chars().take(pos.col).map(char::len_utf16).sum();
+    Position::new(pos.line as u32, character as u32)
+}
+
+fn to_range(loc: &SourceLoc) -> Range {
+    let source = loc.file.source();
+    Range::new(to_position(source, loc.start), to_position(source, loc.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_utf16_positions() {
+        let line = "let s = \"\u{1F600}\u{E9}\"; s";
+        let pos = to_file_pos(line, Position::new(2, 13));
+        assert_eq!(pos, FilePos { line: 2, col: 12 });
+
+        let source = format!("\n\n{}", line);
+        assert_eq!(to_position(&source, pos), Position::new(2, 13));
+    }
+}
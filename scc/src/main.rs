@@ -1,8 +1,9 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::{self, File, OpenOptions};
 use std::io;
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::SystemTime;
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
@@ -10,9 +11,10 @@ use fd_lock::RwLock;
 use redscript::ast::Span;
 use redscript::bundle::ScriptBundle;
 use redscript_compiler::error::Error;
+use redscript_compiler::lint::{Lint, LintConfig};
 use redscript_compiler::source_map::{Files, SourceFilter};
 use redscript_compiler::unit::CompilationUnit;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use time::format_description::well_known::Rfc3339 as Rfc3339Format;
 use time::OffsetDateTime;
 use vmap::Map;
@@ -26,9 +28,10 @@ fn main() -> Result<(), Error> {
             let cache_dir = script_dir.parent().unwrap().join("cache");
             setup_logger(&cache_dir)?;
             let manifest = ScriptManifest::load_with_fallback(&script_dir);
+            let lints = manifest.lint_config();
             let files = Files::from_dir(&script_dir, manifest.source_filter())?;
 
-            match load_scripts(&cache_dir, &files) {
+            match load_scripts(&cache_dir, &files, lints) {
                 Ok(_) => {
                     log::info!("Output successfully saved in {}", cache_dir.display());
                 }
@@ -63,10 +66,11 @@ fn setup_logger(cache_dir: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-fn load_scripts(cache_dir: &Path, files: &Files) -> Result<(), Error> {
+fn load_scripts(cache_dir: &Path, files: &Files, lints: LintConfig) -> Result<(), Error> {
     let bundle_path = cache_dir.join("final.redscripts");
     let backup_path = cache_dir.join("final.redscripts.bk");
     let timestamp_path = cache_dir.join("redscript.ts");
+    let hashes_path = cache_dir.join("redscript.hashes.toml");
 
     let mut ts_lock = RwLock::new(
         OpenOptions::new()
@@ -96,20 +100,30 @@ fn load_scripts(cache_dir: &Path, files: &Files) -> Result<(), Error> {
     let (map, _) = Map::with_options()
         .open(backup_path)
         .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    let hashes = SourceHashes::new(files, map.as_ref());
+    if saved_timestamp == Some(write_timestamp) && SourceHashes::load(&hashes_path).ok().as_ref() == Some(&hashes) {
+        log::info!("No changes in the scripts since the last compilation, skipping");
+        return Ok(());
+    }
+
     let mut bundle = ScriptBundle::load(&mut io::Cursor::new(map.as_ref()))?;
 
-    CompilationUnit::new(&mut bundle.pool)?.compile_and_report(files)?;
+    CompilationUnit::new(&mut bundle.pool)?
+        .with_lints(lints)
+        .compile_and_report(files)?;
 
     let mut file = File::create(&bundle_path)?;
     bundle.save(&mut io::BufWriter::new(&mut file))?;
     file.sync_all()?;
 
     CompileTimestamp::of_cache_file(&file)?.write(ts_file.deref_mut())?;
+    hashes.save(&hashes_path)?;
 
     Ok(())
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct CompileTimestamp {
     nanos: u128,
 }
@@ -138,9 +152,65 @@ impl CompileTimestamp {
     }
 }
 
+/// Hashes of the inputs of the last successful compilation, used to skip it when nothing has changed.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SourceHashes {
+    version: String,
+    bundle: String,
+    files: BTreeMap<String, String>,
+}
+
+impl SourceHashes {
+    fn new(files: &Files, bundle: &[u8]) -> Self {
+        let files = files
+            .files()
+            .map(|file| (file.path().display().to_string(), hash(file.source().as_bytes())))
+            .collect();
+        SourceHashes {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            bundle: hash(bundle),
+            files,
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)?;
+        let hashes =
+            toml::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok(hashes)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents =
+            toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+// the hashes are persisted, so they have to stay the same across builds and toolchains
+fn hash(bytes: &[u8]) -> String {
+    format!("{:08x}", crc32fast::hash(bytes))
+}
+
 #[derive(Debug, Deserialize, Default)]
 struct ScriptManifest {
+    #[serde(default)]
     exclusions: HashSet<String>,
+    #[serde(default)]
+    scc: SccManifest,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct SccManifest {
+    #[serde(default)]
+    lints: LintManifest,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct LintManifest {
+    #[serde(default)]
+    allow: Vec<String>,
 }
 
 impl ScriptManifest {
@@ -163,6 +233,17 @@ impl ScriptManifest {
         })
     }
 
+    pub fn lint_config(&self) -> LintConfig {
+        let mut config = LintConfig::default();
+        for name in &self.scc.lints.allow {
+            match Lint::from_str(name) {
+                Ok(lint) => config = config.allow(lint),
+                Err(_) => log::warn!("Unknown lint in the manifest: {}", name),
+            }
+        }
+        config
+    }
+
     pub fn source_filter(self) -> SourceFilter {
         SourceFilter::Exclude(self.exclusions)
     }
@@ -214,3 +295,70 @@ fn error_message(error: Error, files: &Files, scripts_dir: &Path) -> String {
         str
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREDEF: &[u8] = include_bytes!("../../resources/predef.redscripts");
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scc-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("final.redscripts"), PREDEF).unwrap();
+        dir
+    }
+
+    fn sources(source: &str) -> Files {
+        let mut files = Files::new();
+        files.add("test.reds".into(), source.to_owned());
+        files
+    }
+
+    fn bundle_timestamp(cache_dir: &Path) -> CompileTimestamp {
+        CompileTimestamp::of_cache_file(&File::open(cache_dir.join("final.redscripts")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn skip_compilation_when_nothing_changed() {
+        let dir = cache_dir("skip");
+        let files = sources("func Test() -> Int32 = 1");
+
+        load_scripts(&dir, &files, LintConfig::default()).unwrap();
+        let compiled = bundle_timestamp(&dir);
+        load_scripts(&dir, &files, LintConfig::default()).unwrap();
+        assert_eq!(bundle_timestamp(&dir), compiled);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recompile_when_sources_change() {
+        let dir = cache_dir("invalidate");
+        let hashes_path = dir.join("redscript.hashes.toml");
+
+        load_scripts(&dir, &sources("func Test() -> Int32 = 1"), LintConfig::default()).unwrap();
+        let compiled = bundle_timestamp(&dir);
+        let hashes = SourceHashes::load(&hashes_path).unwrap();
+
+        load_scripts(&dir, &sources("func Test() -> Int32 = 2"), LintConfig::default()).unwrap();
+        assert_ne!(bundle_timestamp(&dir), compiled);
+        assert_ne!(SourceHashes::load(&hashes_path).unwrap(), hashes);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        assert_eq!(hash(b"redscript"), "72ed1f4d");
+    }
+
+    #[test]
+    fn read_lints_from_the_scc_section() {
+        let manifest: ScriptManifest = toml::from_str("[scc.lints]\nallow = [\"unused-local\"]").unwrap();
+        let config = manifest.lint_config();
+        assert!(!config.is_enabled(Lint::UnusedLocal));
+        assert!(config.is_enabled(Lint::UnusedImport));
+    }
+}